@@ -1,6 +1,7 @@
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program;
-use std::convert::Into;
+use anchor_lang::Discriminator;
+use std::convert::{Into, TryInto};
 
 declare_id!("Fg6PaFpoGXkYsidMpWTK6W2BeZ7FEfcYkg476zPFsLnS");
 
@@ -20,7 +21,7 @@ pub enum ErrorCode {
     AlreadyExecuted,
     #[msg("Threshold must be less than or equal to the number of owners.")]
     InvalidThreshold,
-    #[msg("Delay must be less than 30 days.")]
+    #[msg("Delay must be between 0 and 30 days.")]
     InvalidDelay,
     #[msg("Owners changed.")]
     OwnersChanged,
@@ -28,6 +29,124 @@ pub enum ErrorCode {
     BeforeETA,
     #[msg("Unique Owners.")]
     UniqueOwners,
+    #[msg("Too many pending transactions.")]
+    TooManyPending,
+    #[msg("Too many transactions in batch.")]
+    BatchTooLarge,
+    #[msg("Insufficient funds above rent-exemption.")]
+    InsufficientFunds,
+    #[msg("Only the proposer can modify this transaction.")]
+    NotProposer,
+    #[msg("The owner set is unchanged.")]
+    NoOwnerChange,
+    #[msg("Callback program can't be the multisig program.")]
+    InvalidCallback,
+    #[msg("Amount exceeds the owner's remaining allowance.")]
+    AllowanceExceeded,
+    #[msg("There is no pending owner change.")]
+    NoPendingOwnerChange,
+    #[msg("The multisig is still active.")]
+    StillActive,
+    #[msg("Instruction too large for CPI.")]
+    InstructionTooLarge,
+    #[msg("Invalid address seed.")]
+    InvalidSeed,
+    #[msg("Delay is below the minimum required for governance changes.")]
+    DelayBelowMinimum,
+    #[msg("Transaction precondition not met.")]
+    PreconditionNotMet,
+    #[msg("Too many instructions for scoped approval.")]
+    TooManyInstructions,
+    #[msg("Account not referenced by the transaction.")]
+    UnexpectedAccount,
+    #[msg("Execution is paused after an owner change.")]
+    OwnerChangeCooldown,
+    #[msg("Owner proposed a transaction too recently.")]
+    ProposalRateLimited,
+    #[msg("Transaction instructions don't match the expected hash.")]
+    HashMismatch,
+    #[msg("Grouped transactions must be executed together.")]
+    GroupedTransaction,
+    #[msg("Transactions don't form a complete group.")]
+    InvalidGroup,
+    #[msg("Not every transaction in the group is ready.")]
+    GroupNotReady,
+    #[msg("Bump is not the canonical bump.")]
+    InvalidBump,
+    #[msg("Signer is not allowed to execute transactions.")]
+    ExecutorNotAllowed,
+    #[msg("Balance is outside the expected range.")]
+    BalanceAssertionFailed,
+    #[msg("Seed account can't be resolved.")]
+    InvalidSeedSpec,
+    #[msg("Migration is complete.")]
+    MigrationComplete,
+    #[msg("The proposer can't execute their own transaction.")]
+    SeparationOfDutiesViolated,
+    #[msg("The transaction this one depends on hasn't executed.")]
+    DependencyNotExecuted,
+    #[msg("Expected one action hint per instruction.")]
+    InvalidActionHints,
+    #[msg("Transaction belongs to a different multisig.")]
+    MultisigMismatch,
+    #[msg("Grace period must be positive.")]
+    InvalidGracePeriod,
+    #[msg("Execution would leave the multisig below its rent-exempt minimum.")]
+    WouldDeinitialize,
+    #[msg("Title must be zero padded UTF-8.")]
+    InvalidTitle,
+    #[msg("Memo is too long.")]
+    MemoTooLong,
+    #[msg("Proposer diversity can't exceed the number of owners.")]
+    InvalidProposerDiversity,
+    #[msg("This owner proposed too recently, other owners must propose first.")]
+    ProposerNotDistinct,
+    #[msg("Delegate must be a new key and the delegation must not be expired.")]
+    InvalidDelegate,
+    #[msg("Transaction is still valid for the current owners.")]
+    TransactionStillValid,
+    #[msg("Transaction is stale, its grace period has passed.")]
+    TransactionStale,
+    #[msg("The multisig's audit log must be passed to record the execution.")]
+    AuditLogMissing,
+    #[msg("Raising the threshold strands approved transactions, confirm to proceed.")]
+    StrandsPendingTransactions,
+    #[msg("Instructions can't write to the transaction being executed.")]
+    SelfReferenceNotAllowed,
+    #[msg("Transaction was cancelled.")]
+    TransactionCancelled,
+    #[msg("All owners must confirm the multisig setup first.")]
+    SetupNotConfirmed,
+    #[msg("Sysvars can only be passed read-only and unsigned.")]
+    InvalidSysvarMeta,
+    #[msg("Instruction data exceeds the multisig's limit.")]
+    InstructionDataTooLarge,
+    #[msg("The Clock sysvar is unavailable.")]
+    ClockUnavailable,
+    #[msg("Too many value tiers.")]
+    TooManyValueTiers,
+    #[msg("The multisig would have fewer owners than its minimum.")]
+    TooFewOwners,
+    #[msg("Eta must be a positive timestamp.")]
+    InvalidEta,
+    #[msg("Transaction is frozen.")]
+    TransactionFrozen,
+    #[msg("Transaction was proposed before the multisig's proposal cutoff.")]
+    ProposalBeforeCutoff,
+    #[msg("Template parameters don't match the template.")]
+    InvalidTemplateParams,
+    #[msg("Multisig account predates the current layout, migrate it first.")]
+    MultisigNotMigrated,
+    #[msg("Multisig account already uses the current layout.")]
+    AlreadyMigrated,
+    #[msg("Transaction account predates the current layout, migrate it first.")]
+    TransactionNotMigrated,
+    #[msg("An owner change is already pending.")]
+    OwnerChangePending,
+    #[msg("Imported approvals must stay below the threshold.")]
+    TooManyImportedApprovals,
+    #[msg("An account appears more than once in an instruction.")]
+    DuplicateAccountMeta,
 }
 
 #[account]
@@ -40,10 +159,90 @@ pub struct Multisig {
     pub num_transactions: u64,
     pub owners_seq_no: u64,
     pub owners: Vec<Pubkey>,
+    /// Fields up to `_reserved` took over the reserved space of earlier
+    /// versions and have fixed sizes, so their offsets only depend on the
+    /// owner count. `MULTISIG_LAYOUT_VERSION` once written in this layout, 0
+    /// in accounts that need `migrate_multisig`.
+    pub layout_version: u8,
+    pub pending_transactions: u64,
+    pub max_pending: u64,
+    pub executed_transactions: u64,
+    pub cancelled_transactions: u64,
+    pub approval_ttl: i64,
+    pub preserve_approvals_on_removal: bool,
+    pub allowance_period: i64,
+    pub allowance_period_start: i64,
+    pub pending_threshold: u64,
+    pub pending_owners_eta: i64,
+    pub pending_owners_seq_no: u64,
+    pub last_activity_at: i64,
+    pub recovery_owner: Pubkey,
+    pub recovery_inactivity: i64,
+    pub recovery_owners_seq_no: u64,
+    pub min_delay: i64,
+    pub owners_changed_at: i64,
+    pub post_change_cooldown: i64,
+    pub proposal_cooldown: i64,
+    pub failsafe_beneficiary: Pubkey,
+    pub inactivity_timeout: i64,
+    pub require_distinct_slots: bool,
+    pub migration_authority: Pubkey,
+    pub migration_complete: bool,
+    pub enforce_separation: bool,
+    pub approval_reward: u64,
+    /// Incremented each time the threshold, delay or grace period changes.
+    pub config_version: u64,
+    /// Every `min_distinct_recent_proposers` consecutive proposals must come
+    /// from as many different owners. 0 disables it.
+    pub min_distinct_recent_proposers: u8,
+    /// Executions are recorded in this `AuditLog` once set.
+    pub audit_log: Pubkey,
+    /// Cancel votes needed by `cancel_transaction`, `threshold` when 0.
+    pub cancel_threshold: u64,
+    /// Set once every initial owner acknowledged the configuration with
+    /// `confirm_setup`. Transactions can't be created or executed before,
+    /// see `setup_confirmed`.
+    pub confirmed: bool,
+    /// Lamports paid out as approval rewards so far.
+    pub total_incentives_paid: u64,
+    /// Rewards that would take `total_incentives_paid` above this are
+    /// skipped. 0 means no cap.
+    pub max_total_incentives: u64,
+    /// Longest instruction data a proposal may carry. 0 means no limit.
+    pub max_instruction_data_len: u32,
+    pub preserve_approvals_on_reorder: bool,
+    pub proposal_rent_source: ProposalRentSource,
+    /// Owner changes can't leave fewer owners than this. 0, on multisigs
+    /// created before it existed, counts as 1.
+    pub min_owners: u8,
+    /// Label telling apart multisigs of the same base, part of the PDA
+    /// seeds unless all zero, see `purpose_seed`.
+    pub purpose: [u8; 8],
+    /// How long transactions keep the owners they were proposed under,
+    /// approving and executing with them after an owner change. 0 doesn't
+    /// snapshot owners.
+    pub owner_snapshot_ttl: i64,
+    /// Transactions created before this can't be approved or executed.
+    pub proposal_cutoff: i64,
     _reserved: [u64; 16],
+    // Growable fields go last, new ones are appended below.
+    pub allowance: Vec<u64>,
+    pub allowance_spent: Vec<u64>,
+    pub pending_owners: Vec<Pubkey>,
+    pub last_proposal_at: Vec<i64>,
+    pub executor_allowlist: Vec<Pubkey>,
+    /// Proposers of the latest proposals, oldest first, as many as
+    /// `min_distinct_recent_proposers` needs.
+    pub recent_proposers: Vec<Pubkey>,
+    pub delegations: Vec<Delegation>,
+    pub setup_confirmations: Vec<bool>,
+    /// Approvals required by the lamports a transaction transfers out of
+    /// the vault, see `transferred_out`.
+    pub value_tiers: Vec<ValueTier>,
 }
 
 #[account]
+#[derive(Default)]
 pub struct Transaction {
     pub multisig: Pubkey,
     pub index: u64,
@@ -55,9 +254,201 @@ pub struct Transaction {
     pub signers: Vec<bool>,
     pub executor: Pubkey,
     pub executed_at: i64,
+    /// Zero for transactions proposed before it existed, which need
+    /// `migrate_transaction`. Sits in the legacy reserved space.
+    pub layout_version: u8,
+    pub approved_at: Vec<i64>,
+    pub callback_program: Pubkey,
+    pub callback_data: Vec<u8>,
+    pub precondition: Option<Precondition>,
+    pub instruction_approvals: Vec<u32>,
+    pub skipped_instructions: u32,
+    pub group_id: u64,
+    pub group_index: u8,
+    /// Number of transactions in the group, 0 when not grouped.
+    pub group_size: u8,
+    pub approved_slot: Vec<u64>,
+    pub balance_assertion: Option<BalanceAssertion>,
+    pub seed_accounts: Vec<SeedSpec>,
+    pub depends_on: Pubkey,
+    /// Advisory, UI-defined kind of each instruction, e.g. transfer or
+    /// upgrade. Empty when not provided.
+    pub action_hints: Vec<u8>,
+    /// Approvals required for this transaction when above the multisig
+    /// threshold.
+    pub min_approvals: u64,
+    pub title: Title,
+    pub memo: String,
+    pub cancel_votes: Vec<bool>,
+    pub cancelled_at: i64,
+    /// Execute as part of the approval reaching the threshold past the eta.
+    pub auto_execute: bool,
+    /// Last non-empty return data set by an executed instruction, at most
+    /// `MAX_RETURN_DATA_LEN` bytes.
+    pub return_data: Vec<u8>,
+    /// Frozen transactions can't be approved or executed until unfrozen.
+    pub frozen: bool,
+    /// Votes, by owner index, to flip `frozen`. Cleared when it flips.
+    pub freeze_votes: Vec<bool>,
+    /// Owners when proposed, if the multisig snapshots them.
+    pub owner_snapshot: Vec<Pubkey>,
+    pub owner_snapshot_until: i64,
+    /// 0 for transactions created before it was recorded.
+    pub created_at: i64,
+    /// Owners, by index, who revoked an approval. Approving again isn't a
+    /// first approval: no reward and no activity count.
+    pub approval_revoked: Vec<bool>,
     _reserved: [u64; 16],
 }
 
+/// Per-owner data kept out of the multisig account, at PDA
+/// `[b"owner", multisig, owner]`.
+#[account]
+#[derive(Default)]
+pub struct OwnerMetadata {
+    pub multisig: Pubkey,
+    pub owner: Pubkey,
+    pub bump: u8,
+    pub last_seen: i64,
+    /// Activity counters, only updated by instructions given this account,
+    /// see `record_owner_activity`.
+    pub proposals_created: u64,
+    pub approvals: u64,
+    pub executions: u64,
+    _reserved: [u64; 1],
+}
+
+/// Append-only record of executions, at PDA `[b"audit_log", multisig]`.
+/// Holds the latest `MAX_AUDIT_RECORDS`, overwriting the oldest first.
+#[account]
+#[derive(Default)]
+pub struct AuditLog {
+    pub multisig: Pubkey,
+    pub bump: u8,
+    /// Records ever written, `records[total % MAX_AUDIT_RECORDS]` is next.
+    pub total: u64,
+    pub records: Vec<AuditRecord>,
+}
+
+/// Reusable instructions, at PDA `[b"template", multisig, template_id]`,
+/// turned into transactions by `create_transaction_from_template`.
+#[account]
+#[derive(Default)]
+pub struct ProposalTemplate {
+    pub multisig: Pubkey,
+    pub template_id: u64,
+    pub bump: u8,
+    pub creator: Pubkey,
+    pub instructions: Vec<TransactionInstruction>,
+    /// Filled, in order, from the parameters of each instantiation.
+    pub params: Vec<TemplateParam>,
+}
+
+/// Returned by `list_transactions`.
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, PartialEq, Copy, Clone)]
+pub struct TransactionSummary {
+    pub index: u64,
+    pub eta: i64,
+    pub executed: bool,
+    /// Current approvals, 0 after an owner change.
+    pub signer_count: u8,
+    pub num_instructions: u8,
+}
+
+/// Returned by `owner_activity`.
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, PartialEq, Copy, Clone)]
+pub struct OwnerActivity {
+    pub owner: Pubkey,
+    pub proposals_created: u64,
+    pub approvals: u64,
+    pub executions: u64,
+    pub last_seen: i64,
+}
+
+/// Returned by `execute_transaction`.
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, PartialEq, Copy, Clone)]
+pub struct ExecutionReceipt {
+    pub transaction: Pubkey,
+    pub executed_at: i64,
+    /// Set when an idempotent retry found the transaction executed.
+    pub already_executed: bool,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, PartialEq, Copy, Clone)]
+pub struct AuditRecord {
+    pub index: u64,
+    pub executor: Pubkey,
+    pub executed_at: i64,
+    pub num_instructions: u8,
+}
+
+#[event]
+pub struct ThresholdAutoAdjusted {
+    pub multisig: Pubkey,
+    pub old_threshold: u64,
+    pub new_threshold: u64,
+}
+
+/// Emitted when raising the threshold leaves `stranded` transactions short
+/// of approvals.
+#[event]
+pub struct ThresholdRaised {
+    pub multisig: Pubkey,
+    pub old_threshold: u64,
+    pub new_threshold: u64,
+    /// Pending transactions that had enough approvals before.
+    pub stranded: Vec<Pubkey>,
+}
+
+/// Emitted for every new proposal so indexers can rebuild it from logs.
+/// `instructions` holds the Borsh-serialized instructions, truncated to
+/// `MAX_EVENT_INSTRUCTIONS_SIZE` bytes for large proposals.
+#[event]
+pub struct TransactionProposed {
+    pub multisig: Pubkey,
+    pub index: u64,
+    pub proposer: Pubkey,
+    pub eta: i64,
+    pub num_instructions: u32,
+    pub instructions_hash: [u8; 32],
+    pub instructions: Vec<u8>,
+    pub truncated: bool,
+}
+
+#[event]
+pub struct InactivityFailsafeTriggered {
+    pub multisig: Pubkey,
+    pub beneficiary: Pubkey,
+    pub to: Pubkey,
+    pub amount: u64,
+}
+
+/// Emitted by `transfer_proposership`. `approval_withdrawn` tells whether
+/// the previous proposer lost the approval that came with proposing.
+#[event]
+pub struct ProposershipTransferred {
+    pub multisig: Pubkey,
+    pub transaction: Pubkey,
+    pub old_proposer: Pubkey,
+    pub new_proposer: Pubkey,
+    pub approval_withdrawn: bool,
+}
+
+/// Short UTF-8 title of a transaction, 48 bytes zero padded. Borsh has no
+/// impl for 48 byte arrays, so it's split in two arrays serialized back to
+/// back.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct Title {
+    pub head: [u8; 32],
+    pub tail: [u8; 16],
+}
+
+impl Title {
+    pub fn bytes(&self) -> Vec<u8> {
+        [&self.head[..], &self.tail[..]].concat()
+    }
+}
+
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, Default, PartialEq)]
 pub struct TransactionInstruction {
     pub program_id: Pubkey,
@@ -65,6 +456,24 @@ pub struct TransactionInstruction {
     pub data: Vec<u8>,
 }
 
+impl From<solana_program::instruction::Instruction> for TransactionInstruction {
+    fn from(ix: solana_program::instruction::Instruction) -> Self {
+        TransactionInstruction {
+            program_id: ix.program_id,
+            keys: ix
+                .accounts
+                .into_iter()
+                .map(|a| TransactionInstructionMeta {
+                    pubkey: a.pubkey,
+                    is_signer: a.is_signer,
+                    is_writable: a.is_writable,
+                })
+                .collect(),
+            data: ix.data,
+        }
+    }
+}
+
 #[derive(AnchorSerialize, AnchorDeserialize, Debug, PartialEq, Copy, Clone)]
 pub struct TransactionInstructionMeta {
     pub pubkey: Pubkey,
@@ -72,6 +481,143 @@ pub struct TransactionInstructionMeta {
     pub is_writable: bool,
 }
 
+/// Condition on another account's state, checked before execution.
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, PartialEq, Copy, Clone)]
+pub struct Precondition {
+    pub account: Pubkey,
+    /// Offset of the little-endian u64 compared against `value`.
+    pub offset: u32,
+    pub comparison: Comparison,
+    pub value: u64,
+}
+
+/// Expected balance range of an account, checked right before execution.
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, PartialEq, Copy, Clone)]
+pub struct BalanceAssertion {
+    pub account: Pubkey,
+    /// Check the amount of an SPL token account instead of its lamports.
+    pub token_account: bool,
+    pub min_balance: u64,
+    pub max_balance: u64,
+}
+
+/// Lets `delegate` approve on behalf of `owner` until `until`.
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, PartialEq, Copy, Clone)]
+pub struct Delegation {
+    pub owner: Pubkey,
+    pub delegate: Pubkey,
+    pub until: i64,
+}
+
+/// Transactions transferring at least `min_lamports` need `threshold`
+/// approvals.
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, PartialEq, Copy, Clone)]
+pub struct ValueTier {
+    pub min_lamports: u64,
+    pub threshold: u64,
+}
+
+/// Settings of a multisig created with `create_multisig_v2`.
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, PartialEq, Clone)]
+pub struct MultisigConfig {
+    pub owners: Vec<Pubkey>,
+    pub threshold: u64,
+    pub delay: i64,
+    pub grace_period: i64,
+    pub min_delay: i64,
+    pub max_pending: u64,
+    pub approval_ttl: i64,
+    pub proposal_cooldown: i64,
+    pub cancel_threshold: u64,
+    pub approval_reward: u64,
+    pub max_total_incentives: u64,
+    pub max_instruction_data_len: u32,
+    pub min_owners: u8,
+    pub purpose: [u8; 8],
+    pub migration_authority: Pubkey,
+}
+
+/// Returned by `export_config`: what `create_multisig_v2` takes, then the
+/// settings changed by their own instructions.
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, PartialEq, Clone)]
+pub struct ExportedConfig {
+    pub config: MultisigConfig,
+    pub config_version: u64,
+    pub preserve_approvals_on_removal: bool,
+    pub preserve_approvals_on_reorder: bool,
+    pub require_distinct_slots: bool,
+    pub enforce_separation: bool,
+    pub proposal_rent_source: ProposalRentSource,
+    pub allowance_period: i64,
+    pub recovery_owner: Pubkey,
+    pub recovery_inactivity: i64,
+    pub post_change_cooldown: i64,
+    pub failsafe_beneficiary: Pubkey,
+    pub inactivity_timeout: i64,
+    pub min_distinct_recent_proposers: u8,
+    pub owner_snapshot_ttl: i64,
+    pub proposal_cutoff: i64,
+    pub audit_log: Pubkey,
+    pub executor_allowlist: Vec<Pubkey>,
+    pub value_tiers: Vec<ValueTier>,
+    pub allowance: Vec<u64>,
+}
+
+/// Derives the pubkey of `instructions[instruction_index].keys[key_index]`
+/// at execution time, as the address of `program_id` for `seeds`.
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, PartialEq, Clone)]
+pub struct SeedSpec {
+    pub instruction_index: u8,
+    pub key_index: u8,
+    pub program_id: Pubkey,
+    pub seeds: Vec<Seed>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, PartialEq, Clone)]
+pub enum Seed {
+    Bytes {
+        bytes: Vec<u8>,
+    },
+    /// `length` bytes of `account`'s data at `offset`, read on execution.
+    AccountData {
+        account: Pubkey,
+        offset: u32,
+        length: u8,
+    },
+}
+
+/// Part of a template instruction replaced by parameter bytes.
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, PartialEq, Copy, Clone)]
+pub enum TemplateParam {
+    /// `length` bytes of the data at `offset`.
+    Data {
+        instruction_index: u8,
+        offset: u16,
+        length: u8,
+    },
+    /// The pubkey of an account, 32 bytes.
+    Key {
+        instruction_index: u8,
+        key_index: u8,
+    },
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, PartialEq, Copy, Clone)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    LessThan,
+    GreaterThan,
+}
+
+/// Who pays the rent of accounts made by `create_transaction`.
+#[derive(AnchorSerialize, AnchorDeserialize, Debug, PartialEq, Copy, Clone)]
+pub enum ProposalRentSource {
+    Proposer,
+    /// The multisig PDA, out of its balance above rent exemption.
+    Pda,
+}
+
 #[program]
 pub mod multisig {
     use super::*;
@@ -81,7 +627,7 @@ pub mod multisig {
     pub struct CreateMultisig<'info> {
         #[account(mut)]
         pub signer: Signer<'info>,
-        pub base: AccountInfo<'info>,
+        pub base: Signer<'info>,
         #[account(
             init,
             seeds = [
@@ -90,12 +636,14 @@ pub mod multisig {
             ],
             bump = bump,
             payer = signer,
-            space = 4 + std::mem::size_of::<Multisig>() + 4 + (15*32),
+            space = multisig_space(),
         )]
         multisig: Account<'info, Multisig>,
         system_program: Program<'info, System>,
     }
 
+    /// Creates a multisig with default settings and no purpose or migration
+    /// authority, see `create_multisig_v2` for those.
     pub fn create_multisig(
         ctx: Context<CreateMultisig>,
         owners: Vec<Pubkey>,
@@ -105,217 +653,4937 @@ pub mod multisig {
     ) -> ProgramResult {
         let multisig = &mut ctx.accounts.multisig;
         require_unique_owners(&owners)?;
+        require_canonical_bump(&[b"multisig", ctx.accounts.base.key().as_ref()], bump)?;
+        validate_delay(multisig, delay)?;
         multisig.base = ctx.accounts.base.key();
         multisig.bump = bump;
+        multisig.layout_version = MULTISIG_LAYOUT_VERSION;
         multisig.threshold = threshold;
         multisig.delay = delay;
         multisig.grace_period = 14 * 24 * 3600;
+        multisig.setup_confirmations = vec![false; owners.len()];
         multisig.owners = owners;
+        multisig.min_owners = 1;
+        multisig.last_activity_at = clock()?.unix_timestamp;
+        assert_invariants(multisig)?;
         Ok(())
     }
 
     #[derive(Accounts)]
-    pub struct SetOwners<'info> {
-        #[account(mut, signer)]
+    #[instruction(config: MultisigConfig, bump: u8)]
+    pub struct CreateMultisigV2<'info> {
+        #[account(mut)]
+        pub signer: Signer<'info>,
+        pub base: Signer<'info>,
+        #[account(
+            init,
+            seeds = [
+                b"multisig",
+                base.key().to_bytes().as_ref(),
+                purpose_seed(&config.purpose)
+            ],
+            bump = bump,
+            payer = signer,
+            space = multisig_space(),
+        )]
         multisig: Account<'info, Multisig>,
+        system_program: Program<'info, System>,
     }
 
-    pub fn set_owners(ctx: Context<SetOwners>, owners: Vec<Pubkey>) -> ProgramResult {
+    /// Same as `create_multisig` with every setting validated up front, so
+    /// the multisig needs no follow-up configuration. Owners still have to
+    /// `confirm_setup`.
+    ///
+    /// A `migration_authority` is trusted to import only approvals the owners
+    /// actually gave on the multisig being migrated from, nothing checks
+    /// their signatures. Imports stay below the threshold, so at least one
+    /// owner approves each imported transaction here.
+    pub fn create_multisig_v2(
+        ctx: Context<CreateMultisigV2>,
+        config: MultisigConfig,
+        bump: u8,
+    ) -> ProgramResult {
         let multisig = &mut ctx.accounts.multisig;
-        require_unique_owners(&owners)?;
-        if (owners.len() as u64) < multisig.threshold {
-            multisig.threshold = owners.len() as u64;
-        }
-        multisig.owners = owners;
-        multisig.owners_seq_no = multisig
-            .owners_seq_no
-            .checked_add(1)
-            .ok_or(ErrorCode::Overflow)?;
+        require_unique_owners(&config.owners)?;
+        require_canonical_bump(
+            &[
+                b"multisig",
+                ctx.accounts.base.key().as_ref(),
+                purpose_seed(&config.purpose),
+            ],
+            bump,
+        )?;
+        multisig.setup_confirmations = vec![false; config.owners.len()];
+        multisig.owners = config.owners;
+        require!(config.threshold >= 1, InvalidThreshold);
+        validate_threshold(multisig, config.threshold)?;
+        require!(
+            config.cancel_threshold <= multisig.owners.len() as u64,
+            InvalidThreshold
+        );
+        require!(
+            config.min_delay >= 0 && config.min_delay <= config.delay,
+            InvalidDelay
+        );
+        multisig.min_delay = config.min_delay;
+        validate_delay(multisig, config.delay)?;
+        validate_grace_period(config.grace_period)?;
+        require!(
+            config.approval_ttl >= 0 && config.proposal_cooldown >= 0,
+            InvalidDelay
+        );
+
+        multisig.base = ctx.accounts.base.key();
+        multisig.bump = bump;
+        multisig.layout_version = MULTISIG_LAYOUT_VERSION;
+        multisig.purpose = config.purpose;
+        multisig.threshold = config.threshold;
+        multisig.delay = config.delay;
+        multisig.grace_period = config.grace_period;
+        multisig.max_pending = config.max_pending;
+        multisig.approval_ttl = config.approval_ttl;
+        multisig.proposal_cooldown = config.proposal_cooldown;
+        multisig.cancel_threshold = config.cancel_threshold;
+        multisig.approval_reward = config.approval_reward;
+        multisig.max_total_incentives = config.max_total_incentives;
+        multisig.max_instruction_data_len = config.max_instruction_data_len;
+        require!(config.min_owners >= 1, TooFewOwners);
+        multisig.min_owners = config.min_owners;
+        multisig.last_activity_at = clock()?.unix_timestamp;
+        multisig.migration_authority = config.migration_authority;
+        assert_invariants(multisig)?;
         Ok(())
     }
 
     #[derive(Accounts)]
-    pub struct ChangeThreshold<'info> {
-        #[account(mut, signer)]
+    pub struct MigrateMultisig<'info> {
+        #[account(mut)]
+        payer: Signer<'info>,
+        // Doesn't deserialize as a `Multisig` before the migration.
+        #[account(mut, owner = crate::ID)]
+        multisig: AccountInfo<'info>,
+        system_program: Program<'info, System>,
+    }
+
+    /// Rewrites a multisig created before `layout_version` existed in the
+    /// current layout, growing it to `multisig_space` with `payer` covering
+    /// the extra rent. Anyone can migrate, nothing but the layout changes.
+    pub fn migrate_multisig(ctx: Context<MigrateMultisig>) -> ProgramResult {
+        let info = &ctx.accounts.multisig;
+        let multisig = migrate_legacy_multisig(&info.try_borrow_data()?, clock()?.unix_timestamp)?;
+        grow_account(
+            info,
+            &ctx.accounts.payer,
+            &ctx.accounts.system_program,
+            multisig_space(),
+        )?;
+        let mut data = info.try_borrow_mut_data()?;
+        data.fill(0);
+        multisig.try_serialize(&mut &mut data[..])
+    }
+
+    #[derive(Accounts)]
+    pub struct MigrateTransaction<'info> {
+        #[account(mut)]
+        payer: Signer<'info>,
+        multisig: Account<'info, Multisig>,
+        // Doesn't deserialize as a `Transaction` before the migration.
+        #[account(mut, owner = crate::ID)]
+        transaction: AccountInfo<'info>,
+        system_program: Program<'info, System>,
+    }
+
+    /// Rewrites a transaction proposed before `layout_version` existed in
+    /// the current layout, like `migrate_multisig`. Its multisig has to be
+    /// migrated first.
+    pub fn migrate_transaction(ctx: Context<MigrateTransaction>) -> ProgramResult {
+        let multisig = &ctx.accounts.multisig;
+        require!(layout_current(multisig), MultisigNotMigrated);
+        let info = &ctx.accounts.transaction;
+        let tx = migrate_legacy_transaction(&info.try_borrow_data()?)?;
+        require_transaction_of(multisig, &tx)?;
+        grow_account(
+            info,
+            &ctx.accounts.payer,
+            &ctx.accounts.system_program,
+            transaction_space(tx.instructions.clone()),
+        )?;
+        let mut data = info.try_borrow_mut_data()?;
+        data.fill(0);
+        tx.try_serialize(&mut &mut data[..])
+    }
+
+    // The multisig PDA only signs through `execute_transaction`, so config
+    // changes below always go through the proposal flow and its delay.
+    #[derive(Accounts)]
+    pub struct ConfirmSetup<'info> {
+        signer: Signer<'info>,
+        #[account(mut)]
         multisig: Account<'info, Multisig>,
     }
 
-    pub fn change_threshold(ctx: Context<ChangeThreshold>, threshold: u64) -> ProgramResult {
+    /// Acknowledges the multisig configuration as the signing owner. The
+    /// multisig becomes usable once all owners did.
+    pub fn confirm_setup(ctx: Context<ConfirmSetup>) -> ProgramResult {
+        let signer_key = ctx.accounts.signer.key();
         let multisig = &mut ctx.accounts.multisig;
-        if threshold > multisig.owners.len() as u64 {
-            return Err(ErrorCode::InvalidThreshold.into());
+        let owner_index = multisig
+            .owners
+            .iter()
+            .position(|a| *a == signer_key)
+            .ok_or(ErrorCode::InvalidOwner)?;
+        let num_owners = multisig.owners.len();
+        multisig.setup_confirmations.resize(num_owners, false);
+        multisig.setup_confirmations[owner_index] = true;
+        if multisig.setup_confirmations.iter().all(|&c| c) {
+            multisig.confirmed = true;
         }
-        multisig.threshold = threshold;
         Ok(())
     }
 
     #[derive(Accounts)]
-    pub struct ChangeDelay<'info> {
+    pub struct SetOwners<'info> {
         #[account(mut, signer)]
         multisig: Account<'info, Multisig>,
     }
 
-    pub fn change_delay(ctx: Context<ChangeDelay>, delay: i64) -> ProgramResult {
+    /// A reordering of the current owners is rejected, unless
+    /// `preserve_approvals_on_reorder` is set: approvals of the pending
+    /// transactions in `remaining_accounts` then follow their owner to its
+    /// new index, others are invalidated.
+    pub fn set_owners<'info>(
+        ctx: Context<'_, '_, '_, 'info, SetOwners<'info>>,
+        owners: Vec<Pubkey>,
+        confirm_threshold_reduction: bool,
+    ) -> ProgramResult {
         let multisig = &mut ctx.accounts.multisig;
-        if delay > 30 * 24 * 3600 {
-            return Err(ErrorCode::InvalidDelay.into());
+        require_unique_owners(&owners)?;
+        // Approvals are tracked by owner index, so a reordering can't keep
+        // pending transactions valid. Reject it instead of invalidating them.
+        let mut old_owners = multisig.owners.clone();
+        let mut new_owners = owners.clone();
+        old_owners.sort();
+        new_owners.sort();
+        if old_owners == new_owners {
+            require!(
+                multisig.preserve_approvals_on_reorder && owners != multisig.owners,
+                NoOwnerChange
+            );
+            reorder_owners(multisig, owners, ctx.remaining_accounts)?;
+            assert_invariants(multisig)?;
+            return Ok(());
         }
-        multisig.delay = delay;
+        if (owners.len() as u64) < multisig.threshold {
+            require!(confirm_threshold_reduction, InvalidThreshold);
+            emit!(ThresholdAutoAdjusted {
+                multisig: multisig.key(),
+                old_threshold: multisig.threshold,
+                new_threshold: owners.len() as u64,
+            });
+            multisig.threshold = owners.len() as u64;
+        }
+        // Allowances are per owner index, revoke them all on a new owner set.
+        multisig.allowance = Vec::new();
+        multisig.allowance_spent = Vec::new();
+        multisig.last_proposal_at = Vec::new();
+        multisig.owners = owners;
+        reset_setup_confirmations(multisig);
+        multisig.owners_seq_no = multisig
+            .owners_seq_no
+            .checked_add(1)
+            .ok_or(ErrorCode::Overflow)?;
+        multisig.owners_changed_at = clock()?.unix_timestamp;
+        prune_executor_allowlist(multisig);
+        assert_invariants(multisig)?;
         Ok(())
     }
 
     #[derive(Accounts)]
-    #[instruction(instructions: Vec<TransactionInstruction>, bump: u8)]
-    pub struct CreateTransaction<'info> {
-        #[account(mut)]
-        signer: Signer<'info>,
-        #[account(mut)]
+    pub struct RemoveOwner<'info> {
+        #[account(mut, signer)]
         multisig: Account<'info, Multisig>,
-        #[account(
-            init,
-            seeds = [
-                b"transaction",
-                multisig.key().to_bytes().as_ref(),
-                multisig.num_transactions.to_le_bytes().as_ref()
-            ],
-            bump = bump,
-            payer = signer,
-            space = transaction_space(instructions),
-        )]
-        transaction: Account<'info, Transaction>,
-        system_program: Program<'info, System>,
     }
 
-    pub fn create_transaction(
-        ctx: Context<CreateTransaction>,
-        instructions: Vec<TransactionInstruction>,
-        bump: u8,
+    /// Removes `owner`. When `preserve_approvals_on_removal` is set, pending
+    /// transactions passed in `remaining_accounts` drop the owner's approval
+    /// and stay valid, others are invalidated like with `set_owners`.
+    pub fn remove_owner<'info>(
+        ctx: Context<'_, '_, '_, 'info, RemoveOwner<'info>>,
+        owner: Pubkey,
     ) -> ProgramResult {
         let multisig = &mut ctx.accounts.multisig;
-        let tx = &mut ctx.accounts.transaction;
-        let signer_key = ctx.accounts.signer.key;
         let owner_index = multisig
             .owners
             .iter()
-            .position(|a| a == signer_key)
+            .position(|a| *a == owner)
             .ok_or(ErrorCode::InvalidOwner)?;
+        let old_seq_no = multisig.owners_seq_no;
+        remove_owner_at(multisig, owner_index)?;
 
-        let mut signers = Vec::new();
-        signers.resize(multisig.owners.len(), false);
-        signers[owner_index] = true;
-
-        tx.multisig = multisig.key();
-        tx.bump = bump;
-        tx.eta = Clock::get()?.unix_timestamp + multisig.delay;
-        tx.owners_seq_no = multisig.owners_seq_no;
-        tx.proposer = ctx.accounts.signer.key();
-        tx.instructions = instructions.clone();
-        tx.signers = signers;
-
-        multisig.num_transactions = multisig
-            .num_transactions
-            .checked_add(1)
-            .ok_or(ErrorCode::Overflow)?;
+        if !multisig.preserve_approvals_on_removal {
+            return Ok(());
+        }
+        for info in ctx.remaining_accounts.iter() {
+            let mut tx = Account::<Transaction>::try_from(info)?;
+            if tx.multisig != multisig.key()
+                || tx.owners_seq_no != old_seq_no
+                || tx.executed_at != 0
+            {
+                continue;
+            }
+            move_owner_slots(&mut tx, OwnerSlots::Remove(owner_index));
+            tx.owners_seq_no = multisig.owners_seq_no;
+            tx.exit(ctx.program_id)?;
+        }
         Ok(())
     }
 
     #[derive(Accounts)]
-    pub struct Approve<'info> {
+    pub struct Resign<'info> {
         signer: Signer<'info>,
+        #[account(mut)]
         multisig: Account<'info, Multisig>,
-        #[account(mut, has_one = multisig)]
-        transaction: Account<'info, Transaction>,
     }
 
-    pub fn approve(ctx: Context<Approve>) -> ProgramResult {
-        let owner_index = ctx
-            .accounts
-            .multisig
+    /// Removes the signing owner, as long as enough owners remain to reach
+    /// the threshold. Pending transactions are invalidated.
+    pub fn resign(ctx: Context<Resign>) -> ProgramResult {
+        let signer_key = ctx.accounts.signer.key();
+        let multisig = &mut ctx.accounts.multisig;
+        let owner_index = multisig
             .owners
             .iter()
-            .position(|a| a == ctx.accounts.signer.key)
+            .position(|a| *a == signer_key)
             .ok_or(ErrorCode::InvalidOwner)?;
+        remove_owner_at(multisig, owner_index)?;
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct ProposeOwnerChange<'info> {
+        #[account(mut, signer)]
+        multisig: Account<'info, Multisig>,
+    }
+
+    /// Stores an owner change that any owner can apply with
+    /// `confirm_owner_change` once the multisig delay elapsed, or cancel with
+    /// `abort_owner_change` before that. Signed by the multisig, so the
+    /// change itself needs the threshold's approval. Fails while another
+    /// change is pending.
+    pub fn propose_owner_change(
+        ctx: Context<ProposeOwnerChange>,
+        new_owners: Vec<Pubkey>,
+        new_threshold: u64,
+    ) -> ProgramResult {
+        let multisig = &mut ctx.accounts.multisig;
+        require!(multisig.pending_owners_eta == 0, OwnerChangePending);
+        require_governance_delay(multisig)?;
+        require_unique_owners(&new_owners)?;
         require!(
-            ctx.accounts.multisig.owners_seq_no == ctx.accounts.transaction.owners_seq_no,
-            OwnersChanged
+            new_threshold > 0 && new_threshold <= new_owners.len() as u64,
+            InvalidThreshold
         );
-        ctx.accounts.transaction.signers[owner_index] = true;
+        multisig.pending_owners = new_owners;
+        multisig.pending_threshold = new_threshold;
+        multisig.pending_owners_eta = eta_after(clock()?.unix_timestamp, multisig.delay)?;
+        multisig.pending_owners_seq_no = multisig.owners_seq_no;
         Ok(())
     }
 
     #[derive(Accounts)]
-    pub struct ExecuteTransaction<'info> {
+    pub struct ConfirmOwnerChange<'info> {
+        signer: Signer<'info>,
         #[account(
-            signer,
+            mut,
             constraint = multisig.owners.contains(&signer.key()) @ ErrorCode::InvalidOwner
         )]
-        signer: AccountInfo<'info>,
         multisig: Account<'info, Multisig>,
-        #[account(mut, has_one = multisig)]
-        transaction: Account<'info, Transaction>,
     }
 
-    pub fn execute_transaction(ctx: Context<ExecuteTransaction>) -> ProgramResult {
-        let tx = &mut ctx.accounts.transaction;
-
-        let now = Clock::get()?.unix_timestamp;
-        require!(now >= tx.eta, BeforeETA);
-        require!(tx.executed_at == 0, AlreadyExecuted);
+    pub fn confirm_owner_change(ctx: Context<ConfirmOwnerChange>) -> ProgramResult {
+        let multisig = &mut ctx.accounts.multisig;
+        require!(multisig.pending_owners_eta != 0, NoPendingOwnerChange);
         require!(
-            ctx.accounts.multisig.owners_seq_no == tx.owners_seq_no,
+            clock()?.unix_timestamp >= multisig.pending_owners_eta,
+            BeforeETA
+        );
+        require!(
+            multisig.pending_owners_seq_no == multisig.owners_seq_no,
             OwnersChanged
         );
+        multisig.owners = std::mem::take(&mut multisig.pending_owners);
+        multisig.threshold = multisig.pending_threshold;
+        multisig.allowance = Vec::new();
+        multisig.allowance_spent = Vec::new();
+        multisig.last_proposal_at = Vec::new();
+        reset_setup_confirmations(multisig);
+        multisig.pending_threshold = 0;
+        multisig.pending_owners_eta = 0;
+        multisig.owners_seq_no = multisig
+            .owners_seq_no
+            .checked_add(1)
+            .ok_or(ErrorCode::Overflow)?;
+        multisig.owners_changed_at = clock()?.unix_timestamp;
+        prune_executor_allowlist(multisig);
+        assert_invariants(multisig)?;
+        Ok(())
+    }
 
-        // Do we have enough signers?
-        let sig_count = tx.signers.iter().filter(|&signed| *signed).count();
-        if sig_count < ctx.accounts.multisig.threshold as usize {
-            return Err(ErrorCode::NotEnoughSigners.into());
-        }
-
-        tx.executed_at = now;
-        tx.executor = ctx.accounts.signer.key();
-
-        let seeds = &[
-            b"multisig",
-            ctx.accounts.multisig.base.as_ref(),
-            &[ctx.accounts.multisig.bump],
-        ];
-        for ix in ctx.accounts.transaction.instructions.iter() {
-            let six = solana_program::instruction::Instruction {
-                program_id: ix.program_id,
-                accounts: ix
-                    .keys
-                    .clone()
-                    .into_iter()
-                    .map(|a| solana_program::instruction::AccountMeta {
-                        pubkey: a.pubkey,
-                        is_signer: a.is_signer,
-                        is_writable: a.is_writable,
-                    })
-                    .collect(),
-                data: ix.data.clone(),
-            };
-            solana_program::program::invoke_signed(&six, ctx.remaining_accounts, &[seeds])?;
-        }
+    #[derive(Accounts)]
+    pub struct AbortOwnerChange<'info> {
+        signer: Signer<'info>,
+        #[account(
+            mut,
+            constraint = multisig.owners.contains(&signer.key()) @ ErrorCode::InvalidOwner
+        )]
+        multisig: Account<'info, Multisig>,
+    }
 
+    pub fn abort_owner_change(ctx: Context<AbortOwnerChange>) -> ProgramResult {
+        let multisig = &mut ctx.accounts.multisig;
+        require!(multisig.pending_owners_eta != 0, NoPendingOwnerChange);
+        multisig.pending_owners = Vec::new();
+        multisig.pending_threshold = 0;
+        multisig.pending_owners_eta = 0;
         Ok(())
     }
-}
-
-pub fn require_unique_owners(owners: &[Pubkey]) -> Result<()> {
-    let mut uniq_owners = owners.to_vec();
-    uniq_owners.sort();
-    uniq_owners.dedup();
-    require!(owners.len() == uniq_owners.len(), UniqueOwners);
-    Ok(())
-}
 
-pub fn transaction_space(instructions: Vec<TransactionInstruction>) -> usize {
-    let mut space = 4 + std::mem::size_of::<Transaction>() + 4 + 15 + 4;
+    #[derive(Accounts)]
+    pub struct SetRecovery<'info> {
+        #[account(mut, signer)]
+        multisig: Account<'info, Multisig>,
+    }
+
+    /// Lets `recovery_owner` call `recover` once no transaction executed for
+    /// `recovery_inactivity` seconds, `DEFAULT_RECOVERY_INACTIVITY` when 0.
+    /// `Pubkey::default()` disables recovery.
+    pub fn set_recovery(
+        ctx: Context<SetRecovery>,
+        recovery_owner: Pubkey,
+        recovery_inactivity: i64,
+    ) -> ProgramResult {
+        let multisig = &mut ctx.accounts.multisig;
+        require!(recovery_inactivity >= 0, InvalidDelay);
+        multisig.recovery_owner = recovery_owner;
+        multisig.recovery_inactivity = recovery_inactivity;
+        multisig.recovery_owners_seq_no = multisig.owners_seq_no;
+        multisig.last_activity_at = clock()?.unix_timestamp;
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct Recover<'info> {
+        #[account(
+            constraint = signer.key() != Pubkey::default()
+                && signer.key() == multisig.recovery_owner @ ErrorCode::InvalidOwner
+        )]
+        signer: Signer<'info>,
+        #[account(mut)]
+        multisig: Account<'info, Multisig>,
+    }
+
+    /// Lowers the threshold to `new_threshold` and, unless `old_owner` is
+    /// `Pubkey::default()`, replaces `old_owner` by `new_owner`. Only usable
+    /// once per recovery configuration as it bumps the owners seq no.
+    pub fn recover(
+        ctx: Context<Recover>,
+        new_threshold: u64,
+        old_owner: Pubkey,
+        new_owner: Pubkey,
+    ) -> ProgramResult {
+        let multisig = &mut ctx.accounts.multisig;
+        let now = clock()?.unix_timestamp;
+        let inactivity = if multisig.recovery_inactivity == 0 {
+            DEFAULT_RECOVERY_INACTIVITY
+        } else {
+            multisig.recovery_inactivity
+        };
+        require!(
+            multisig.recovery_owners_seq_no == multisig.owners_seq_no,
+            OwnersChanged
+        );
+        require!(
+            now >= multisig.last_activity_at.saturating_add(inactivity),
+            StillActive
+        );
+        require!(
+            new_threshold > 0 && new_threshold <= multisig.threshold,
+            InvalidThreshold
+        );
+
+        if old_owner != Pubkey::default() {
+            let owner_index = multisig
+                .owners
+                .iter()
+                .position(|a| *a == old_owner)
+                .ok_or(ErrorCode::InvalidOwner)?;
+            multisig.owners[owner_index] = new_owner;
+            require_unique_owners(&multisig.owners)?;
+            if let Some(allowance) = multisig.allowance.get_mut(owner_index) {
+                *allowance = 0;
+            }
+            if let Some(last_proposal_at) = multisig.last_proposal_at.get_mut(owner_index) {
+                *last_proposal_at = 0;
+            }
+            if let Some(confirmed) = multisig.setup_confirmations.get_mut(owner_index) {
+                *confirmed = false;
+            }
+        }
+        multisig.threshold = new_threshold;
+        multisig.last_activity_at = now;
+        multisig.owners_seq_no = multisig
+            .owners_seq_no
+            .checked_add(1)
+            .ok_or(ErrorCode::Overflow)?;
+        multisig.owners_changed_at = now;
+        prune_executor_allowlist(multisig);
+        assert_invariants(multisig)?;
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct SetFailsafe<'info> {
+        #[account(mut, signer)]
+        multisig: Account<'info, Multisig>,
+    }
+
+    /// Lets `failsafe_beneficiary` call `inactivity_failsafe` once no
+    /// transaction executed for `inactivity_timeout` seconds,
+    /// `DEFAULT_FAILSAFE_INACTIVITY` when 0. `Pubkey::default()` disables it.
+    pub fn set_failsafe(
+        ctx: Context<SetFailsafe>,
+        failsafe_beneficiary: Pubkey,
+        inactivity_timeout: i64,
+    ) -> ProgramResult {
+        let multisig = &mut ctx.accounts.multisig;
+        require!(inactivity_timeout >= 0, InvalidDelay);
+        multisig.failsafe_beneficiary = failsafe_beneficiary;
+        multisig.inactivity_timeout = inactivity_timeout;
+        multisig.last_activity_at = clock()?.unix_timestamp;
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct InactivityFailsafe<'info> {
+        #[account(
+            constraint = signer.key() != Pubkey::default()
+                && signer.key() == multisig.failsafe_beneficiary @ ErrorCode::InvalidOwner
+        )]
+        signer: Signer<'info>,
+        #[account(mut)]
+        multisig: Account<'info, Multisig>,
+        #[account(mut, seeds = [b"vault", multisig.key().as_ref()], bump)]
+        vault: AccountInfo<'info>,
+        #[account(mut)]
+        to: AccountInfo<'info>,
+        system_program: Program<'info, System>,
+    }
+
+    /// Moves everything the multisig PDA and its vault can spend to `to`.
+    pub fn inactivity_failsafe(ctx: Context<InactivityFailsafe>) -> ProgramResult {
+        let multisig = &ctx.accounts.multisig;
+        let now = clock()?.unix_timestamp;
+        let timeout = if multisig.inactivity_timeout == 0 {
+            DEFAULT_FAILSAFE_INACTIVITY
+        } else {
+            multisig.inactivity_timeout
+        };
+        require!(
+            now >= multisig.last_activity_at.saturating_add(timeout),
+            StillActive
+        );
+
+        let multisig_info = multisig.to_account_info();
+        let available = available_balance(&multisig_info)?;
+        **multisig_info.try_borrow_mut_lamports()? -= available;
+        **ctx.accounts.to.try_borrow_mut_lamports()? += available;
+
+        let vault = &ctx.accounts.vault;
+        let vault_lamports = vault.lamports();
+        if vault_lamports > 0 {
+            let multisig_key = multisig.key();
+            let (_, vault_bump) = vault_address(&multisig_key);
+            solana_program::program::invoke_signed(
+                &solana_program::system_instruction::transfer(
+                    vault.key,
+                    ctx.accounts.to.key,
+                    vault_lamports,
+                ),
+                &[
+                    vault.clone(),
+                    ctx.accounts.to.clone(),
+                    ctx.accounts.system_program.to_account_info(),
+                ],
+                &[&[b"vault", multisig_key.as_ref(), &[vault_bump]]],
+            )?;
+        }
+
+        emit!(InactivityFailsafeTriggered {
+            multisig: multisig.key(),
+            beneficiary: ctx.accounts.signer.key(),
+            to: ctx.accounts.to.key(),
+            amount: available
+                .checked_add(vault_lamports)
+                .ok_or(ErrorCode::Overflow)?,
+        });
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct ChangeThreshold<'info> {
+        #[account(mut, signer)]
+        multisig: Account<'info, Multisig>,
+    }
+
+    /// Pending transactions passed in the remaining accounts that had enough
+    /// approvals but won't under the raised threshold are reported in
+    /// `ThresholdRaised`, and need `confirm_stranded`.
+    pub fn change_threshold(
+        ctx: Context<ChangeThreshold>,
+        threshold: u64,
+        confirm_stranded: bool,
+    ) -> ProgramResult {
+        let multisig = &mut ctx.accounts.multisig;
+        validate_threshold(multisig, threshold)?;
+        if threshold > multisig.threshold {
+            let now = clock()?.unix_timestamp;
+            let stranded: Vec<Pubkey> = ctx
+                .remaining_accounts
+                .iter()
+                .filter(|info| stranded_by_threshold(multisig, info, threshold, now))
+                .map(|info| info.key())
+                .collect();
+            require!(
+                stranded.is_empty() || confirm_stranded,
+                StrandsPendingTransactions
+            );
+            emit!(ThresholdRaised {
+                multisig: multisig.key(),
+                old_threshold: multisig.threshold,
+                new_threshold: threshold,
+                stranded,
+            });
+        }
+        multisig.threshold = threshold;
+        bump_config_version(multisig)?;
+        assert_invariants(multisig)?;
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct ChangeDelay<'info> {
+        #[account(mut, signer)]
+        multisig: Account<'info, Multisig>,
+    }
+
+    pub fn change_delay(ctx: Context<ChangeDelay>, delay: i64) -> ProgramResult {
+        let multisig = &mut ctx.accounts.multisig;
+        validate_delay(multisig, delay)?;
+        multisig.delay = delay;
+        bump_config_version(multisig)?;
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct Reconfigure<'info> {
+        #[account(mut, signer)]
+        multisig: Account<'info, Multisig>,
+    }
+
+    /// Changes any of the threshold, delay and grace period at once. Every
+    /// provided value is validated before any is applied.
+    pub fn reconfigure(
+        ctx: Context<Reconfigure>,
+        threshold: Option<u64>,
+        delay: Option<i64>,
+        grace_period: Option<i64>,
+    ) -> ProgramResult {
+        let multisig = &mut ctx.accounts.multisig;
+        if let Some(threshold) = threshold {
+            validate_threshold(multisig, threshold)?;
+        }
+        if let Some(delay) = delay {
+            validate_delay(multisig, delay)?;
+        }
+        if let Some(grace_period) = grace_period {
+            validate_grace_period(grace_period)?;
+        }
+
+        if let Some(threshold) = threshold {
+            multisig.threshold = threshold;
+        }
+        if let Some(delay) = delay {
+            multisig.delay = delay;
+        }
+        if let Some(grace_period) = grace_period {
+            multisig.grace_period = grace_period;
+        }
+        bump_config_version(multisig)?;
+        assert_invariants(multisig)?;
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct ChangeMinDelay<'info> {
+        #[account(mut, signer)]
+        multisig: Account<'info, Multisig>,
+    }
+
+    /// Sets the delay governance proposals require, see `PRIVILEGED_INSTRUCTIONS`.
+    pub fn change_min_delay(ctx: Context<ChangeMinDelay>, min_delay: i64) -> ProgramResult {
+        let multisig = &mut ctx.accounts.multisig;
+        require!(min_delay >= 0 && min_delay <= multisig.delay, InvalidDelay);
+        multisig.min_delay = min_delay;
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct ChangePostChangeCooldown<'info> {
+        #[account(mut, signer)]
+        multisig: Account<'info, Multisig>,
+    }
+
+    /// Sets how long execution stays paused after the owners change, giving
+    /// remaining owners time to react. 0 disables the pause.
+    pub fn change_post_change_cooldown(
+        ctx: Context<ChangePostChangeCooldown>,
+        post_change_cooldown: i64,
+    ) -> ProgramResult {
+        require!(post_change_cooldown >= 0, InvalidDelay);
+        ctx.accounts.multisig.post_change_cooldown = post_change_cooldown;
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct ChangeMaxPending<'info> {
+        #[account(mut, signer)]
+        multisig: Account<'info, Multisig>,
+    }
+
+    pub fn change_max_pending(ctx: Context<ChangeMaxPending>, max_pending: u64) -> ProgramResult {
+        let multisig = &mut ctx.accounts.multisig;
+        multisig.max_pending = max_pending;
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct ChangeMaxInstructionDataLen<'info> {
+        #[account(mut, signer)]
+        multisig: Account<'info, Multisig>,
+    }
+
+    /// Sets the longest data, in bytes, a proposed instruction may carry. 0
+    /// disables the limit.
+    pub fn change_max_instruction_data_len(
+        ctx: Context<ChangeMaxInstructionDataLen>,
+        max_instruction_data_len: u32,
+    ) -> ProgramResult {
+        ctx.accounts.multisig.max_instruction_data_len = max_instruction_data_len;
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct ChangeProposalCooldown<'info> {
+        #[account(mut, signer)]
+        multisig: Account<'info, Multisig>,
+    }
+
+    /// Sets how long, in seconds, an owner must wait between proposals. 0
+    /// disables the limit.
+    pub fn change_proposal_cooldown(
+        ctx: Context<ChangeProposalCooldown>,
+        proposal_cooldown: i64,
+    ) -> ProgramResult {
+        require!(proposal_cooldown >= 0, InvalidDelay);
+        ctx.accounts.multisig.proposal_cooldown = proposal_cooldown;
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct ChangeCancelThreshold<'info> {
+        #[account(mut, signer)]
+        multisig: Account<'info, Multisig>,
+    }
+
+    pub fn change_cancel_threshold(
+        ctx: Context<ChangeCancelThreshold>,
+        cancel_threshold: u64,
+    ) -> ProgramResult {
+        let multisig = &mut ctx.accounts.multisig;
+        require!(
+            cancel_threshold >= 1 && cancel_threshold <= multisig.owners.len() as u64,
+            InvalidThreshold
+        );
+        multisig.cancel_threshold = cancel_threshold;
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct ChangeApprovalReward<'info> {
+        #[account(mut, signer)]
+        multisig: Account<'info, Multisig>,
+    }
+
+    /// Sets the lamports paid from the multisig PDA to an owner approving a
+    /// transaction before its eta. 0 disables rewards.
+    pub fn change_approval_reward(
+        ctx: Context<ChangeApprovalReward>,
+        approval_reward: u64,
+    ) -> ProgramResult {
+        ctx.accounts.multisig.approval_reward = approval_reward;
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct ChangeMaxTotalIncentives<'info> {
+        #[account(mut, signer)]
+        multisig: Account<'info, Multisig>,
+    }
+
+    /// Caps the lamports ever paid out as approval rewards. 0 removes the cap.
+    pub fn change_max_total_incentives(
+        ctx: Context<ChangeMaxTotalIncentives>,
+        max_total_incentives: u64,
+    ) -> ProgramResult {
+        ctx.accounts.multisig.max_total_incentives = max_total_incentives;
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct SetValueTiers<'info> {
+        #[account(mut, signer)]
+        multisig: Account<'info, Multisig>,
+    }
+
+    /// Replaces the value tiers. An empty list only requires the threshold.
+    pub fn set_value_tiers(
+        ctx: Context<SetValueTiers>,
+        value_tiers: Vec<ValueTier>,
+    ) -> ProgramResult {
+        let multisig = &mut ctx.accounts.multisig;
+        require!(value_tiers.len() <= MAX_VALUE_TIERS, TooManyValueTiers);
+        for tier in value_tiers.iter() {
+            require!(tier.threshold >= 1, InvalidThreshold);
+            validate_threshold(multisig, tier.threshold)?;
+        }
+        multisig.value_tiers = value_tiers;
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct ChangeProposalRentSource<'info> {
+        #[account(mut, signer)]
+        multisig: Account<'info, Multisig>,
+    }
+
+    /// Sets who pays the rent of accounts made by `create_transaction`.
+    pub fn change_proposal_rent_source(
+        ctx: Context<ChangeProposalRentSource>,
+        proposal_rent_source: ProposalRentSource,
+    ) -> ProgramResult {
+        ctx.accounts.multisig.proposal_rent_source = proposal_rent_source;
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct SetProposalCutoff<'info> {
+        #[account(mut, signer)]
+        multisig: Account<'info, Multisig>,
+    }
+
+    /// Invalidates transactions created before `proposal_cutoff`, clamped to
+    /// the current time, which invalidates every other pending transaction.
+    pub fn set_proposal_cutoff(
+        ctx: Context<SetProposalCutoff>,
+        proposal_cutoff: i64,
+    ) -> ProgramResult {
+        ctx.accounts.multisig.proposal_cutoff = proposal_cutoff.min(clock()?.unix_timestamp);
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct ChangeOwnerSnapshotTtl<'info> {
+        #[account(mut, signer)]
+        multisig: Account<'info, Multisig>,
+    }
+
+    /// Sets how long, in seconds, new transactions stay valid under the
+    /// owners they were proposed with. 0 invalidates them on any owner
+    /// change.
+    pub fn change_owner_snapshot_ttl(
+        ctx: Context<ChangeOwnerSnapshotTtl>,
+        owner_snapshot_ttl: i64,
+    ) -> ProgramResult {
+        require!(owner_snapshot_ttl >= 0, InvalidDelay);
+        ctx.accounts.multisig.owner_snapshot_ttl = owner_snapshot_ttl;
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct ChangeMinOwners<'info> {
+        #[account(mut, signer)]
+        multisig: Account<'info, Multisig>,
+    }
+
+    /// Sets the fewest owners the multisig may be left with, at least 1 and
+    /// at most the current number of owners.
+    pub fn change_min_owners(ctx: Context<ChangeMinOwners>, min_owners: u8) -> ProgramResult {
+        let multisig = &mut ctx.accounts.multisig;
+        require!(min_owners >= 1, TooFewOwners);
+        multisig.min_owners = min_owners;
+        bump_config_version(multisig)?;
+        assert_invariants(multisig)?;
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct ChangeApprovalTtl<'info> {
+        #[account(mut, signer)]
+        multisig: Account<'info, Multisig>,
+    }
+
+    pub fn change_approval_ttl(
+        ctx: Context<ChangeApprovalTtl>,
+        approval_ttl: i64,
+    ) -> ProgramResult {
+        let multisig = &mut ctx.accounts.multisig;
+        require!(approval_ttl >= 0, InvalidDelay);
+        multisig.approval_ttl = approval_ttl;
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct ChangePreserveApprovals<'info> {
+        #[account(mut, signer)]
+        multisig: Account<'info, Multisig>,
+    }
+
+    pub fn change_preserve_approvals(
+        ctx: Context<ChangePreserveApprovals>,
+        preserve_approvals_on_removal: bool,
+    ) -> ProgramResult {
+        let multisig = &mut ctx.accounts.multisig;
+        multisig.preserve_approvals_on_removal = preserve_approvals_on_removal;
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct ChangePreserveApprovalsOnReorder<'info> {
+        #[account(mut, signer)]
+        multisig: Account<'info, Multisig>,
+    }
+
+    pub fn change_preserve_approvals_on_reorder(
+        ctx: Context<ChangePreserveApprovalsOnReorder>,
+        preserve_approvals_on_reorder: bool,
+    ) -> ProgramResult {
+        let multisig = &mut ctx.accounts.multisig;
+        multisig.preserve_approvals_on_reorder = preserve_approvals_on_reorder;
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct SetExecutorAllowlist<'info> {
+        #[account(mut, signer)]
+        multisig: Account<'info, Multisig>,
+    }
+
+    /// Restricts execution to the given owners. An empty list lets any owner
+    /// execute. Owners are dropped from the list when removed.
+    pub fn set_executor_allowlist(
+        ctx: Context<SetExecutorAllowlist>,
+        executor_allowlist: Vec<Pubkey>,
+    ) -> ProgramResult {
+        let multisig = &mut ctx.accounts.multisig;
+        require_unique_owners(&executor_allowlist)?;
+        require!(
+            executor_allowlist
+                .iter()
+                .all(|k| multisig.owners.contains(k)),
+            InvalidOwner
+        );
+        multisig.executor_allowlist = executor_allowlist;
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct ChangeEnforceSeparation<'info> {
+        #[account(mut, signer)]
+        multisig: Account<'info, Multisig>,
+    }
+
+    /// When set, a transaction must be executed by an owner other than its
+    /// proposer.
+    pub fn change_enforce_separation(
+        ctx: Context<ChangeEnforceSeparation>,
+        enforce_separation: bool,
+    ) -> ProgramResult {
+        ctx.accounts.multisig.enforce_separation = enforce_separation;
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct ChangeRequireDistinctSlots<'info> {
+        #[account(mut, signer)]
+        multisig: Account<'info, Multisig>,
+    }
+
+    /// When set, approvals only count towards the threshold if made in
+    /// distinct slots, so a single bundle of owner signatures isn't enough.
+    pub fn change_require_distinct_slots(
+        ctx: Context<ChangeRequireDistinctSlots>,
+        require_distinct_slots: bool,
+    ) -> ProgramResult {
+        ctx.accounts.multisig.require_distinct_slots = require_distinct_slots;
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct ChangeMinDistinctRecentProposers<'info> {
+        #[account(mut, signer)]
+        multisig: Account<'info, Multisig>,
+    }
+
+    pub fn change_min_distinct_recent_proposers(
+        ctx: Context<ChangeMinDistinctRecentProposers>,
+        min_distinct_recent_proposers: u8,
+    ) -> ProgramResult {
+        let multisig = &mut ctx.accounts.multisig;
+        require!(
+            min_distinct_recent_proposers as usize <= multisig.owners.len(),
+            InvalidProposerDiversity
+        );
+        multisig.min_distinct_recent_proposers = min_distinct_recent_proposers;
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct SetAllowance<'info> {
+        #[account(mut, signer)]
+        multisig: Account<'info, Multisig>,
+    }
+
+    pub fn set_allowance(
+        ctx: Context<SetAllowance>,
+        owner: Pubkey,
+        allowance: u64,
+    ) -> ProgramResult {
+        let multisig = &mut ctx.accounts.multisig;
+        let owner_index = multisig
+            .owners
+            .iter()
+            .position(|a| *a == owner)
+            .ok_or(ErrorCode::InvalidOwner)?;
+        let num_owners = multisig.owners.len();
+        multisig.allowance.resize(num_owners, 0);
+        multisig.allowance_spent.resize(num_owners, 0);
+        multisig.allowance[owner_index] = allowance;
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct ChangeAllowancePeriod<'info> {
+        #[account(mut, signer)]
+        multisig: Account<'info, Multisig>,
+    }
+
+    /// Sets how often, in seconds, spent allowances reset. 0 never resets them.
+    pub fn change_allowance_period(
+        ctx: Context<ChangeAllowancePeriod>,
+        allowance_period: i64,
+    ) -> ProgramResult {
+        let multisig = &mut ctx.accounts.multisig;
+        require!(allowance_period >= 0, InvalidDelay);
+        multisig.allowance_period = allowance_period;
+        multisig.allowance_period_start = clock()?.unix_timestamp;
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    #[instruction(
+        instructions: Vec<TransactionInstruction>,
+        bump: u8,
+        canonicalize: bool,
+        action_hints: Vec<u8>,
+        min_approvals: u64,
+        title: Title,
+        memo: String
+    )]
+    pub struct CreateTransaction<'info> {
+        #[account(mut)]
+        signer: Signer<'info>,
+        #[account(mut)]
+        multisig: Account<'info, Multisig>,
+        #[account(
+            mut,
+            seeds = [
+                b"transaction",
+                multisig.key().to_bytes().as_ref(),
+                multisig.num_transactions.to_le_bytes().as_ref()
+            ],
+            bump = bump,
+        )]
+        transaction: AccountInfo<'info>,
+        system_program: Program<'info, System>,
+    }
+
+    /// The transaction account's rent is paid by the signer or the multisig
+    /// PDA, following `proposal_rent_source`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_transaction(
+        ctx: Context<CreateTransaction>,
+        instructions: Vec<TransactionInstruction>,
+        bump: u8,
+        canonicalize: bool,
+        action_hints: Vec<u8>,
+        min_approvals: u64,
+        title: Title,
+        memo: String,
+        auto_execute: bool,
+    ) -> ProgramResult {
+        // Instructions writing to the multisig are serialized, so this sees
+        // any owner change landing before it, even in the same slot.
+        require!(
+            ctx.accounts
+                .multisig
+                .owners
+                .contains(ctx.accounts.signer.key),
+            InvalidOwner
+        );
+        let signer = ctx.accounts.signer.to_account_info();
+        create_transaction_account(
+            &ctx.accounts.multisig,
+            &ctx.accounts.transaction,
+            proposal_rent_payer(&ctx.accounts.multisig, &signer),
+            &ctx.accounts.system_program,
+            bump,
+            None,
+            proposal_space(&ctx.accounts.multisig, instructions.clone(), memo.len()),
+        )?;
+
+        let mut tx = Transaction::default();
+        init_transaction(
+            &mut ctx.accounts.multisig,
+            &mut tx,
+            ctx.accounts.signer.key(),
+            instructions,
+            bump,
+            None,
+            canonicalize,
+            action_hints,
+            min_approvals,
+        )?;
+        set_title_and_memo(&mut tx, title, memo)?;
+        tx.auto_execute = auto_execute;
+        write_transaction(&ctx.accounts.transaction, &tx)?;
+        record_owner_activity(
+            &ctx.accounts.multisig.key(),
+            ctx.accounts.signer.key,
+            ctx.remaining_accounts,
+            |metadata| metadata.proposals_created = metadata.proposals_created.saturating_add(1),
+        )
+    }
+
+    #[derive(Accounts)]
+    #[instruction(instructions: Vec<TransactionInstruction>, nonce: u64, bump: u8)]
+    pub struct CreateTransactionWithNonce<'info> {
+        #[account(mut)]
+        signer: Signer<'info>,
+        #[account(mut)]
+        multisig: Account<'info, Multisig>,
+        #[account(
+            mut,
+            seeds = [
+                b"transaction_nonce",
+                multisig.key().to_bytes().as_ref(),
+                nonce.to_le_bytes().as_ref()
+            ],
+            bump = bump,
+        )]
+        transaction: AccountInfo<'info>,
+        system_program: Program<'info, System>,
+    }
+
+    /// Same as `create_transaction` but the transaction PDA is derived from a
+    /// client chosen `nonce` instead of `num_transactions`, so concurrent
+    /// proposals don't collide. `index` still comes from the counter.
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_transaction_with_nonce(
+        ctx: Context<CreateTransactionWithNonce>,
+        instructions: Vec<TransactionInstruction>,
+        nonce: u64,
+        bump: u8,
+        canonicalize: bool,
+        action_hints: Vec<u8>,
+        min_approvals: u64,
+        title: Title,
+        memo: String,
+        auto_execute: bool,
+    ) -> ProgramResult {
+        let signer = ctx.accounts.signer.to_account_info();
+        create_transaction_account(
+            &ctx.accounts.multisig,
+            &ctx.accounts.transaction,
+            proposal_rent_payer(&ctx.accounts.multisig, &signer),
+            &ctx.accounts.system_program,
+            bump,
+            Some(nonce),
+            proposal_space(&ctx.accounts.multisig, instructions.clone(), memo.len()),
+        )?;
+
+        let mut tx = Transaction::default();
+        init_transaction(
+            &mut ctx.accounts.multisig,
+            &mut tx,
+            ctx.accounts.signer.key(),
+            instructions,
+            bump,
+            Some(nonce),
+            canonicalize,
+            action_hints,
+            min_approvals,
+        )?;
+        set_title_and_memo(&mut tx, title, memo)?;
+        tx.auto_execute = auto_execute;
+        write_transaction(&ctx.accounts.transaction, &tx)?;
+        record_owner_activity(
+            &ctx.accounts.multisig.key(),
+            ctx.accounts.signer.key,
+            ctx.remaining_accounts,
+            |metadata| metadata.proposals_created = metadata.proposals_created.saturating_add(1),
+        )
+    }
+
+    #[derive(Accounts)]
+    #[instruction(instructions: Vec<TransactionInstruction>, bump: u8)]
+    pub struct CreateTransactionPdaFunded<'info> {
+        signer: Signer<'info>,
+        #[account(mut)]
+        multisig: Account<'info, Multisig>,
+        #[account(
+            mut,
+            seeds = [
+                b"transaction",
+                multisig.key().to_bytes().as_ref(),
+                multisig.num_transactions.to_le_bytes().as_ref()
+            ],
+            bump = bump,
+        )]
+        transaction: AccountInfo<'info>,
+        system_program: Program<'info, System>,
+    }
+
+    /// Same as `create_transaction` but the rent for the transaction account is
+    /// paid by the multisig PDA.
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_transaction_pda_funded(
+        ctx: Context<CreateTransactionPdaFunded>,
+        instructions: Vec<TransactionInstruction>,
+        bump: u8,
+        canonicalize: bool,
+        action_hints: Vec<u8>,
+        min_approvals: u64,
+        title: Title,
+        memo: String,
+        auto_execute: bool,
+    ) -> ProgramResult {
+        require!(
+            ctx.accounts
+                .multisig
+                .owners
+                .contains(ctx.accounts.signer.key),
+            InvalidOwner
+        );
+        create_transaction_account(
+            &ctx.accounts.multisig,
+            &ctx.accounts.transaction,
+            None,
+            &ctx.accounts.system_program,
+            bump,
+            None,
+            proposal_space(&ctx.accounts.multisig, instructions.clone(), memo.len()),
+        )?;
+
+        let mut tx = Transaction::default();
+        init_transaction(
+            &mut ctx.accounts.multisig,
+            &mut tx,
+            ctx.accounts.signer.key(),
+            instructions,
+            bump,
+            None,
+            canonicalize,
+            action_hints,
+            min_approvals,
+        )?;
+        set_title_and_memo(&mut tx, title, memo)?;
+        tx.auto_execute = auto_execute;
+        write_transaction(&ctx.accounts.transaction, &tx)?;
+        record_owner_activity(
+            &ctx.accounts.multisig.key(),
+            ctx.accounts.signer.key,
+            ctx.remaining_accounts,
+            |metadata| metadata.proposals_created = metadata.proposals_created.saturating_add(1),
+        )
+    }
+
+    #[derive(Accounts)]
+    #[instruction(
+        template_id: u64,
+        bump: u8,
+        instructions: Vec<TransactionInstruction>,
+        params: Vec<TemplateParam>
+    )]
+    pub struct CreateTemplate<'info> {
+        #[account(mut)]
+        signer: Signer<'info>,
+        multisig: Account<'info, Multisig>,
+        #[account(
+            init,
+            seeds = [
+                b"template",
+                multisig.key().to_bytes().as_ref(),
+                template_id.to_le_bytes().as_ref()
+            ],
+            bump = bump,
+            payer = signer,
+            space = template_space(&instructions, &params),
+        )]
+        template: Account<'info, ProposalTemplate>,
+        system_program: Program<'info, System>,
+    }
+
+    /// Stores `instructions` as a template for owners to propose with
+    /// different `params`. Templates aren't approved themselves, the
+    /// transactions made from them are.
+    pub fn create_template(
+        ctx: Context<CreateTemplate>,
+        template_id: u64,
+        bump: u8,
+        instructions: Vec<TransactionInstruction>,
+        params: Vec<TemplateParam>,
+    ) -> ProgramResult {
+        let multisig = &ctx.accounts.multisig;
+        require!(
+            multisig.owners.contains(ctx.accounts.signer.key),
+            InvalidOwner
+        );
+        require_canonical_bump(
+            &[
+                b"template",
+                multisig.key().as_ref(),
+                template_id.to_le_bytes().as_ref(),
+            ],
+            bump,
+        )?;
+        // Checks every parameter fits the instructions.
+        let zeroes = vec![0; params.iter().map(template_param_len).sum()];
+        instantiate_template(&instructions, &params, &zeroes)?;
+
+        let template = &mut ctx.accounts.template;
+        template.multisig = multisig.key();
+        template.template_id = template_id;
+        template.bump = bump;
+        template.creator = ctx.accounts.signer.key();
+        template.instructions = instructions;
+        template.params = params;
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    #[instruction(bump: u8, params: Vec<u8>)]
+    pub struct CreateTransactionFromTemplate<'info> {
+        #[account(mut)]
+        signer: Signer<'info>,
+        #[account(mut)]
+        multisig: Account<'info, Multisig>,
+        #[account(has_one = multisig @ ErrorCode::MultisigMismatch)]
+        template: Account<'info, ProposalTemplate>,
+        #[account(
+            mut,
+            seeds = [
+                b"transaction",
+                multisig.key().to_bytes().as_ref(),
+                multisig.num_transactions.to_le_bytes().as_ref()
+            ],
+            bump = bump,
+        )]
+        transaction: AccountInfo<'info>,
+        system_program: Program<'info, System>,
+    }
+
+    /// Same as `create_transaction` with the template's instructions, `params`
+    /// holding the bytes of each of its parameters in order.
+    pub fn create_transaction_from_template(
+        ctx: Context<CreateTransactionFromTemplate>,
+        bump: u8,
+        params: Vec<u8>,
+    ) -> ProgramResult {
+        require!(
+            ctx.accounts
+                .multisig
+                .owners
+                .contains(ctx.accounts.signer.key),
+            InvalidOwner
+        );
+        let template = &ctx.accounts.template;
+        let instructions = instantiate_template(&template.instructions, &template.params, &params)?;
+        let signer = ctx.accounts.signer.to_account_info();
+        create_transaction_account(
+            &ctx.accounts.multisig,
+            &ctx.accounts.transaction,
+            proposal_rent_payer(&ctx.accounts.multisig, &signer),
+            &ctx.accounts.system_program,
+            bump,
+            None,
+            proposal_space(&ctx.accounts.multisig, instructions.clone(), 0),
+        )?;
+
+        let mut tx = Transaction::default();
+        init_transaction(
+            &mut ctx.accounts.multisig,
+            &mut tx,
+            ctx.accounts.signer.key(),
+            instructions,
+            bump,
+            None,
+            false,
+            Vec::new(),
+            0,
+        )?;
+        write_transaction(&ctx.accounts.transaction, &tx)?;
+        record_owner_activity(
+            &ctx.accounts.multisig.key(),
+            ctx.accounts.signer.key,
+            ctx.remaining_accounts,
+            |metadata| metadata.proposals_created = metadata.proposals_created.saturating_add(1),
+        )
+    }
+
+    #[derive(Accounts)]
+    #[instruction(bump: u8)]
+    pub struct ProposeNonceCreate<'info> {
+        #[account(mut)]
+        signer: Signer<'info>,
+        #[account(mut)]
+        multisig: Account<'info, Multisig>,
+        #[account(
+            mut,
+            seeds = [
+                b"transaction",
+                multisig.key().to_bytes().as_ref(),
+                multisig.num_transactions.to_le_bytes().as_ref()
+            ],
+            bump = bump,
+        )]
+        transaction: AccountInfo<'info>,
+        system_program: Program<'info, System>,
+    }
+
+    /// Proposes creating a durable nonce account at the address derived from
+    /// the multisig PDA and `seed`, with the multisig PDA as its authority.
+    /// `funder` pays for it and has to sign the execution.
+    pub fn propose_nonce_create(
+        ctx: Context<ProposeNonceCreate>,
+        bump: u8,
+        funder: Pubkey,
+        seed: String,
+        lamports: u64,
+    ) -> ProgramResult {
+        let instructions =
+            nonce_create_instructions(&ctx.accounts.multisig.key(), &funder, &seed, lamports)?;
+        let signer = ctx.accounts.signer.to_account_info();
+        create_transaction_account(
+            &ctx.accounts.multisig,
+            &ctx.accounts.transaction,
+            proposal_rent_payer(&ctx.accounts.multisig, &signer),
+            &ctx.accounts.system_program,
+            bump,
+            None,
+            proposal_space(&ctx.accounts.multisig, instructions.clone(), 0),
+        )?;
+
+        let mut tx = Transaction::default();
+        init_transaction(
+            &mut ctx.accounts.multisig,
+            &mut tx,
+            ctx.accounts.signer.key(),
+            instructions,
+            bump,
+            None,
+            false,
+            Vec::new(),
+            0,
+        )?;
+        write_transaction(&ctx.accounts.transaction, &tx)?;
+        record_owner_activity(
+            &ctx.accounts.multisig.key(),
+            ctx.accounts.signer.key,
+            ctx.remaining_accounts,
+            |metadata| metadata.proposals_created = metadata.proposals_created.saturating_add(1),
+        )
+    }
+
+    #[derive(Accounts)]
+    #[instruction(bump: u8)]
+    pub struct ProposeNonceAdvance<'info> {
+        #[account(mut)]
+        signer: Signer<'info>,
+        #[account(mut)]
+        multisig: Account<'info, Multisig>,
+        #[account(
+            mut,
+            seeds = [
+                b"transaction",
+                multisig.key().to_bytes().as_ref(),
+                multisig.num_transactions.to_le_bytes().as_ref()
+            ],
+            bump = bump,
+        )]
+        transaction: AccountInfo<'info>,
+        system_program: Program<'info, System>,
+    }
+
+    /// Proposes advancing a durable nonce account the multisig PDA is the
+    /// authority of.
+    pub fn propose_nonce_advance(
+        ctx: Context<ProposeNonceAdvance>,
+        bump: u8,
+        nonce: Pubkey,
+    ) -> ProgramResult {
+        let instructions = nonce_advance_instructions(&ctx.accounts.multisig.key(), &nonce);
+        let signer = ctx.accounts.signer.to_account_info();
+        create_transaction_account(
+            &ctx.accounts.multisig,
+            &ctx.accounts.transaction,
+            proposal_rent_payer(&ctx.accounts.multisig, &signer),
+            &ctx.accounts.system_program,
+            bump,
+            None,
+            proposal_space(&ctx.accounts.multisig, instructions.clone(), 0),
+        )?;
+
+        let mut tx = Transaction::default();
+        init_transaction(
+            &mut ctx.accounts.multisig,
+            &mut tx,
+            ctx.accounts.signer.key(),
+            instructions,
+            bump,
+            None,
+            false,
+            Vec::new(),
+            0,
+        )?;
+        write_transaction(&ctx.accounts.transaction, &tx)?;
+        record_owner_activity(
+            &ctx.accounts.multisig.key(),
+            ctx.accounts.signer.key,
+            ctx.remaining_accounts,
+            |metadata| metadata.proposals_created = metadata.proposals_created.saturating_add(1),
+        )
+    }
+
+    #[derive(Accounts)]
+    #[instruction(bump: u8)]
+    pub struct ProposeChangeThreshold<'info> {
+        #[account(mut)]
+        signer: Signer<'info>,
+        #[account(mut)]
+        multisig: Account<'info, Multisig>,
+        #[account(
+            mut,
+            seeds = [
+                b"transaction",
+                multisig.key().to_bytes().as_ref(),
+                multisig.num_transactions.to_le_bytes().as_ref()
+            ],
+            bump = bump,
+        )]
+        transaction: AccountInfo<'info>,
+        system_program: Program<'info, System>,
+    }
+
+    /// Proposes calling `change_threshold` on the multisig, e.g. to restore
+    /// some margin after an owner lost their key. It goes through the delay
+    /// like any other proposal.
+    pub fn propose_change_threshold(
+        ctx: Context<ProposeChangeThreshold>,
+        bump: u8,
+        threshold: u64,
+    ) -> ProgramResult {
+        validate_threshold(&ctx.accounts.multisig, threshold)?;
+        let instructions = change_threshold_instructions(&ctx.accounts.multisig.key(), threshold);
+        let signer = ctx.accounts.signer.to_account_info();
+        create_transaction_account(
+            &ctx.accounts.multisig,
+            &ctx.accounts.transaction,
+            proposal_rent_payer(&ctx.accounts.multisig, &signer),
+            &ctx.accounts.system_program,
+            bump,
+            None,
+            proposal_space(&ctx.accounts.multisig, instructions.clone(), 0),
+        )?;
+
+        let mut tx = Transaction::default();
+        init_transaction(
+            &mut ctx.accounts.multisig,
+            &mut tx,
+            ctx.accounts.signer.key(),
+            instructions,
+            bump,
+            None,
+            false,
+            Vec::new(),
+            0,
+        )?;
+        write_transaction(&ctx.accounts.transaction, &tx)?;
+        record_owner_activity(
+            &ctx.accounts.multisig.key(),
+            ctx.accounts.signer.key,
+            ctx.remaining_accounts,
+            |metadata| metadata.proposals_created = metadata.proposals_created.saturating_add(1),
+        )
+    }
+
+    #[derive(Accounts)]
+    #[instruction(instructions: Vec<TransactionInstruction>, bump: u8)]
+    pub struct ImportTransaction<'info> {
+        #[account(
+            mut,
+            constraint = signer.key() != Pubkey::default()
+                && signer.key() == multisig.migration_authority @ ErrorCode::InvalidOwner
+        )]
+        signer: Signer<'info>,
+        #[account(mut)]
+        multisig: Account<'info, Multisig>,
+        #[account(
+            mut,
+            seeds = [
+                b"transaction",
+                multisig.key().to_bytes().as_ref(),
+                multisig.num_transactions.to_le_bytes().as_ref()
+            ],
+            bump = bump,
+        )]
+        transaction: AccountInfo<'info>,
+        system_program: Program<'info, System>,
+    }
+
+    /// Creates a transaction mirroring a pending proposal of another multisig,
+    /// with its approvals and eta. Only the migration authority set at
+    /// creation can import, until it completes the migration or the multisig
+    /// executes its first transaction.
+    pub fn import_transaction(
+        ctx: Context<ImportTransaction>,
+        instructions: Vec<TransactionInstruction>,
+        bump: u8,
+        signers: Vec<bool>,
+        eta: i64,
+    ) -> ProgramResult {
+        let signer = ctx.accounts.signer.to_account_info();
+        create_transaction_account(
+            &ctx.accounts.multisig,
+            &ctx.accounts.transaction,
+            proposal_rent_payer(&ctx.accounts.multisig, &signer),
+            &ctx.accounts.system_program,
+            bump,
+            None,
+            proposal_space(&ctx.accounts.multisig, instructions.clone(), 0),
+        )?;
+
+        let multisig = &mut ctx.accounts.multisig;
+        require!(
+            !multisig.migration_complete && multisig.executed_transactions == 0,
+            MigrationComplete
+        );
+        require!(signers.len() == multisig.owners.len(), InvalidOwner);
+        require!(
+            (signers.iter().filter(|&&s| s).count() as u64) < multisig.threshold,
+            TooManyImportedApprovals
+        );
+        require_canonical_bump(
+            &[
+                b"transaction",
+                multisig.key().as_ref(),
+                multisig.num_transactions.to_le_bytes().as_ref(),
+            ],
+            bump,
+        )?;
+        require!(
+            multisig.max_pending == 0 || multisig.pending_transactions < multisig.max_pending,
+            TooManyPending
+        );
+        validate_instructions(multisig, &instructions)?;
+
+        let clock = clock()?;
+        let tx = Transaction {
+            multisig: multisig.key(),
+            index: multisig.num_transactions,
+            bump,
+            layout_version: TRANSACTION_LAYOUT_VERSION,
+            // Never sooner than a proposal made now.
+            eta: eta.max(eta_after(clock.unix_timestamp, multisig.delay)?),
+            created_at: clock.unix_timestamp,
+            owners_seq_no: multisig.owners_seq_no,
+            proposer: ctx.accounts.signer.key(),
+            instructions,
+            approved_at: signers
+                .iter()
+                .map(|&s| if s { clock.unix_timestamp } else { 0 })
+                .collect(),
+            approved_slot: signers
+                .iter()
+                .map(|&s| if s { clock.slot } else { 0 })
+                .collect(),
+            signers,
+            ..Transaction::default()
+        };
+        write_transaction(&ctx.accounts.transaction, &tx)?;
+        record_proposal(multisig, &tx)?;
+        // Imported approvals count as the owners' own.
+        for (owner, _) in multisig
+            .owners
+            .iter()
+            .zip(tx.signers.iter())
+            .filter(|(_, &signed)| signed)
+        {
+            record_owner_activity(&multisig.key(), owner, ctx.remaining_accounts, |metadata| {
+                metadata.approvals = metadata.approvals.saturating_add(1)
+            })?;
+        }
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct CompleteMigration<'info> {
+        #[account(
+            constraint = signer.key() == multisig.migration_authority @ ErrorCode::InvalidOwner
+        )]
+        signer: Signer<'info>,
+        #[account(mut)]
+        multisig: Account<'info, Multisig>,
+    }
+
+    /// Permanently disables `import_transaction`.
+    pub fn complete_migration(ctx: Context<CompleteMigration>) -> ProgramResult {
+        ctx.accounts.multisig.migration_complete = true;
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct PdaAvailableBalance<'info> {
+        multisig: Account<'info, Multisig>,
+    }
+
+    /// Returns the lamports the multisig PDA can spend without dropping below
+    /// rent-exemption.
+    pub fn pda_available_balance(ctx: Context<PdaAvailableBalance>) -> ProgramResult {
+        let available = available_balance(&ctx.accounts.multisig.to_account_info())?;
+        solana_program::program::set_return_data(&available.try_to_vec()?);
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct TransactionSigners<'info> {
+        multisig: Account<'info, Multisig>,
+        #[account(has_one = multisig @ ErrorCode::MultisigMismatch)]
+        transaction: Account<'info, Transaction>,
+    }
+
+    /// Returns each owner paired with whether they have approved the
+    /// transaction, in owner order. Approvals from before an owner change
+    /// no longer count, so they're reported as unsigned.
+    pub fn transaction_signers(ctx: Context<TransactionSigners>) -> ProgramResult {
+        require_transaction_of(&ctx.accounts.multisig, &ctx.accounts.transaction)?;
+        let multisig = &ctx.accounts.multisig;
+        let tx = &ctx.accounts.transaction;
+        let current = multisig.owners_seq_no == tx.owners_seq_no;
+        let signers: Vec<(Pubkey, bool)> = multisig
+            .owners
+            .iter()
+            .enumerate()
+            .map(|(i, owner)| (*owner, current && tx.signers.get(i) == Some(&true)))
+            .collect();
+        solana_program::program::set_return_data(&signers.try_to_vec()?);
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct CanExecute<'info> {
+        multisig: Account<'info, Multisig>,
+        #[account(has_one = multisig @ ErrorCode::MultisigMismatch)]
+        transaction: Account<'info, Transaction>,
+    }
+
+    /// Returns, as a bool, whether the transaction would be ready to execute
+    /// if `owner` approved it now. Always false for non-owners, who can't
+    /// approve.
+    pub fn can_execute(ctx: Context<CanExecute>, owner: Pubkey) -> ProgramResult {
+        require_transaction_of(&ctx.accounts.multisig, &ctx.accounts.transaction)?;
+        let multisig = &ctx.accounts.multisig;
+        let mut tx: Transaction = (*ctx.accounts.transaction).clone();
+        let clock = clock()?;
+        let ready = match multisig.owners.iter().position(|a| *a == owner) {
+            Some(owner_index) if owner_index < tx.signers.len() => {
+                let num_signers = tx.signers.len();
+                tx.signers[owner_index] = true;
+                tx.approved_at.resize(num_signers, 0);
+                tx.approved_at[owner_index] = clock.unix_timestamp;
+                tx.approved_slot.resize(num_signers, 0);
+                tx.approved_slot[owner_index] = clock.slot;
+                transaction_ready(multisig, &tx, clock.unix_timestamp)
+            }
+            _ => false,
+        };
+        solana_program::program::set_return_data(&ready.try_to_vec()?);
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct ValidateProposal<'info> {
+        signer: Signer<'info>,
+        multisig: Account<'info, Multisig>,
+    }
+
+    /// Runs the checks `create_transaction` would for the signer proposing
+    /// `instructions` with `action_hints` and `min_approvals`, without
+    /// creating anything. Returns `None`, or the first error's code, as an
+    /// `Option<u32>`.
+    pub fn validate_proposal(
+        ctx: Context<ValidateProposal>,
+        instructions: Vec<TransactionInstruction>,
+        action_hints: Vec<u8>,
+        min_approvals: u64,
+    ) -> ProgramResult {
+        let now = clock()?.unix_timestamp;
+        let error = match check_proposal(
+            &ctx.accounts.multisig,
+            ctx.accounts.signer.key,
+            &instructions,
+            &action_hints,
+            min_approvals,
+            now,
+        )
+        .map_err(ProgramError::from)
+        {
+            Ok(()) => None,
+            Err(ProgramError::Custom(code)) => Some(code),
+            Err(err) => return Err(err),
+        };
+        solana_program::program::set_return_data(&error.try_to_vec()?);
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct AmIOwner<'info> {
+        signer: Signer<'info>,
+        multisig: Account<'info, Multisig>,
+    }
+
+    /// Returns whether the signer is an owner, as a bool, followed by their
+    /// index as an `Option<u32>`.
+    pub fn am_i_owner(ctx: Context<AmIOwner>) -> ProgramResult {
+        let index = ctx
+            .accounts
+            .multisig
+            .owners
+            .iter()
+            .position(|a| a == ctx.accounts.signer.key)
+            .map(|i| i as u32);
+        solana_program::program::set_return_data(&(index.is_some(), index).try_to_vec()?);
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct VerifyMultisigPda<'info> {
+        multisig: Account<'info, Multisig>,
+    }
+
+    /// Returns, as a bool, whether the multisig is the PDA of `base` with its
+    /// stored bump.
+    pub fn verify_multisig_pda(ctx: Context<VerifyMultisigPda>, base: Pubkey) -> ProgramResult {
+        let multisig = &ctx.accounts.multisig;
+        let valid = multisig.base == base
+            && Pubkey::create_program_address(&multisig_signer_seeds(multisig), ctx.program_id)
+                == Ok(multisig.key());
+        solana_program::program::set_return_data(&valid.try_to_vec()?);
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct ExportConfig<'info> {
+        multisig: Account<'info, Multisig>,
+    }
+
+    /// Returns the multisig's settings as an `ExportedConfig`. Fails when
+    /// they don't fit in return data, with many owners and executors.
+    pub fn export_config(ctx: Context<ExportConfig>) -> ProgramResult {
+        let multisig = &ctx.accounts.multisig;
+        let exported = ExportedConfig {
+            config: MultisigConfig {
+                owners: multisig.owners.clone(),
+                threshold: multisig.threshold,
+                delay: multisig.delay,
+                grace_period: multisig.grace_period,
+                min_delay: multisig.min_delay,
+                max_pending: multisig.max_pending,
+                approval_ttl: multisig.approval_ttl,
+                proposal_cooldown: multisig.proposal_cooldown,
+                cancel_threshold: multisig.cancel_threshold,
+                approval_reward: multisig.approval_reward,
+                max_total_incentives: multisig.max_total_incentives,
+                max_instruction_data_len: multisig.max_instruction_data_len,
+                min_owners: multisig.min_owners.max(1),
+                purpose: multisig.purpose,
+                migration_authority: multisig.migration_authority,
+            },
+            config_version: multisig.config_version,
+            preserve_approvals_on_removal: multisig.preserve_approvals_on_removal,
+            preserve_approvals_on_reorder: multisig.preserve_approvals_on_reorder,
+            require_distinct_slots: multisig.require_distinct_slots,
+            enforce_separation: multisig.enforce_separation,
+            proposal_rent_source: multisig.proposal_rent_source,
+            allowance_period: multisig.allowance_period,
+            recovery_owner: multisig.recovery_owner,
+            recovery_inactivity: multisig.recovery_inactivity,
+            post_change_cooldown: multisig.post_change_cooldown,
+            failsafe_beneficiary: multisig.failsafe_beneficiary,
+            inactivity_timeout: multisig.inactivity_timeout,
+            min_distinct_recent_proposers: multisig.min_distinct_recent_proposers,
+            owner_snapshot_ttl: multisig.owner_snapshot_ttl,
+            proposal_cutoff: multisig.proposal_cutoff,
+            audit_log: multisig.audit_log,
+            executor_allowlist: multisig.executor_allowlist.clone(),
+            value_tiers: multisig.value_tiers.clone(),
+            allowance: multisig.allowance.clone(),
+        };
+        solana_program::program::set_return_data(&exported.try_to_vec()?);
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct ListTransactions<'info> {
+        multisig: Account<'info, Multisig>,
+    }
+
+    /// Returns a `Vec<TransactionSummary>`, by index, of the transactions in
+    /// the remaining accounts with an index in `start..start + limit`.
+    /// Accounts of other multisigs are ignored.
+    pub fn list_transactions(
+        ctx: Context<ListTransactions>,
+        start: u64,
+        limit: u8,
+    ) -> ProgramResult {
+        require!(limit as usize <= MAX_LIST_TRANSACTIONS, BatchTooLarge);
+        let multisig = &ctx.accounts.multisig;
+        let now = clock()?.unix_timestamp;
+        let end = start.saturating_add(limit as u64);
+        let mut summaries = Vec::new();
+        for info in ctx.remaining_accounts.iter() {
+            let tx = Account::<Transaction>::try_from(info)?;
+            if tx.multisig != multisig.key() || tx.index < start || tx.index >= end {
+                continue;
+            }
+            let signer_count = if tx.owners_seq_no == multisig.owners_seq_no {
+                approval_count(multisig, &tx, now)
+            } else {
+                0
+            };
+            summaries.push(TransactionSummary {
+                index: tx.index,
+                eta: tx.eta,
+                executed: tx.executed_at != 0,
+                signer_count: signer_count as u8,
+                num_instructions: tx.instructions.len() as u8,
+            });
+        }
+        summaries.sort_by_key(|s| s.index);
+        solana_program::program::set_return_data(&summaries.try_to_vec()?);
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct TimeUntil<'info> {
+        multisig: Account<'info, Multisig>,
+        #[account(has_one = multisig @ ErrorCode::MultisigMismatch)]
+        transaction: Account<'info, Transaction>,
+    }
+
+    /// Returns, as an i64, the seconds left until the transaction's eta, 0
+    /// once it's reached.
+    pub fn time_until_executable(ctx: Context<TimeUntil>) -> ProgramResult {
+        require_transaction_of(&ctx.accounts.multisig, &ctx.accounts.transaction)?;
+        let now = clock()?.unix_timestamp;
+        let remaining = ctx.accounts.transaction.eta.saturating_sub(now).max(0);
+        solana_program::program::set_return_data(&remaining.try_to_vec()?);
+        Ok(())
+    }
+
+    /// Returns, as an i64, the seconds left until the transaction's grace
+    /// period ends, 0 once it's over.
+    pub fn time_until_expiry(ctx: Context<TimeUntil>) -> ProgramResult {
+        require_transaction_of(&ctx.accounts.multisig, &ctx.accounts.transaction)?;
+        let now = clock()?.unix_timestamp;
+        let expiry = ctx
+            .accounts
+            .transaction
+            .eta
+            .saturating_add(ctx.accounts.multisig.grace_period);
+        let remaining = expiry.saturating_sub(now).max(0);
+        solana_program::program::set_return_data(&remaining.try_to_vec()?);
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct VerifyTransactionHash<'info> {
+        multisig: Account<'info, Multisig>,
+        #[account(has_one = multisig @ ErrorCode::MultisigMismatch)]
+        transaction: Account<'info, Transaction>,
+    }
+
+    /// Fails unless the transaction of `multisig` has instructions hashing to
+    /// `expected`, see `instructions_hash`. Lets reviewers confirm a proposal
+    /// before approving.
+    pub fn verify_transaction_hash(
+        ctx: Context<VerifyTransactionHash>,
+        expected: [u8; 32],
+    ) -> ProgramResult {
+        require_transaction_of(&ctx.accounts.multisig, &ctx.accounts.transaction)?;
+        let data = ctx.accounts.transaction.instructions.try_to_vec()?;
+        require!(instructions_hash(&data) == expected, HashMismatch);
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct EstimateSpace<'info> {
+        multisig: Account<'info, Multisig>,
+    }
+
+    /// Returns the account sizes allocated for a multisig and for a
+    /// transaction of `multisig` holding `instructions` and a memo of
+    /// `memo_len` bytes, as two u64s.
+    pub fn estimate_space(
+        ctx: Context<EstimateSpace>,
+        instructions: Vec<TransactionInstruction>,
+        memo_len: u32,
+    ) -> ProgramResult {
+        let space = (
+            multisig_space() as u64,
+            proposal_space(&ctx.accounts.multisig, instructions, memo_len as usize) as u64,
+        );
+        solana_program::program::set_return_data(&space.try_to_vec()?);
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    #[instruction(bump: u8)]
+    pub struct Heartbeat<'info> {
+        #[account(mut)]
+        signer: Signer<'info>,
+        multisig: Account<'info, Multisig>,
+        #[account(
+            init_if_needed,
+            seeds = [
+                b"owner",
+                multisig.key().to_bytes().as_ref(),
+                signer.key().to_bytes().as_ref()
+            ],
+            bump = bump,
+            payer = signer,
+            space = owner_metadata_space(),
+        )]
+        owner_metadata: Account<'info, OwnerMetadata>,
+        system_program: Program<'info, System>,
+    }
+
+    /// Records that the signing owner is still around, without approving
+    /// anything. Creates their metadata account on first use.
+    pub fn heartbeat(ctx: Context<Heartbeat>, bump: u8) -> ProgramResult {
+        require!(
+            ctx.accounts
+                .multisig
+                .owners
+                .contains(ctx.accounts.signer.key),
+            InvalidOwner
+        );
+        require_canonical_bump(
+            &[
+                b"owner",
+                ctx.accounts.multisig.key().as_ref(),
+                ctx.accounts.signer.key().as_ref(),
+            ],
+            bump,
+        )?;
+        let metadata = &mut ctx.accounts.owner_metadata;
+        metadata.multisig = ctx.accounts.multisig.key();
+        metadata.owner = ctx.accounts.signer.key();
+        metadata.bump = bump;
+        metadata.last_seen = clock()?.unix_timestamp;
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct OwnerActivityQuery<'info> {
+        owner_metadata: Account<'info, OwnerMetadata>,
+    }
+
+    /// Returns the owner's counters as an `OwnerActivity`. Proposals,
+    /// approvals and executions only count when given the owner's metadata
+    /// account in `remaining_accounts`.
+    pub fn owner_activity(ctx: Context<OwnerActivityQuery>) -> ProgramResult {
+        let metadata = &ctx.accounts.owner_metadata;
+        let activity = OwnerActivity {
+            owner: metadata.owner,
+            proposals_created: metadata.proposals_created,
+            approvals: metadata.approvals,
+            executions: metadata.executions,
+            last_seen: metadata.last_seen,
+        };
+        solana_program::program::set_return_data(&activity.try_to_vec()?);
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct Approve<'info> {
+        #[account(mut)]
+        signer: Signer<'info>,
+        #[account(mut)]
+        multisig: Account<'info, Multisig>,
+        #[account(mut, has_one = multisig @ ErrorCode::MultisigMismatch)]
+        transaction: Account<'info, Transaction>,
+    }
+
+    /// With `auto_execute`, an approval making the transaction ready also
+    /// executes it, `remaining_accounts` holding what `execute_transaction`
+    /// would need. A failing execution reverts the approval too.
+    pub fn approve<'info>(ctx: Context<'_, '_, '_, 'info, Approve<'info>>) -> ProgramResult {
+        require_transaction_of(&ctx.accounts.multisig, &ctx.accounts.transaction)?;
+        let now = clock()?.unix_timestamp;
+        let was_ready = transaction_ready(&ctx.accounts.multisig, &ctx.accounts.transaction, now);
+        let owner_index = check_approval(
+            &ctx.accounts.multisig,
+            &ctx.accounts.transaction,
+            ctx.accounts.signer.key,
+            now,
+        )?;
+        let tx = &mut ctx.accounts.transaction;
+        let first_approval = is_first_approval(tx, owner_index);
+        let num_signers = tx.signers.len();
+        tx.signers[owner_index] = true;
+        // Transactions created before approval timestamps were tracked.
+        tx.approved_at.resize(num_signers, 0);
+        tx.approved_at[owner_index] = now;
+        tx.approved_slot.resize(num_signers, 0);
+        tx.approved_slot[owner_index] = clock()?.slot;
+        if first_approval {
+            record_owner_activity(
+                &ctx.accounts.multisig.key(),
+                ctx.accounts.signer.key,
+                ctx.remaining_accounts,
+                |metadata| metadata.approvals = metadata.approvals.saturating_add(1),
+            )?;
+        }
+
+        // Rewards are best effort: an underfunded PDA skips the payment
+        // rather than blocking the approval or dipping below rent exemption.
+        // Once the incentives cap is reached rewards stop, approvals don't.
+        let multisig = &mut ctx.accounts.multisig;
+        let reward = multisig.approval_reward;
+        let paid = multisig.total_incentives_paid.saturating_add(reward);
+        let within_cap =
+            multisig.max_total_incentives == 0 || paid <= multisig.max_total_incentives;
+        let multisig_info = multisig.to_account_info();
+        if reward > 0
+            && first_approval
+            && now < tx.eta
+            && within_cap
+            && available_balance(&multisig_info)? >= reward
+        {
+            multisig.total_incentives_paid = paid;
+            **multisig_info.try_borrow_mut_lamports()? -= reward;
+            **ctx.accounts.signer.try_borrow_mut_lamports()? += reward;
+        }
+
+        let signer_key = ctx.accounts.signer.key();
+        let multisig = &ctx.accounts.multisig;
+        let tx = &ctx.accounts.transaction;
+        if tx.auto_execute
+            && tx.group_size == 0
+            && !was_ready
+            && transaction_ready(multisig, tx, now)
+            && multisig.owners.contains(&signer_key)
+            && executor_allowed(multisig, &signer_key)
+            && separation_respected(multisig, tx, &signer_key)
+        {
+            execute(
+                &mut ctx.accounts.multisig,
+                &mut ctx.accounts.transaction,
+                signer_key,
+                ctx.remaining_accounts,
+            )?;
+        }
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct RevokeApproval<'info> {
+        signer: Signer<'info>,
+        multisig: Account<'info, Multisig>,
+        #[account(mut, has_one = multisig @ ErrorCode::MultisigMismatch)]
+        transaction: Account<'info, Transaction>,
+    }
+
+    /// Withdraws the signer's approval of a pending transaction, including
+    /// the one a proposer gets on creation. The transaction stays open for
+    /// the other owners.
+    pub fn revoke_approval(ctx: Context<RevokeApproval>) -> ProgramResult {
+        require_transaction_of(&ctx.accounts.multisig, &ctx.accounts.transaction)?;
+        let now = clock()?.unix_timestamp;
+        let owners = tx_owners(&ctx.accounts.multisig, &ctx.accounts.transaction, now)
+            .ok_or(ErrorCode::OwnersChanged)?;
+        require!(
+            tx_owners_match(owners, &ctx.accounts.transaction),
+            OwnersChanged
+        );
+        let owner_index =
+            approver_index(&ctx.accounts.multisig, owners, ctx.accounts.signer.key, now)
+                .ok_or(ErrorCode::InvalidOwner)?;
+        let tx = &mut ctx.accounts.transaction;
+        require!(tx.executed_at == 0, AlreadyExecuted);
+        if tx.signers[owner_index]
+            || tx
+                .instruction_approvals
+                .get(owner_index)
+                .copied()
+                .unwrap_or(0)
+                != 0
+        {
+            let num_signers = tx.signers.len();
+            tx.approval_revoked.resize(num_signers, false);
+            tx.approval_revoked[owner_index] = true;
+        }
+        tx.signers[owner_index] = false;
+        if let Some(approved_at) = tx.approved_at.get_mut(owner_index) {
+            *approved_at = 0;
+        }
+        if let Some(approved_slot) = tx.approved_slot.get_mut(owner_index) {
+            *approved_slot = 0;
+        }
+        if let Some(mask) = tx.instruction_approvals.get_mut(owner_index) {
+            *mask = 0;
+        }
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct ApproveInstructions<'info> {
+        signer: Signer<'info>,
+        multisig: Account<'info, Multisig>,
+        #[account(mut, has_one = multisig @ ErrorCode::MultisigMismatch)]
+        transaction: Account<'info, Transaction>,
+    }
+
+    /// Approves only the instructions whose bit is set in `mask`. On
+    /// execution, instructions short of the threshold are skipped.
+    pub fn approve_instructions(ctx: Context<ApproveInstructions>, mask: u32) -> ProgramResult {
+        require_transaction_of(&ctx.accounts.multisig, &ctx.accounts.transaction)?;
+        let now = clock()?.unix_timestamp;
+        let owner_index = check_approval(
+            &ctx.accounts.multisig,
+            &ctx.accounts.transaction,
+            ctx.accounts.signer.key,
+            now,
+        )?;
+        let tx = &mut ctx.accounts.transaction;
+        require!(tx.instructions.len() <= 32, TooManyInstructions);
+        let first_approval = is_first_approval(tx, owner_index) && mask != 0;
+        let num_signers = tx.signers.len();
+        tx.instruction_approvals.resize(num_signers, 0);
+        tx.instruction_approvals[owner_index] = mask;
+        tx.approved_at.resize(num_signers, 0);
+        tx.approved_at[owner_index] = now;
+        tx.approved_slot.resize(num_signers, 0);
+        tx.approved_slot[owner_index] = clock()?.slot;
+        if first_approval {
+            record_owner_activity(
+                &ctx.accounts.multisig.key(),
+                ctx.accounts.signer.key,
+                ctx.remaining_accounts,
+                |metadata| metadata.approvals = metadata.approvals.saturating_add(1),
+            )?;
+        }
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    #[instruction(bump: u8)]
+    pub struct CreateAuditLog<'info> {
+        #[account(
+            mut,
+            constraint = multisig.owners.contains(&signer.key()) @ ErrorCode::InvalidOwner
+        )]
+        signer: Signer<'info>,
+        #[account(mut)]
+        multisig: Account<'info, Multisig>,
+        #[account(
+            init,
+            seeds = [b"audit_log", multisig.key().to_bytes().as_ref()],
+            bump = bump,
+            payer = signer,
+            space = audit_log_space(),
+        )]
+        audit_log: Account<'info, AuditLog>,
+        system_program: Program<'info, System>,
+    }
+
+    /// Starts recording executions. The log has to be passed, writable, in
+    /// the remaining accounts of every execution from then on.
+    pub fn create_audit_log(ctx: Context<CreateAuditLog>, bump: u8) -> ProgramResult {
+        let multisig_key = ctx.accounts.multisig.key();
+        require_canonical_bump(&[b"audit_log", multisig_key.as_ref()], bump)?;
+        let audit_log = &mut ctx.accounts.audit_log;
+        audit_log.multisig = multisig_key;
+        audit_log.bump = bump;
+        ctx.accounts.multisig.audit_log = audit_log.key();
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct ExecuteTransaction<'info> {
+        #[account(
+            constraint = multisig.owners.contains(&signer.key()) @ ErrorCode::InvalidOwner,
+            constraint = executor_allowed(&multisig, &signer.key()) @ ErrorCode::ExecutorNotAllowed
+        )]
+        signer: Signer<'info>,
+        #[account(mut)]
+        multisig: Account<'info, Multisig>,
+        #[account(mut, has_one = multisig @ ErrorCode::MultisigMismatch)]
+        transaction: Account<'info, Transaction>,
+    }
+
+    /// Returns an `ExecutionReceipt`. With `idempotent`, retrying a
+    /// transaction the signer already executed succeeds without doing
+    /// anything, flagging the receipt `already_executed`.
+    pub fn execute_transaction<'info>(
+        ctx: Context<'_, '_, '_, 'info, ExecuteTransaction<'info>>,
+        idempotent: bool,
+    ) -> ProgramResult {
+        require_transaction_of(&ctx.accounts.multisig, &ctx.accounts.transaction)?;
+        let tx = &ctx.accounts.transaction;
+        let already_executed =
+            idempotent && tx.executed_at != 0 && tx.executor == ctx.accounts.signer.key();
+        if !already_executed {
+            execute(
+                &mut ctx.accounts.multisig,
+                &mut ctx.accounts.transaction,
+                ctx.accounts.signer.key(),
+                ctx.remaining_accounts,
+            )?;
+        }
+        let receipt = ExecutionReceipt {
+            transaction: ctx.accounts.transaction.key(),
+            executed_at: ctx.accounts.transaction.executed_at,
+            already_executed,
+        };
+        solana_program::program::set_return_data(&receipt.try_to_vec()?);
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct ExecuteAllReady<'info> {
+        #[account(
+            constraint = multisig.owners.contains(&signer.key()) @ ErrorCode::InvalidOwner,
+            constraint = executor_allowed(&multisig, &signer.key()) @ ErrorCode::ExecutorNotAllowed
+        )]
+        signer: Signer<'info>,
+        #[account(mut)]
+        multisig: Account<'info, Multisig>,
+    }
+
+    /// Executes every ready transaction in `remaining_accounts`, laid out as
+    /// each transaction account followed by the `num_accounts[i]` accounts its
+    /// instructions need. Transactions that can't be executed, not ready or
+    /// passed with the wrong accounts, are skipped, but a failing instruction
+    /// reverts the whole batch.
+    pub fn execute_all_ready<'info>(
+        ctx: Context<'_, '_, '_, 'info, ExecuteAllReady<'info>>,
+        num_accounts: Vec<u8>,
+    ) -> ProgramResult {
+        require!(num_accounts.len() <= MAX_BATCH_SIZE, BatchTooLarge);
+        let now = clock()?.unix_timestamp;
+
+        let mut offset = 0;
+        for &n in num_accounts.iter() {
+            let end = offset + 1 + n as usize;
+            let accounts = ctx
+                .remaining_accounts
+                .get(offset..end)
+                .ok_or(ProgramError::NotEnoughAccountKeys)?;
+            offset = end;
+
+            let mut tx = Account::<Transaction>::try_from(&accounts[0])?;
+            let signer = ctx.accounts.signer.key();
+            let execution =
+                check_execution(&ctx.accounts.multisig, &tx, &signer, &accounts[1..], now);
+            let execution = match execution {
+                Ok(execution)
+                    if tx.group_size == 0
+                        && dependency_executed(&tx, &accounts[1..])
+                        && balance_in_range(&tx, &accounts[1..]) =>
+                {
+                    execution
+                }
+                _ => {
+                    msg!("Skipping transaction {}", tx.key());
+                    continue;
+                }
+            };
+            run_transaction(
+                &mut ctx.accounts.multisig,
+                &mut tx,
+                signer,
+                execution,
+                &accounts[1..],
+                ctx.remaining_accounts,
+                now,
+            )?;
+            tx.exit(ctx.program_id)?;
+        }
+
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct ExecuteGroup<'info> {
+        #[account(
+            constraint = multisig.owners.contains(&signer.key()) @ ErrorCode::InvalidOwner,
+            constraint = executor_allowed(&multisig, &signer.key()) @ ErrorCode::ExecutorNotAllowed
+        )]
+        signer: Signer<'info>,
+        #[account(mut)]
+        multisig: Account<'info, Multisig>,
+    }
+
+    /// Executes a whole transaction group in `group_index` order, with
+    /// `remaining_accounts` laid out as in `execute_all_ready`. Fails unless
+    /// every transaction of the group is passed and ready. Like
+    /// `execute_transaction` it's atomic, a failing instruction reverts the
+    /// whole group.
+    pub fn execute_group<'info>(
+        ctx: Context<'_, '_, '_, 'info, ExecuteGroup<'info>>,
+        num_accounts: Vec<u8>,
+    ) -> ProgramResult {
+        require!(num_accounts.len() <= MAX_BATCH_SIZE, BatchTooLarge);
+        let now = clock()?.unix_timestamp;
+
+        let mut group = Vec::with_capacity(num_accounts.len());
+        let mut offset = 0;
+        for &n in num_accounts.iter() {
+            let end = offset + 1 + n as usize;
+            let accounts = ctx
+                .remaining_accounts
+                .get(offset..end)
+                .ok_or(ProgramError::NotEnoughAccountKeys)?;
+            offset = end;
+
+            let tx = Account::<Transaction>::try_from(&accounts[0])?;
+            group.push((tx, &accounts[1..]));
+        }
+
+        // Check the whole group before executing any of it.
+        let (group_id, group_size) = match group.first() {
+            Some((tx, _)) => (tx.group_id, tx.group_size),
+            None => return Err(ErrorCode::InvalidGroup.into()),
+        };
+        require!(group.len() == group_size as usize, InvalidGroup);
+        for (i, (tx, accounts)) in group.iter().enumerate() {
+            require!(
+                tx.multisig == ctx.accounts.multisig.key()
+                    && tx.group_id == group_id
+                    && tx.group_size == group_size
+                    && tx.group_index as usize == i,
+                InvalidGroup
+            );
+            require!(
+                transaction_ready(&ctx.accounts.multisig, tx, now)
+                    && precondition_met(tx, accounts),
+                GroupNotReady
+            );
+            require!(
+                tx.depends_on == Pubkey::default()
+                    || group[..i].iter().any(|(t, _)| t.key() == tx.depends_on)
+                    || dependency_executed(tx, accounts),
+                DependencyNotExecuted
+            );
+        }
+
+        // Earlier members may change the multisig, e.g. its threshold or
+        // owners, so each one is checked against the state it runs under.
+        let signer = ctx.accounts.signer.key();
+        for (mut tx, accounts) in group.into_iter() {
+            let execution = check_execution(&ctx.accounts.multisig, &tx, &signer, accounts, now)?;
+            run_transaction(
+                &mut ctx.accounts.multisig,
+                &mut tx,
+                signer,
+                execution,
+                accounts,
+                ctx.remaining_accounts,
+                now,
+            )?;
+            tx.exit(ctx.program_id)?;
+        }
+
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct DelegateApproval<'info> {
+        signer: Signer<'info>,
+        #[account(mut)]
+        multisig: Account<'info, Multisig>,
+    }
+
+    /// Lets `to` approve transactions in place of the signing owner until
+    /// `until`, replacing any previous delegation of theirs.
+    /// `Pubkey::default()` revokes it.
+    pub fn delegate_approval(
+        ctx: Context<DelegateApproval>,
+        to: Pubkey,
+        until: i64,
+    ) -> ProgramResult {
+        let multisig = &mut ctx.accounts.multisig;
+        let owner = ctx.accounts.signer.key();
+        require!(multisig.owners.contains(&owner), InvalidOwner);
+        let now = clock()?.unix_timestamp;
+        multisig
+            .delegations
+            .retain(|d| d.owner != owner && d.until > now);
+        if to == Pubkey::default() {
+            return Ok(());
+        }
+
+        // A key standing in for several owners would count as many approvals.
+        require!(!multisig.owners.contains(&to), InvalidDelegate);
+        require!(
+            !multisig.delegations.iter().any(|d| d.delegate == to),
+            InvalidDelegate
+        );
+        require!(until > now, InvalidDelegate);
+        multisig.delegations.push(Delegation {
+            owner,
+            delegate: to,
+            until,
+        });
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct OwnerSpend<'info> {
+        signer: Signer<'info>,
+        #[account(mut)]
+        multisig: Account<'info, Multisig>,
+        #[account(mut)]
+        to: AccountInfo<'info>,
+    }
+
+    /// Transfers `amount` lamports from the multisig PDA to `to`, bypassing the
+    /// threshold, up to the signing owner's remaining allowance.
+    pub fn owner_spend(ctx: Context<OwnerSpend>, amount: u64) -> ProgramResult {
+        let multisig = &mut ctx.accounts.multisig;
+        require!(layout_current(multisig), MultisigNotMigrated);
+        let signer_key = ctx.accounts.signer.key;
+        let owner_index = multisig
+            .owners
+            .iter()
+            .position(|a| a == signer_key)
+            .ok_or(ErrorCode::InvalidOwner)?;
+
+        let now = clock()?.unix_timestamp;
+        if multisig.allowance_period > 0
+            && now >= multisig.allowance_period_start + multisig.allowance_period
+        {
+            multisig.allowance_spent.iter_mut().for_each(|s| *s = 0);
+            multisig.allowance_period_start = now;
+        }
+
+        let allowance = multisig
+            .allowance
+            .get(owner_index)
+            .copied()
+            .ok_or(ErrorCode::AllowanceExceeded)?;
+        let spent = multisig.allowance_spent[owner_index]
+            .checked_add(amount)
+            .ok_or(ErrorCode::Overflow)?;
+        require!(spent <= allowance, AllowanceExceeded);
+        multisig.allowance_spent[owner_index] = spent;
+
+        let multisig_info = multisig.to_account_info();
+        require!(
+            available_balance(&multisig_info)? >= amount,
+            InsufficientFunds
+        );
+        **multisig_info.try_borrow_mut_lamports()? -= amount;
+        **ctx.accounts.to.try_borrow_mut_lamports()? += amount;
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct CancelTransaction<'info> {
+        signer: Signer<'info>,
+        #[account(mut)]
+        multisig: Account<'info, Multisig>,
+        #[account(mut, has_one = multisig @ ErrorCode::MultisigMismatch)]
+        transaction: Account<'info, Transaction>,
+    }
+
+    /// Votes to cancel the transaction. Once `cancel_threshold` owners voted
+    /// it can't be approved or executed anymore, and the proposer can delete
+    /// it.
+    pub fn cancel_transaction(ctx: Context<CancelTransaction>) -> ProgramResult {
+        require_transaction_of(&ctx.accounts.multisig, &ctx.accounts.transaction)?;
+        let multisig = &mut ctx.accounts.multisig;
+        let tx = &mut ctx.accounts.transaction;
+        let (owner_index, num_owners) = voter_index(
+            multisig,
+            tx,
+            ctx.accounts.signer.key,
+            clock()?.unix_timestamp,
+        )?;
+        require!(tx.executed_at == 0, AlreadyExecuted);
+        require!(tx.cancelled_at == 0, TransactionCancelled);
+
+        tx.cancel_votes.resize(num_owners, false);
+        tx.cancel_votes[owner_index] = true;
+        let votes = tx.cancel_votes.iter().filter(|&&v| v).count() as u64;
+        if votes >= effective_cancel_threshold(multisig) {
+            tx.cancelled_at = clock()?.unix_timestamp;
+            record_cancellation(multisig)?;
+        }
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct FreezeTransaction<'info> {
+        signer: Signer<'info>,
+        multisig: Account<'info, Multisig>,
+        #[account(mut, has_one = multisig @ ErrorCode::MultisigMismatch)]
+        transaction: Account<'info, Transaction>,
+    }
+
+    /// Votes to freeze the transaction. Once `cancel_threshold` owners voted
+    /// it can't be approved or executed until unfrozen the same way.
+    pub fn freeze_transaction(ctx: Context<FreezeTransaction>) -> ProgramResult {
+        require_transaction_of(&ctx.accounts.multisig, &ctx.accounts.transaction)?;
+        vote_freeze(
+            &ctx.accounts.multisig,
+            &mut ctx.accounts.transaction,
+            ctx.accounts.signer.key,
+            true,
+        )?;
+        Ok(())
+    }
+
+    /// Votes to unfreeze a frozen transaction.
+    pub fn unfreeze_transaction(ctx: Context<FreezeTransaction>) -> ProgramResult {
+        require_transaction_of(&ctx.accounts.multisig, &ctx.accounts.transaction)?;
+        vote_freeze(
+            &ctx.accounts.multisig,
+            &mut ctx.accounts.transaction,
+            ctx.accounts.signer.key,
+            false,
+        )?;
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct DeleteTransaction<'info> {
+        #[account(mut)]
+        signer: Signer<'info>,
+        #[account(mut)]
+        multisig: Account<'info, Multisig>,
+        #[account(
+            mut,
+            has_one = multisig @ ErrorCode::MultisigMismatch,
+            close = signer,
+            constraint = transaction.proposer == signer.key() @ ErrorCode::UnableToDelete
+        )]
+        transaction: Account<'info, Transaction>,
+    }
+
+    pub fn delete_transaction(ctx: Context<DeleteTransaction>) -> ProgramResult {
+        require_transaction_of(&ctx.accounts.multisig, &ctx.accounts.transaction)?;
+        let multisig = &mut ctx.accounts.multisig;
+        let tx = &ctx.accounts.transaction;
+        require!(tx.executed_at == 0, AlreadyExecuted);
+        // Cancelled transactions can always be cleaned up.
+        if tx.cancelled_at == 0 {
+            require_unsigned_by_others(multisig, tx)?;
+        }
+
+        if tx.cancelled_at == 0 {
+            record_cancellation(multisig)?;
+        }
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct TransferProposership<'info> {
+        signer: Signer<'info>,
+        multisig: Account<'info, Multisig>,
+        #[account(
+            mut,
+            has_one = multisig @ ErrorCode::MultisigMismatch,
+            constraint = transaction.proposer == signer.key()
+                || multisig.key() == signer.key() @ ErrorCode::NotProposer
+        )]
+        transaction: Account<'info, Transaction>,
+    }
+
+    /// Makes `new_proposer` the one maintaining the transaction, signed by
+    /// the proposer or by the multisig itself.
+    ///
+    /// The previous proposer's approval came with proposing, so it's
+    /// withdrawn: otherwise the new proposer couldn't delete the transaction,
+    /// see `require_unsigned_by_others`. Like a revoked approval, approving
+    /// again isn't a first approval. Emits `ProposershipTransferred`.
+    pub fn transfer_proposership(
+        ctx: Context<TransferProposership>,
+        new_proposer: Pubkey,
+    ) -> ProgramResult {
+        require_transaction_of(&ctx.accounts.multisig, &ctx.accounts.transaction)?;
+        let multisig = &ctx.accounts.multisig;
+        let tx = &mut ctx.accounts.transaction;
+        require!(tx.executed_at == 0, AlreadyExecuted);
+        require!(multisig.owners.contains(&new_proposer), InvalidOwner);
+        let mut approval_withdrawn = false;
+        if let Some(old_index) = multisig.owners.iter().position(|a| *a == tx.proposer) {
+            if old_index < tx.signers.len() && new_proposer != tx.proposer {
+                approval_withdrawn = tx.signers[old_index];
+                if approval_withdrawn {
+                    let num_signers = tx.signers.len();
+                    tx.approval_revoked.resize(num_signers, false);
+                    tx.approval_revoked[old_index] = true;
+                }
+                tx.signers[old_index] = false;
+            }
+        }
+        emit!(ProposershipTransferred {
+            multisig: multisig.key(),
+            transaction: tx.key(),
+            old_proposer: tx.proposer,
+            new_proposer,
+            approval_withdrawn,
+        });
+        tx.proposer = new_proposer;
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct ReclaimInvalidated<'info> {
+        signer: Signer<'info>,
+        #[account(mut)]
+        multisig: Account<'info, Multisig>,
+        #[account(
+            mut,
+            has_one = multisig @ ErrorCode::MultisigMismatch,
+            has_one = proposer,
+            close = proposer,
+        )]
+        transaction: Account<'info, Transaction>,
+        #[account(mut)]
+        proposer: AccountInfo<'info>,
+    }
+
+    /// Closes a transaction left unexecutable by an owner change, refunding
+    /// its rent to the proposer. Any owner can call it: approvals from the
+    /// previous owner set no longer mean anything.
+    pub fn reclaim_invalidated(ctx: Context<ReclaimInvalidated>) -> ProgramResult {
+        require_transaction_of(&ctx.accounts.multisig, &ctx.accounts.transaction)?;
+        let multisig = &mut ctx.accounts.multisig;
+        let tx = &ctx.accounts.transaction;
+        require!(
+            multisig.owners.contains(ctx.accounts.signer.key),
+            InvalidOwner
+        );
+        require!(tx.executed_at == 0, AlreadyExecuted);
+        require!(
+            tx_owners(multisig, tx, clock()?.unix_timestamp).is_none(),
+            TransactionStillValid
+        );
+
+        if tx.cancelled_at == 0 {
+            record_cancellation(multisig)?;
+        }
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct AmendTransaction<'info> {
+        #[account(mut)]
+        signer: Signer<'info>,
+        multisig: Account<'info, Multisig>,
+        #[account(
+            mut,
+            has_one = multisig @ ErrorCode::MultisigMismatch,
+            constraint = transaction.proposer == signer.key() @ ErrorCode::NotProposer
+        )]
+        transaction: Account<'info, Transaction>,
+        system_program: Program<'info, System>,
+    }
+
+    /// Replaces the instructions of a transaction no other owner approved yet,
+    /// restarting its delay.
+    pub fn amend_transaction(
+        ctx: Context<AmendTransaction>,
+        instructions: Vec<TransactionInstruction>,
+    ) -> ProgramResult {
+        require_transaction_of(&ctx.accounts.multisig, &ctx.accounts.transaction)?;
+        let tx = &ctx.accounts.transaction;
+        require!(tx.executed_at == 0, AlreadyExecuted);
+        require_unsigned_by_others(&ctx.accounts.multisig, tx)?;
+        validate_instructions(&ctx.accounts.multisig, &instructions)?;
+
+        grow_account(
+            &ctx.accounts.transaction.to_account_info(),
+            &ctx.accounts.signer,
+            &ctx.accounts.system_program,
+            transaction_space(instructions.clone())
+                + tx.memo.len()
+                + tx.callback_data.len()
+                + seed_accounts_space(&tx.seed_accounts)?,
+        )?;
+        let tx = &mut ctx.accounts.transaction;
+        tx.eta = eta_after(clock()?.unix_timestamp, ctx.accounts.multisig.delay)?;
+        tx.instructions = instructions;
+        // Hints described the previous instructions.
+        tx.action_hints = Vec::new();
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct AppendInstructions<'info> {
+        #[account(mut)]
+        signer: Signer<'info>,
+        multisig: Account<'info, Multisig>,
+        #[account(
+            mut,
+            has_one = multisig @ ErrorCode::MultisigMismatch,
+            constraint = transaction.proposer == signer.key() @ ErrorCode::NotProposer
+        )]
+        transaction: Account<'info, Transaction>,
+        system_program: Program<'info, System>,
+    }
+
+    /// Adds instructions at the end of a transaction no other owner approved
+    /// yet, restarting its delay.
+    pub fn append_instructions(
+        ctx: Context<AppendInstructions>,
+        extra: Vec<TransactionInstruction>,
+    ) -> ProgramResult {
+        require_transaction_of(&ctx.accounts.multisig, &ctx.accounts.transaction)?;
+        let tx = &ctx.accounts.transaction;
+        require!(tx.executed_at == 0, AlreadyExecuted);
+        require_unsigned_by_others(&ctx.accounts.multisig, tx)?;
+        validate_instructions(&ctx.accounts.multisig, &extra)?;
+
+        let mut instructions = tx.instructions.clone();
+        instructions.extend(extra);
+        grow_account(
+            &ctx.accounts.transaction.to_account_info(),
+            &ctx.accounts.signer,
+            &ctx.accounts.system_program,
+            transaction_space(instructions.clone())
+                + tx.memo.len()
+                + tx.callback_data.len()
+                + seed_accounts_space(&tx.seed_accounts)?,
+        )?;
+        let tx = &mut ctx.accounts.transaction;
+        tx.eta = eta_after(clock()?.unix_timestamp, ctx.accounts.multisig.delay)?;
+        // Keep one hint per instruction, the new ones unspecified.
+        if !tx.action_hints.is_empty() {
+            tx.action_hints.resize(instructions.len(), 0);
+        }
+        tx.instructions = instructions;
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct SetCallback<'info> {
+        #[account(mut)]
+        signer: Signer<'info>,
+        multisig: Account<'info, Multisig>,
+        #[account(
+            mut,
+            has_one = multisig @ ErrorCode::MultisigMismatch,
+            constraint = transaction.proposer == signer.key() @ ErrorCode::NotProposer
+        )]
+        transaction: Account<'info, Transaction>,
+        system_program: Program<'info, System>,
+    }
+
+    /// Sets a program invoked with `callback_data` once the transaction
+    /// executed. `Pubkey::default()` removes the callback.
+    pub fn set_callback(
+        ctx: Context<SetCallback>,
+        callback_program: Pubkey,
+        callback_data: Vec<u8>,
+    ) -> ProgramResult {
+        require_transaction_of(&ctx.accounts.multisig, &ctx.accounts.transaction)?;
+        let tx = &ctx.accounts.transaction;
+        require!(tx.executed_at == 0, AlreadyExecuted);
+        require!(callback_program != crate::ID, InvalidCallback);
+        require_unsigned_by_others(&ctx.accounts.multisig, tx)?;
+
+        grow_account(
+            &ctx.accounts.transaction.to_account_info(),
+            &ctx.accounts.signer,
+            &ctx.accounts.system_program,
+            transaction_space(tx.instructions.clone())
+                + tx.memo.len()
+                + callback_data.len()
+                + seed_accounts_space(&tx.seed_accounts)?,
+        )?;
+        let tx = &mut ctx.accounts.transaction;
+        tx.callback_program = callback_program;
+        tx.callback_data = callback_data;
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct SetSeedAccounts<'info> {
+        #[account(mut)]
+        signer: Signer<'info>,
+        multisig: Account<'info, Multisig>,
+        #[account(
+            mut,
+            has_one = multisig @ ErrorCode::MultisigMismatch,
+            constraint = transaction.proposer == signer.key() @ ErrorCode::NotProposer
+        )]
+        transaction: Account<'info, Transaction>,
+        system_program: Program<'info, System>,
+    }
+
+    /// Sets instruction accounts derived from seeds on execution, replacing
+    /// the pubkeys stored in the instructions.
+    pub fn set_seed_accounts(
+        ctx: Context<SetSeedAccounts>,
+        seed_accounts: Vec<SeedSpec>,
+    ) -> ProgramResult {
+        require_transaction_of(&ctx.accounts.multisig, &ctx.accounts.transaction)?;
+        let tx = &ctx.accounts.transaction;
+        require!(tx.executed_at == 0, AlreadyExecuted);
+        require_unsigned_by_others(&ctx.accounts.multisig, tx)?;
+        for spec in seed_accounts.iter() {
+            let key = tx
+                .instructions
+                .get(spec.instruction_index as usize)
+                .and_then(|ix| ix.keys.get(spec.key_index as usize));
+            require!(key.is_some(), InvalidSeedSpec);
+        }
+
+        grow_account(
+            &ctx.accounts.transaction.to_account_info(),
+            &ctx.accounts.signer,
+            &ctx.accounts.system_program,
+            transaction_space(tx.instructions.clone())
+                + tx.memo.len()
+                + tx.callback_data.len()
+                + seed_accounts_space(&seed_accounts)?,
+        )?;
+        ctx.accounts.transaction.seed_accounts = seed_accounts;
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct SetDependency<'info> {
+        signer: Signer<'info>,
+        multisig: Account<'info, Multisig>,
+        #[account(
+            mut,
+            has_one = multisig @ ErrorCode::MultisigMismatch,
+            constraint = transaction.proposer == signer.key() @ ErrorCode::NotProposer
+        )]
+        transaction: Account<'info, Transaction>,
+    }
+
+    /// Requires the `depends_on` transaction to be executed before this one.
+    /// `Pubkey::default()` removes the dependency.
+    pub fn set_dependency(ctx: Context<SetDependency>, depends_on: Pubkey) -> ProgramResult {
+        require_transaction_of(&ctx.accounts.multisig, &ctx.accounts.transaction)?;
+        let tx = &ctx.accounts.transaction;
+        require!(tx.executed_at == 0, AlreadyExecuted);
+        require!(depends_on != tx.key(), DependencyNotExecuted);
+        require_unsigned_by_others(&ctx.accounts.multisig, tx)?;
+
+        ctx.accounts.transaction.depends_on = depends_on;
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct SetPrecondition<'info> {
+        signer: Signer<'info>,
+        multisig: Account<'info, Multisig>,
+        #[account(
+            mut,
+            has_one = multisig @ ErrorCode::MultisigMismatch,
+            constraint = transaction.proposer == signer.key() @ ErrorCode::NotProposer
+        )]
+        transaction: Account<'info, Transaction>,
+    }
+
+    /// Requires `precondition` to hold for the transaction to execute.
+    /// `None` removes it.
+    pub fn set_precondition(
+        ctx: Context<SetPrecondition>,
+        precondition: Option<Precondition>,
+    ) -> ProgramResult {
+        require_transaction_of(&ctx.accounts.multisig, &ctx.accounts.transaction)?;
+        let tx = &ctx.accounts.transaction;
+        require!(tx.executed_at == 0, AlreadyExecuted);
+        require_unsigned_by_others(&ctx.accounts.multisig, tx)?;
+
+        ctx.accounts.transaction.precondition = precondition;
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct SetBalanceAssertion<'info> {
+        signer: Signer<'info>,
+        multisig: Account<'info, Multisig>,
+        #[account(
+            mut,
+            has_one = multisig @ ErrorCode::MultisigMismatch,
+            constraint = transaction.proposer == signer.key() @ ErrorCode::NotProposer
+        )]
+        transaction: Account<'info, Transaction>,
+    }
+
+    /// Requires an account's balance to be within `balance_assertion`'s range
+    /// right before the transaction executes. `None` removes it.
+    pub fn set_balance_assertion(
+        ctx: Context<SetBalanceAssertion>,
+        balance_assertion: Option<BalanceAssertion>,
+    ) -> ProgramResult {
+        require_transaction_of(&ctx.accounts.multisig, &ctx.accounts.transaction)?;
+        let tx = &ctx.accounts.transaction;
+        require!(tx.executed_at == 0, AlreadyExecuted);
+        require_unsigned_by_others(&ctx.accounts.multisig, tx)?;
+
+        ctx.accounts.transaction.balance_assertion = balance_assertion;
+        Ok(())
+    }
+
+    #[derive(Accounts)]
+    pub struct SetGroup<'info> {
+        signer: Signer<'info>,
+        multisig: Account<'info, Multisig>,
+        #[account(
+            mut,
+            has_one = multisig @ ErrorCode::MultisigMismatch,
+            constraint = transaction.proposer == signer.key() @ ErrorCode::NotProposer
+        )]
+        transaction: Account<'info, Transaction>,
+    }
+
+    /// Links the transaction, as `group_index` of `group_size`, to the other
+    /// transactions with the same `group_id`. Grouped transactions only
+    /// execute together through `execute_group`. A `group_size` of 0 unlinks
+    /// it.
+    pub fn set_group(
+        ctx: Context<SetGroup>,
+        group_id: u64,
+        group_index: u8,
+        group_size: u8,
+    ) -> ProgramResult {
+        require_transaction_of(&ctx.accounts.multisig, &ctx.accounts.transaction)?;
+        let tx = &ctx.accounts.transaction;
+        require!(tx.executed_at == 0, AlreadyExecuted);
+        require!(
+            group_size == 0 || (group_index < group_size && group_size as usize <= MAX_BATCH_SIZE),
+            InvalidGroup
+        );
+        require_unsigned_by_others(&ctx.accounts.multisig, tx)?;
+
+        let tx = &mut ctx.accounts.transaction;
+        tx.group_id = group_id;
+        tx.group_index = group_index;
+        tx.group_size = group_size;
+        Ok(())
+    }
+}
+
+/// Inactivity after which the recovery owner can act, unless configured.
+pub const DEFAULT_RECOVERY_INACTIVITY: i64 = 180 * 24 * 3600;
+
+/// Runtime limit on the accounts and data size of an instruction invoked
+/// through CPI.
+pub const MAX_CPI_INSTRUCTION_SIZE: usize = 1280;
+
+/// Inactivity after which the failsafe beneficiary can drain the multisig,
+/// unless configured.
+pub const DEFAULT_FAILSAFE_INACTIVITY: i64 = 365 * 24 * 3600;
+
+/// Max serialized instructions size included in `TransactionProposed`, to
+/// stay within log limits.
+pub const MAX_EVENT_INSTRUCTIONS_SIZE: usize = 512;
+
+/// Max number of transactions `execute_all_ready` handles, to stay within
+/// compute limits.
+pub const MAX_BATCH_SIZE: usize = 5;
+
+/// Max summaries `list_transactions` returns, to fit in return data.
+pub const MAX_LIST_TRANSACTIONS: usize = 50;
+
+/// Records an `AuditLog` keeps before wrapping around.
+pub const MAX_AUDIT_RECORDS: usize = 64;
+
+/// Max entries of `Multisig::value_tiers`.
+pub const MAX_VALUE_TIERS: usize = 8;
+
+/// Bincode tag of `SystemInstruction::Transfer`.
+pub const SYSTEM_TRANSFER_TAG: u32 = 2;
+
+/// Compute budget of a transaction without a ComputeBudget instruction.
+pub const DEFAULT_COMPUTE_UNITS: u64 = 200_000;
+
+/// Heuristic costs behind `log_compute_estimate`: this program's own work,
+/// each invoked instruction, and each byte of instruction data.
+pub const COMPUTE_UNITS_BASE: u64 = 25_000;
+pub const COMPUTE_UNITS_PER_INSTRUCTION: u64 = 20_000;
+pub const COMPUTE_UNITS_PER_DATA_BYTE: u64 = 10;
+
+pub fn vault_address(multisig: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"vault", multisig.as_ref()], &crate::ID)
+}
+
+/// Counts approvals on `tx`, ignoring those older than the multisig's
+/// `approval_ttl` when set.
+pub fn approval_count(multisig: &Multisig, tx: &Transaction, now: i64) -> usize {
+    let approvers = tx
+        .signers
+        .iter()
+        .enumerate()
+        .filter(|&(i, &signed)| signed && approval_fresh(multisig, tx, i, now))
+        .map(|(i, _)| i);
+    count_approvers(multisig, tx, approvers)
+}
+
+/// Index of the owner `signer` approves as: themselves, or the owner whose
+/// active delegation names them.
+pub fn approver_index(
+    multisig: &Multisig,
+    owners: &[Pubkey],
+    signer: &Pubkey,
+    now: i64,
+) -> Option<usize> {
+    let position = |key: &Pubkey| owners.iter().position(|a| a == key);
+    position(signer).or_else(|| {
+        multisig
+            .delegations
+            .iter()
+            .find(|d| d.delegate == *signer && d.until > now)
+            .and_then(|d| position(&d.owner))
+    })
+}
+
+/// Owners `tx` is approved and executed under: the current ones unless they
+/// changed since it was proposed, then its unexpired snapshot if any.
+pub fn tx_owners<'a>(
+    multisig: &'a Multisig,
+    tx: &'a Transaction,
+    now: i64,
+) -> Option<&'a [Pubkey]> {
+    if multisig.owners_seq_no == tx.owners_seq_no {
+        Some(&multisig.owners)
+    } else if !tx.owner_snapshot.is_empty() && now <= tx.owner_snapshot_until {
+        Some(&tx.owner_snapshot)
+    } else {
+        None
+    }
+}
+
+/// Index of `signer` among the owners voting on cancelling or freezing `tx`,
+/// resolved like approvals, and the number of those owners.
+pub fn voter_index(
+    multisig: &Multisig,
+    tx: &Transaction,
+    signer: &Pubkey,
+    now: i64,
+) -> Result<(usize, usize)> {
+    let owners = tx_owners(multisig, tx, now).ok_or(ErrorCode::OwnersChanged)?;
+    let owner_index = owners
+        .iter()
+        .position(|a| a == signer)
+        .ok_or(ErrorCode::InvalidOwner)?;
+    Ok((owner_index, owners.len()))
+}
+
+/// Whether `tx` has one approval slot per owner it's approved under. They
+/// can drift apart, e.g. approvals preserved across an owner removal read
+/// against an older owner snapshot, and approving past the end would panic.
+pub fn tx_owners_match(owners: &[Pubkey], tx: &Transaction) -> bool {
+    tx.signers.len() == owners.len()
+}
+
+/// Whether an approval by the owner at `index` is their first on `tx`, so
+/// it's rewarded and counted. Revoked approvals don't count as never given.
+pub fn is_first_approval(tx: &Transaction, index: usize) -> bool {
+    !tx.signers[index]
+        && tx.instruction_approvals.get(index).copied().unwrap_or(0) == 0
+        && !tx.approval_revoked.get(index).copied().unwrap_or(false)
+}
+
+/// Checks `signer` may approve `tx` now, for `approve` and
+/// `approve_instructions`, and returns the index of the owner approving.
+pub fn check_approval(
+    multisig: &Multisig,
+    tx: &Transaction,
+    signer: &Pubkey,
+    now: i64,
+) -> Result<usize> {
+    require!(layout_current(multisig), MultisigNotMigrated);
+    require!(transaction_layout_current(tx), TransactionNotMigrated);
+    let owners = tx_owners(multisig, tx, now).ok_or(ErrorCode::OwnersChanged)?;
+    // Approvals are indexed by owner, see `tx_owners_match`.
+    require!(tx_owners_match(owners, tx), OwnersChanged);
+    let owner_index =
+        approver_index(multisig, owners, signer, now).ok_or(ErrorCode::InvalidOwner)?;
+    require!(tx.executed_at == 0, AlreadyExecuted);
+    require!(tx.cancelled_at == 0, TransactionCancelled);
+    require!(!tx.frozen, TransactionFrozen);
+    require!(
+        tx.created_at >= multisig.proposal_cutoff,
+        ProposalBeforeCutoff
+    );
+    Ok(owner_index)
+}
+
+/// Space for the owner snapshot of a transaction of `multisig`, see
+/// `owner_snapshot_ttl`.
+pub fn owner_snapshot_space(multisig: &Multisig) -> usize {
+    if multisig.owner_snapshot_ttl > 0 {
+        multisig.owners.len() * std::mem::size_of::<Pubkey>()
+    } else {
+        0
+    }
+}
+
+/// Space allocated for a proposal of `multisig` holding `instructions` and a
+/// memo of `memo_len` bytes.
+pub fn proposal_space(
+    multisig: &Multisig,
+    instructions: Vec<TransactionInstruction>,
+    memo_len: usize,
+) -> usize {
+    transaction_space(instructions) + memo_len + owner_snapshot_space(multisig)
+}
+
+/// With `enforce_separation`, proposers can't execute their own transactions.
+pub fn separation_respected(multisig: &Multisig, tx: &Transaction, executor: &Pubkey) -> bool {
+    !multisig.enforce_separation || tx.proposer != *executor
+}
+
+pub fn executor_allowed(multisig: &Multisig, executor: &Pubkey) -> bool {
+    multisig.executor_allowlist.is_empty() || multisig.executor_allowlist.contains(executor)
+}
+
+/// Removes the owner at `owner_index` along with its per owner entries,
+/// failing if the remaining owners couldn't reach the threshold.
+pub fn remove_owner_at(multisig: &mut Multisig, owner_index: usize) -> Result<()> {
+    require!(
+        multisig.owners.len() as u64 > multisig.threshold,
+        InvalidThreshold
+    );
+    multisig.owners.remove(owner_index);
+    if owner_index < multisig.allowance.len() {
+        multisig.allowance.remove(owner_index);
+        multisig.allowance_spent.remove(owner_index);
+    }
+    if owner_index < multisig.last_proposal_at.len() {
+        multisig.last_proposal_at.remove(owner_index);
+    }
+    if owner_index < multisig.setup_confirmations.len() {
+        multisig.setup_confirmations.remove(owner_index);
+    }
+    multisig.owners_seq_no = multisig
+        .owners_seq_no
+        .checked_add(1)
+        .ok_or(ErrorCode::Overflow)?;
+    multisig.owners_changed_at = clock()?.unix_timestamp;
+    prune_executor_allowlist(multisig);
+    assert_invariants(multisig)
+}
+
+/// Applies `owners`, a reordering of the current owners, moving every per
+/// owner entry along with its owner, including the approvals of the pending
+/// transactions in `accounts`.
+pub fn reorder_owners(
+    multisig: &mut Account<Multisig>,
+    owners: Vec<Pubkey>,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    // `old_index[i]` is where `owners[i]` was.
+    let old_index = owners
+        .iter()
+        .map(|o| multisig.owners.iter().position(|a| a == o))
+        .collect::<Option<Vec<usize>>>()
+        .ok_or(ErrorCode::InvalidOwner)?;
+    let old_seq_no = multisig.owners_seq_no;
+    permute(&mut multisig.allowance, &old_index);
+    permute(&mut multisig.allowance_spent, &old_index);
+    permute(&mut multisig.last_proposal_at, &old_index);
+    permute(&mut multisig.setup_confirmations, &old_index);
+    multisig.owners = owners;
+    multisig.owners_seq_no = multisig
+        .owners_seq_no
+        .checked_add(1)
+        .ok_or(ErrorCode::Overflow)?;
+    multisig.owners_changed_at = clock()?.unix_timestamp;
+
+    for info in accounts.iter() {
+        let mut tx = Account::<Transaction>::try_from(info)?;
+        if tx.multisig != multisig.key() || tx.owners_seq_no != old_seq_no || tx.executed_at != 0 {
+            continue;
+        }
+        move_owner_slots(&mut tx, OwnerSlots::Permute(&old_index));
+        tx.owners_seq_no = multisig.owners_seq_no;
+        tx.exit(&crate::ID)?;
+    }
+    Ok(())
+}
+
+/// Reorders per owner `values` so that entry `i` is the one previously at
+/// `old_index[i]`. Vectors not filled yet are left empty.
+pub fn permute<T: Clone + Default>(values: &mut Vec<T>, old_index: &[usize]) {
+    if values.is_empty() {
+        return;
+    }
+    *values = old_index
+        .iter()
+        .map(|&i| values.get(i).cloned().unwrap_or_default())
+        .collect();
+}
+
+/// An owner change applied to the per owner entries of a transaction.
+pub enum OwnerSlots<'a> {
+    /// The owner at this index was removed.
+    Remove(usize),
+    /// Entry `i` is the one previously at `old_index[i]`, see `permute`.
+    Permute(&'a [usize]),
+}
+
+/// Applies `change` to every per owner vector of `tx`, so approvals and
+/// votes stay with the owner they came from.
+pub fn move_owner_slots(tx: &mut Transaction, change: OwnerSlots) {
+    fn apply<T: Clone + Default>(values: &mut Vec<T>, change: &OwnerSlots) {
+        match *change {
+            OwnerSlots::Remove(index) => {
+                if index < values.len() {
+                    values.remove(index);
+                }
+            }
+            OwnerSlots::Permute(old_index) => permute(values, old_index),
+        }
+    }
+    apply(&mut tx.signers, &change);
+    apply(&mut tx.approved_at, &change);
+    apply(&mut tx.approved_slot, &change);
+    apply(&mut tx.instruction_approvals, &change);
+    apply(&mut tx.cancel_votes, &change);
+    apply(&mut tx.freeze_votes, &change);
+    apply(&mut tx.approval_revoked, &change);
+    apply(&mut tx.owner_snapshot, &change);
+}
+
+/// Keeps only allowlisted executors that are still owners, so an owner change
+/// can't leave the multisig without anyone able to execute.
+pub fn prune_executor_allowlist(multisig: &mut Multisig) {
+    let owners = &multisig.owners;
+    multisig.executor_allowlist.retain(|k| owners.contains(k));
+}
+
+/// With `require_distinct_slots`, approvals landing in the same slot only
+/// count once.
+pub fn count_approvers(
+    multisig: &Multisig,
+    tx: &Transaction,
+    approvers: impl Iterator<Item = usize>,
+) -> usize {
+    if !multisig.require_distinct_slots {
+        return approvers.count();
+    }
+    let mut slots: Vec<u64> = approvers
+        .map(|i| tx.approved_slot.get(i).copied().unwrap_or(0))
+        .collect();
+    slots.sort_unstable();
+    slots.dedup();
+    slots.len()
+}
+
+/// Approvals `tx` needs: the multisig threshold, raised by the transaction's
+/// `min_approvals` and by the value tier of what it transfers.
+pub fn effective_threshold(multisig: &Multisig, tx: &Transaction) -> usize {
+    if multisig.value_tiers.is_empty() {
+        return multisig.threshold.max(tx.min_approvals) as usize;
+    }
+    let value = transferred_out(&tx.multisig, &tx.instructions);
+    let tier = multisig
+        .value_tiers
+        .iter()
+        .filter(|t| value >= t.min_lamports)
+        .map(|t| t.threshold)
+        .max()
+        .unwrap_or(0)
+        // Owners removed since the tier was set can't lock funds away.
+        .min(multisig.owners.len() as u64);
+    multisig.threshold.max(tx.min_approvals).max(tier) as usize
+}
+
+/// Lamports moved out of the vault or the multisig by the system transfers
+/// in `instructions`. Other instructions aren't considered.
+pub fn transferred_out(multisig_key: &Pubkey, instructions: &[TransactionInstruction]) -> u64 {
+    let (vault, _) = vault_address(multisig_key);
+    instructions
+        .iter()
+        .filter(|ix| ix.program_id == solana_program::system_program::ID)
+        .filter(|ix| {
+            matches!(ix.keys.first(), Some(from)
+                if from.pubkey == vault || from.pubkey == *multisig_key)
+        })
+        .filter_map(|ix| match ix.data.get(..12) {
+            Some(data) if data[..4] == SYSTEM_TRANSFER_TAG.to_le_bytes() => {
+                Some(u64::from_le_bytes(data[4..12].try_into().unwrap()))
+            }
+            _ => None,
+        })
+        .fold(0, u64::saturating_add)
+}
+
+/// Records the signer's vote to set `frozen`, setting it once
+/// `cancel_threshold` owners voted.
+fn vote_freeze(
+    multisig: &Multisig,
+    tx: &mut Transaction,
+    signer: &Pubkey,
+    frozen: bool,
+) -> Result<()> {
+    let (owner_index, num_owners) = voter_index(multisig, tx, signer, clock()?.unix_timestamp)?;
+    require!(tx.executed_at == 0, AlreadyExecuted);
+    require!(tx.cancelled_at == 0, TransactionCancelled);
+    if tx.frozen == frozen {
+        return Ok(());
+    }
+
+    tx.freeze_votes.resize(num_owners, false);
+    tx.freeze_votes[owner_index] = true;
+    let votes = tx.freeze_votes.iter().filter(|&&v| v).count() as u64;
+    if votes >= effective_cancel_threshold(multisig) {
+        tx.frozen = frozen;
+        tx.freeze_votes = Vec::new();
+    }
+    Ok(())
+}
+
+pub fn effective_cancel_threshold(multisig: &Multisig) -> u64 {
+    if multisig.cancel_threshold == 0 {
+        multisig.threshold
+    } else {
+        multisig.cancel_threshold
+    }
+}
+
+pub fn approval_fresh(multisig: &Multisig, tx: &Transaction, owner_index: usize, now: i64) -> bool {
+    multisig.approval_ttl == 0
+        || tx.approved_at.get(owner_index).copied().unwrap_or(0)
+            >= now.saturating_sub(multisig.approval_ttl)
+}
+
+/// Whether each instruction of `tx` reached the threshold, counting scoped
+/// approvals from `approve_instructions` along with full ones.
+pub fn approved_instructions(multisig: &Multisig, tx: &Transaction, now: i64) -> Vec<bool> {
+    (0..tx.instructions.len())
+        .map(|ix| {
+            let approvers = (0..tx.signers.len()).filter(|&i| {
+                let mask = tx.instruction_approvals.get(i).copied().unwrap_or(0);
+                let scoped = ix < 32 && mask & (1 << ix) != 0;
+                (tx.signers[i] || scoped) && approval_fresh(multisig, tx, i, now)
+            });
+            count_approvers(multisig, tx, approvers) >= effective_threshold(multisig, tx)
+        })
+        .collect()
+}
+
+/// Whether `info` is a pending transaction of `multisig` with enough
+/// approvals for the current threshold but not for `threshold`.
+pub fn stranded_by_threshold(
+    multisig: &Account<Multisig>,
+    info: &AccountInfo,
+    threshold: u64,
+    now: i64,
+) -> bool {
+    if *info.owner != crate::ID {
+        return false;
+    }
+    let data = match info.try_borrow_data() {
+        Ok(data) => data,
+        Err(_) => return false,
+    };
+    let tx = match Transaction::try_deserialize(&mut &data[..]) {
+        Ok(tx) => tx,
+        Err(_) => return false,
+    };
+    let approvals = approval_count(multisig, &tx, now);
+    tx.multisig == multisig.key()
+        && tx.executed_at == 0
+        && multisig.owners_seq_no == tx.owners_seq_no
+        && approvals >= effective_threshold(multisig, &tx)
+        && approvals < threshold.max(tx.min_approvals) as usize
+}
+
+/// Transactions can execute from their eta to `grace_period` seconds after
+/// it, both ends included.
+pub fn transaction_ready(multisig: &Multisig, tx: &Transaction, now: i64) -> bool {
+    let sig_count = approval_count(multisig, tx, now);
+    tx.executed_at == 0
+        && tx.cancelled_at == 0
+        && !tx.frozen
+        && tx.created_at >= multisig.proposal_cutoff
+        && setup_confirmed(multisig)
+        && layout_current(multisig)
+        && transaction_layout_current(tx)
+        && now >= tx.eta
+        && now <= tx.eta.saturating_add(multisig.grace_period)
+        && tx_owners(multisig, tx, now).is_some()
+        && sig_count >= effective_threshold(multisig, tx)
+        && !in_post_change_cooldown(multisig, now)
+}
+
+/// Confirmations are per owner index, a new owner set confirms afresh unless
+/// the setup already is.
+pub fn reset_setup_confirmations(multisig: &mut Multisig) {
+    multisig.setup_confirmations = if multisig.confirmed {
+        Vec::new()
+    } else {
+        vec![false; multisig.owners.len()]
+    };
+}
+
+/// Multisigs created before setup confirmation existed have no
+/// `setup_confirmations` and count as confirmed.
+pub fn setup_confirmed(multisig: &Multisig) -> bool {
+    multisig.confirmed || multisig.setup_confirmations.is_empty()
+}
+
+/// Accounts in an older layout decode with arbitrary field values until
+/// migrated, see `migrate_multisig`.
+pub fn layout_current(multisig: &Multisig) -> bool {
+    multisig.layout_version == MULTISIG_LAYOUT_VERSION
+}
+
+/// See `migrate_transaction`.
+pub fn transaction_layout_current(tx: &Transaction) -> bool {
+    tx.layout_version == TRANSACTION_LAYOUT_VERSION
+}
+
+pub fn in_post_change_cooldown(multisig: &Multisig, now: i64) -> bool {
+    now < multisig
+        .owners_changed_at
+        .saturating_add(multisig.post_change_cooldown)
+}
+
+/// Checks the transaction's precondition against its account, which must be
+/// passed along with the instruction accounts.
+pub fn precondition_met(tx: &Transaction, accounts: &[AccountInfo]) -> bool {
+    let precondition = match tx.precondition {
+        Some(p) => p,
+        None => return true,
+    };
+    let account = match accounts.iter().find(|a| *a.key == precondition.account) {
+        Some(a) => a,
+        None => return false,
+    };
+    let data = match account.try_borrow_data() {
+        Ok(data) => data,
+        Err(_) => return false,
+    };
+    let start = precondition.offset as usize;
+    let bytes = match data.get(start..start + 8) {
+        Some(bytes) => bytes,
+        None => return false,
+    };
+    let actual = u64::from_le_bytes(bytes.try_into().unwrap());
+    match precondition.comparison {
+        Comparison::Equal => actual == precondition.value,
+        Comparison::NotEqual => actual != precondition.value,
+        Comparison::LessThan => actual < precondition.value,
+        Comparison::GreaterThan => actual > precondition.value,
+    }
+}
+
+/// Returns the transaction's instructions with their `seed_accounts`
+/// derived from the current state of `accounts`.
+pub fn resolve_instructions(
+    tx: &Transaction,
+    accounts: &[AccountInfo],
+) -> Result<Vec<TransactionInstruction>> {
+    let mut instructions = tx.instructions.clone();
+    for spec in tx.seed_accounts.iter() {
+        let mut seeds = Vec::with_capacity(spec.seeds.len());
+        for seed in spec.seeds.iter() {
+            match seed {
+                Seed::Bytes { bytes } => seeds.push(bytes.clone()),
+                Seed::AccountData {
+                    account,
+                    offset,
+                    length,
+                } => {
+                    let info = accounts
+                        .iter()
+                        .find(|a| a.key == account)
+                        .ok_or(ErrorCode::InvalidSeedSpec)?;
+                    let data = info.try_borrow_data()?;
+                    let start = *offset as usize;
+                    let bytes = data
+                        .get(start..start + *length as usize)
+                        .ok_or(ErrorCode::InvalidSeedSpec)?;
+                    seeds.push(bytes.to_vec());
+                }
+            }
+        }
+        let seeds: Vec<&[u8]> = seeds.iter().map(|s| s.as_slice()).collect();
+        let (address, _) = Pubkey::try_find_program_address(&seeds, &spec.program_id)
+            .ok_or(ErrorCode::InvalidSeedSpec)?;
+        let meta = instructions
+            .get_mut(spec.instruction_index as usize)
+            .and_then(|ix| ix.keys.get_mut(spec.key_index as usize))
+            .ok_or(ErrorCode::InvalidSeedSpec)?;
+        meta.pubkey = address;
+    }
+    Ok(instructions)
+}
+
+/// Rejects accounts the transaction doesn't reference, so an executor can't
+/// pass extra accounts along to the invoked programs. The multisig's audit
+/// log and the executor's metadata are only used for bookkeeping.
+pub fn require_expected_accounts(
+    multisig: &Account<Multisig>,
+    tx: &Transaction,
+    executor: &Pubkey,
+    instructions: &[TransactionInstruction],
+    accounts: &[AccountInfo],
+) -> Result<()> {
+    let (executor_metadata, _) = Pubkey::find_program_address(
+        &[b"owner", multisig.key().as_ref(), executor.as_ref()],
+        &crate::ID,
+    );
+    let audit_log = Some(multisig.audit_log).filter(|log| *log != Pubkey::default());
+    for account in accounts.iter() {
+        let expected = instructions.iter().any(|ix| {
+            ix.program_id == *account.key || ix.keys.iter().any(|k| k.pubkey == *account.key)
+        }) || tx.callback_program == *account.key
+            || tx.precondition.map(|p| p.account) == Some(*account.key)
+            || tx.balance_assertion.map(|b| b.account) == Some(*account.key)
+            || tx.depends_on == *account.key
+            || tx.seed_accounts.iter().any(|spec| {
+                spec.seeds.iter().any(|seed| match seed {
+                    Seed::AccountData { account: a, .. } => a == account.key,
+                    Seed::Bytes { .. } => false,
+                })
+            })
+            || audit_log == Some(*account.key)
+            || executor_metadata == *account.key;
+        require!(expected, UnexpectedAccount);
+    }
+    Ok(())
+}
+
+/// Instructions writing to the transaction being executed would race with
+/// the execution bookkeeping.
+pub fn require_no_self_reference(
+    tx_key: &Pubkey,
+    instructions: &[TransactionInstruction],
+) -> Result<()> {
+    let self_referencing = instructions
+        .iter()
+        .flat_map(|ix| ix.keys.iter())
+        .any(|meta| meta.pubkey == *tx_key && meta.is_writable);
+    require!(!self_referencing, SelfReferenceNotAllowed);
+    Ok(())
+}
+
+/// Checks the transaction `tx` depends on executed, its account must be
+/// passed along with the instruction accounts.
+pub fn dependency_executed(tx: &Transaction, accounts: &[AccountInfo]) -> bool {
+    if tx.depends_on == Pubkey::default() {
+        return true;
+    }
+    let account = match accounts.iter().find(|a| *a.key == tx.depends_on) {
+        Some(a) if *a.owner == crate::ID => a,
+        _ => return false,
+    };
+    let data = match account.try_borrow_data() {
+        Ok(data) => data,
+        Err(_) => return false,
+    };
+    match Transaction::try_deserialize(&mut &data[..]) {
+        Ok(dependency) => dependency.multisig == tx.multisig && dependency.executed_at != 0,
+        Err(_) => false,
+    }
+}
+
+/// Checks the transaction's balance assertion against its account, which
+/// must be passed along with the instruction accounts.
+pub fn balance_in_range(tx: &Transaction, accounts: &[AccountInfo]) -> bool {
+    let assertion = match tx.balance_assertion {
+        Some(a) => a,
+        None => return true,
+    };
+    let account = match accounts.iter().find(|a| *a.key == assertion.account) {
+        Some(a) => a,
+        None => return false,
+    };
+    let balance = if assertion.token_account {
+        // SPL token accounts store the amount right after the mint and owner.
+        let data = match account.try_borrow_data() {
+            Ok(data) => data,
+            Err(_) => return false,
+        };
+        match data.get(64..72) {
+            Some(bytes) => u64::from_le_bytes(bytes.try_into().unwrap()),
+            None => return false,
+        }
+    } else {
+        account.lamports()
+    };
+    balance >= assertion.min_balance && balance <= assertion.max_balance
+}
+
+/// Applies `update` to the metadata account of `owner` if it's among
+/// `accounts`. Owners without one, or not passing it, aren't tracked.
+pub fn record_owner_activity(
+    multisig: &Pubkey,
+    owner: &Pubkey,
+    accounts: &[AccountInfo],
+    update: impl FnOnce(&mut OwnerMetadata),
+) -> ProgramResult {
+    for info in accounts
+        .iter()
+        .filter(|a| a.is_writable && *a.owner == crate::ID)
+    {
+        let mut metadata = match Account::<OwnerMetadata>::try_from(info) {
+            Ok(metadata) => metadata,
+            Err(_) => continue,
+        };
+        if metadata.multisig == *multisig && metadata.owner == *owner {
+            update(&mut metadata);
+            return metadata.exit(&crate::ID);
+        }
+    }
+    Ok(())
+}
+
+/// Appends `tx` to the multisig's audit log, if it has one.
+pub fn append_audit_record(
+    multisig: &Multisig,
+    tx: &Transaction,
+    accounts: &[AccountInfo],
+    now: i64,
+) -> ProgramResult {
+    if multisig.audit_log == Pubkey::default() {
+        return Ok(());
+    }
+    let info = accounts
+        .iter()
+        .find(|a| *a.key == multisig.audit_log)
+        .ok_or(ErrorCode::AuditLogMissing)?;
+    let mut audit_log = Account::<AuditLog>::try_from(info)?;
+    let record = AuditRecord {
+        index: tx.index,
+        executor: tx.executor,
+        executed_at: now,
+        num_instructions: tx.instructions.len() as u8,
+    };
+    let next = (audit_log.total % MAX_AUDIT_RECORDS as u64) as usize;
+    if next < audit_log.records.len() {
+        audit_log.records[next] = record;
+    } else {
+        audit_log.records.push(record);
+    }
+    audit_log.total = audit_log.total.checked_add(1).ok_or(ErrorCode::Overflow)?;
+    audit_log.exit(&crate::ID)
+}
+
+pub fn record_cancellation(multisig: &mut Multisig) -> Result<()> {
+    multisig.pending_transactions = multisig.pending_transactions.saturating_sub(1);
+    multisig.cancelled_transactions = multisig
+        .cancelled_transactions
+        .checked_add(1)
+        .ok_or(ErrorCode::Overflow)?;
+    Ok(())
+}
+
+pub fn record_execution(multisig: &mut Multisig, now: i64) -> Result<()> {
+    multisig.last_activity_at = now;
+    // Transactions created before the counter existed aren't tracked.
+    multisig.pending_transactions = multisig.pending_transactions.saturating_sub(1);
+    multisig.executed_transactions = multisig
+        .executed_transactions
+        .checked_add(1)
+        .ok_or(ErrorCode::Overflow)?;
+    Ok(())
+}
+
+/// Logs a rough estimate of the compute `instructions` will use, with a
+/// warning when it's likely above the default budget. Advisory only: the
+/// actual cost depends on the invoked programs.
+pub fn log_compute_estimate(instructions: &[TransactionInstruction]) {
+    let data_len: usize = instructions.iter().map(|ix| ix.data.len()).sum();
+    let estimate = COMPUTE_UNITS_BASE
+        .saturating_add(COMPUTE_UNITS_PER_INSTRUCTION.saturating_mul(instructions.len() as u64))
+        .saturating_add(COMPUTE_UNITS_PER_DATA_BYTE.saturating_mul(data_len as u64));
+    msg!("Estimated compute units: {}", estimate);
+    if estimate > DEFAULT_COMPUTE_UNITS {
+        msg!(
+            "Warning: likely exceeds the default {} compute units, consider a ComputeBudget instruction",
+            DEFAULT_COMPUTE_UNITS
+        );
+    }
+}
+
+/// Executes `tx` on behalf of `executor`, shared by `execute_transaction`
+/// and auto-executing approvals. `accounts` holds the accounts its
+/// instructions need.
+pub fn execute<'info>(
+    multisig: &mut Account<'info, Multisig>,
+    tx: &mut Account<'info, Transaction>,
+    executor: Pubkey,
+    accounts: &[AccountInfo<'info>],
+) -> ProgramResult {
+    let now = clock()?.unix_timestamp;
+    require!(tx.group_size == 0, GroupedTransaction);
+    require!(dependency_executed(tx, accounts), DependencyNotExecuted);
+    let execution = check_execution(multisig, tx, &executor, accounts, now)?;
+    run_transaction(multisig, tx, executor, execution, accounts, accounts, now)
+}
+
+/// Instructions of a transaction to run, see `check_execution`.
+pub struct Execution {
+    pub instructions: Vec<TransactionInstruction>,
+    /// Bitmask of the instructions left out, short of approvals.
+    pub skipped_instructions: u32,
+}
+
+/// Checks `executor` can execute `tx` now, except for its group and
+/// dependency which depend on how it's executed, and resolves the
+/// instructions to run.
+pub fn check_execution(
+    multisig: &Account<Multisig>,
+    tx: &Account<Transaction>,
+    executor: &Pubkey,
+    accounts: &[AccountInfo],
+    now: i64,
+) -> Result<Execution> {
+    require!(tx.multisig == multisig.key(), MultisigMismatch);
+    require!(now >= tx.eta, BeforeETA);
+    require!(
+        now <= tx.eta.saturating_add(multisig.grace_period),
+        TransactionStale
+    );
+    require!(tx.executed_at == 0, AlreadyExecuted);
+    require!(tx.cancelled_at == 0, TransactionCancelled);
+    require!(!tx.frozen, TransactionFrozen);
+    require!(
+        tx.created_at >= multisig.proposal_cutoff,
+        ProposalBeforeCutoff
+    );
+    require!(layout_current(multisig), MultisigNotMigrated);
+    require!(transaction_layout_current(tx), TransactionNotMigrated);
+    require!(setup_confirmed(multisig), SetupNotConfirmed);
+    require!(
+        separation_respected(multisig, tx, executor),
+        SeparationOfDutiesViolated
+    );
+    require!(!in_post_change_cooldown(multisig, now), OwnerChangeCooldown);
+    require!(
+        matches!(tx_owners(multisig, tx, now), Some(owners) if tx_owners_match(owners, tx)),
+        OwnersChanged
+    );
+
+    // Do we have enough signers, for at least some of the instructions?
+    let sig_count = approval_count(multisig, tx, now);
+    let approved = approved_instructions(multisig, tx, now);
+    if sig_count < effective_threshold(multisig, tx) && !approved.contains(&true) {
+        return Err(ErrorCode::NotEnoughSigners.into());
+    }
+    require!(precondition_met(tx, accounts), PreconditionNotMet);
+    let resolved = resolve_instructions(tx, accounts)?;
+    require_expected_accounts(multisig, tx, executor, &resolved, accounts)?;
+    require_no_self_reference(&tx.key(), &resolved)?;
+
+    let instructions = resolved
+        .into_iter()
+        .zip(approved.iter())
+        .filter(|&(_, &ok)| ok)
+        .map(|(ix, _)| ix)
+        .collect();
+    let skipped_instructions = approved
+        .iter()
+        .enumerate()
+        .filter(|&(i, &ok)| !ok && i < 32)
+        .fold(0, |mask, (i, _)| mask | 1 << i);
+    Ok(Execution {
+        instructions,
+        skipped_instructions,
+    })
+}
+
+/// Runs a transaction passing `check_execution`. `accounts` holds the
+/// accounts its instructions need, the audit log and the executor's metadata
+/// are looked up in `log_accounts`.
+pub fn run_transaction<'info>(
+    multisig: &mut Account<'info, Multisig>,
+    tx: &mut Account<'info, Transaction>,
+    executor: Pubkey,
+    execution: Execution,
+    accounts: &[AccountInfo<'info>],
+    log_accounts: &[AccountInfo<'info>],
+    now: i64,
+) -> ProgramResult {
+    tx.skipped_instructions = execution.skipped_instructions;
+    // Execution is atomic: the instructions run in the same Solana
+    // transaction as this handler, so if any of them fails everything is
+    // rolled back, `executed_at` included, and it can be retried.
+    tx.executed_at = now;
+    tx.executor = executor;
+
+    append_audit_record(multisig, tx, log_accounts, now)?;
+    record_execution(multisig, now)?;
+    record_owner_activity(&multisig.key(), &executor, log_accounts, |metadata| {
+        metadata.executions = metadata.executions.saturating_add(1)
+    })?;
+    // Persist now, instructions may modify the multisig through CPI.
+    multisig.exit(&crate::ID)?;
+    tx.exit(&crate::ID)?;
+
+    require!(balance_in_range(tx, accounts), BalanceAssertionFailed);
+    log_compute_estimate(&execution.instructions);
+    tx.return_data = invoke_instructions(multisig, &execution.instructions, accounts)?;
+    multisig.reload()?;
+    invoke_callback(tx, multisig, accounts)?;
+    require_rent_exempt(&multisig.to_account_info())?;
+
+    Ok(())
+}
+
+/// Seeds signing as the multisig PDA, its stored bump included.
+pub fn multisig_signer_seeds(multisig: &Multisig) -> [&[u8]; 4] {
+    [
+        b"multisig",
+        multisig.base.as_ref(),
+        purpose_seed(&multisig.purpose),
+        std::slice::from_ref(&multisig.bump),
+    ]
+}
+
+/// Seed of a multisig purpose. Empty seeds don't change the derived
+/// address, so the default purpose keeps the original `[b"multisig", base]`.
+pub fn purpose_seed(purpose: &[u8; 8]) -> &[u8] {
+    if *purpose == [0; 8] {
+        &[]
+    } else {
+        purpose
+    }
+}
+
+/// Invokes `instructions` signed by the multisig PDA and its vault. The
+/// multisig holds data so the system program won't debit it, instructions
+/// needing a payer, e.g. for `create_account`, use the system-owned vault.
+///
+/// Returns the last non-empty return data an instruction set, truncated to
+/// `MAX_RETURN_DATA_LEN`.
+pub fn invoke_instructions(
+    multisig: &Account<Multisig>,
+    instructions: &[TransactionInstruction],
+    accounts: &[AccountInfo],
+) -> Result<Vec<u8>> {
+    let seeds = multisig_signer_seeds(multisig);
+    let multisig_key = multisig.key();
+    let (_, vault_bump) = vault_address(&multisig_key);
+    let vault_seeds: &[&[u8]] = &[b"vault", multisig_key.as_ref(), &[vault_bump]];
+    let mut return_data = Vec::new();
+    for ix in instructions.iter() {
+        let six = solana_program::instruction::Instruction {
+            program_id: ix.program_id,
+            accounts: ix
+                .keys
+                .clone()
+                .into_iter()
+                .map(|a| solana_program::instruction::AccountMeta {
+                    pubkey: a.pubkey,
+                    is_signer: a.is_signer,
+                    is_writable: a.is_writable,
+                })
+                .collect(),
+            data: ix.data.clone(),
+        };
+        solana_program::program::invoke_signed(&six, accounts, &[&seeds, vault_seeds])?;
+        // Return data is reset on every invoke, but may have been set by a
+        // program the instruction called into.
+        if let Some((_, data)) = solana_program::program::get_return_data() {
+            if !data.is_empty() {
+                return_data = data;
+                return_data.truncate(MAX_RETURN_DATA_LEN);
+            }
+        }
+    }
+    Ok(return_data)
+}
+
+/// Return data kept on executed transactions.
+pub const MAX_RETURN_DATA_LEN: usize = 128;
+
+pub fn nonce_create_instructions(
+    multisig: &Pubkey,
+    funder: &Pubkey,
+    seed: &str,
+    lamports: u64,
+) -> Result<Vec<TransactionInstruction>> {
+    let nonce = Pubkey::create_with_seed(multisig, seed, &solana_program::system_program::ID)
+        .map_err(|_| ErrorCode::InvalidSeed)?;
+    Ok(
+        solana_program::system_instruction::create_nonce_account_with_seed(
+            funder, &nonce, multisig, seed, multisig, lamports,
+        )
+        .into_iter()
+        .map(Into::into)
+        .collect(),
+    )
+}
+
+pub fn nonce_advance_instructions(
+    multisig: &Pubkey,
+    nonce: &Pubkey,
+) -> Vec<TransactionInstruction> {
+    vec![solana_program::system_instruction::advance_nonce_account(nonce, multisig).into()]
+}
+
+/// Instructions of a proposal setting the threshold to `threshold`.
+pub fn change_threshold_instructions(
+    multisig: &Pubkey,
+    threshold: u64,
+) -> Vec<TransactionInstruction> {
+    let data = instruction::ChangeThreshold {
+        threshold,
+        confirm_stranded: false,
+    };
+    vec![solana_program::instruction::Instruction {
+        program_id: crate::ID,
+        accounts: accounts::ChangeThreshold {
+            multisig: *multisig,
+        }
+        .to_account_metas(None),
+        data: anchor_lang::InstructionData::data(&data),
+    }
+    .into()]
+}
+
+/// Checks instructions can be invoked at execution time and that
+/// governance changes go through a delay.
+pub fn validate_instructions(
+    multisig: &Multisig,
+    instructions: &[TransactionInstruction],
+) -> Result<()> {
+    // Every violation is logged so a proposal can be fixed in one go, the
+    // first one decides the error returned.
+    let mut first = None;
+    let mut violation = |i: usize, error: ErrorCode| {
+        msg!("Instruction {} rejected: {}", i, error);
+        first.get_or_insert(error);
+    };
+    for (i, ix) in instructions.iter().enumerate() {
+        if ix.program_id == crate::ID
+            && is_privileged(&ix.data)
+            && require_governance_delay(multisig).is_err()
+        {
+            violation(i, ErrorCode::DelayBelowMinimum);
+        }
+        let size = ix.keys.len() * std::mem::size_of::<AccountMeta>() + ix.data.len();
+        if size > MAX_CPI_INSTRUCTION_SIZE {
+            msg!("Instruction {} is {} bytes", i, size);
+            violation(i, ErrorCode::InstructionTooLarge);
+        }
+        if multisig.max_instruction_data_len != 0
+            && ix.data.len() > multisig.max_instruction_data_len as usize
+        {
+            msg!("Instruction {} has {} bytes of data", i, ix.data.len());
+            violation(i, ErrorCode::InstructionDataTooLarge);
+        }
+        // Sysvars are forwarded like any other account, but the runtime
+        // refuses them as signers or writable.
+        if ix.keys.iter().any(|k| {
+            solana_program::sysvar::is_sysvar_id(&k.pubkey) && (k.is_signer || k.is_writable)
+        }) {
+            violation(i, ErrorCode::InvalidSysvarMeta);
+        }
+        // The runtime merges the flags of repeated accounts, which wouldn't
+        // show in the stored proposal.
+        if ix
+            .keys
+            .iter()
+            .enumerate()
+            .any(|(j, k)| ix.keys[..j].iter().any(|m| m.pubkey == k.pubkey))
+        {
+            violation(i, ErrorCode::DuplicateAccountMeta);
+        }
+    }
+    match first {
+        Some(error) => Err(error.into()),
+        None => Ok(()),
+    }
+}
+
+/// Instructions of this program changing who controls the multisig, how
+/// its approvals are counted, or who can move its funds.
+pub const PRIVILEGED_INSTRUCTIONS: &[&str] = &[
+    "set_owners",
+    "remove_owner",
+    "change_threshold",
+    "change_delay",
+    "change_min_delay",
+    "set_recovery",
+    "change_post_change_cooldown",
+    "set_failsafe",
+    "change_approval_reward",
+    "change_max_total_incentives",
+    "change_proposal_rent_source",
+    "set_value_tiers",
+    "change_min_owners",
+    "change_owner_snapshot_ttl",
+    "reconfigure",
+    "set_allowance",
+    "change_allowance_period",
+    "set_executor_allowlist",
+    "change_cancel_threshold",
+    "change_enforce_separation",
+    "change_approval_ttl",
+    "set_proposal_cutoff",
+    "change_require_distinct_slots",
+    "change_preserve_approvals",
+    "change_preserve_approvals_on_reorder",
+    "change_min_distinct_recent_proposers",
+    "propose_owner_change",
+];
+
+pub fn is_privileged(data: &[u8]) -> bool {
+    data.len() >= 8
+        && PRIVILEGED_INSTRUCTIONS
+            .iter()
+            .any(|name| data[..8] == sighash(name))
+}
+
+/// Hash of Borsh-serialized instructions, domain separated so it can't be
+/// confused with other hashes of the same bytes.
+pub fn instructions_hash(data: &[u8]) -> [u8; 32] {
+    solana_program::hash::hashv(&[b"multisig:instructions", data]).to_bytes()
+}
+
+/// Anchor's instruction discriminator.
+pub fn sighash(name: &str) -> [u8; 8] {
+    let preimage = format!("global:{}", name);
+    let mut sighash = [0u8; 8];
+    sighash.copy_from_slice(&solana_program::hash::hash(preimage.as_bytes()).to_bytes()[..8]);
+    sighash
+}
+
+pub fn validate_threshold(multisig: &Multisig, threshold: u64) -> Result<()> {
+    require!(threshold <= multisig.owners.len() as u64, InvalidThreshold);
+    Ok(())
+}
+
+/// Checked after every change to the owners or threshold, whatever
+/// validation the instruction itself did.
+fn assert_invariants(multisig: &Multisig) -> Result<()> {
+    require!(
+        multisig.owners.len() >= multisig.min_owners.max(1) as usize,
+        TooFewOwners
+    );
+    require!(
+        multisig.threshold >= 1 && multisig.threshold <= multisig.owners.len() as u64,
+        InvalidThreshold
+    );
+    require_unique_owners(&multisig.owners)
+}
+
+pub fn validate_delay(multisig: &Multisig, delay: i64) -> Result<()> {
+    require!((0..=30 * 24 * 3600).contains(&delay), InvalidDelay);
+    require!(delay >= multisig.min_delay, DelayBelowMinimum);
+    Ok(())
+}
+
+/// Eta of something proposed at `now` with `delay`. Multisigs predating
+/// delay validation may hold a negative one.
+pub fn eta_after(now: i64, delay: i64) -> Result<i64> {
+    require!(delay >= 0, InvalidDelay);
+    let eta = now.checked_add(delay).ok_or(ErrorCode::InvalidEta)?;
+    require!(eta > 0, InvalidEta);
+    Ok(eta)
+}
+
+/// A grace period of 0 would leave a single second to execute in.
+pub fn validate_grace_period(grace_period: i64) -> Result<()> {
+    require!(grace_period > 0, InvalidGracePeriod);
+    Ok(())
+}
+
+pub fn bump_config_version(multisig: &mut Multisig) -> Result<()> {
+    multisig.config_version = multisig
+        .config_version
+        .checked_add(1)
+        .ok_or(ErrorCode::Overflow)?;
+    Ok(())
+}
+
+/// Governance changes need a non-zero delay, at least `min_delay`.
+pub fn require_governance_delay(multisig: &Multisig) -> Result<()> {
+    require!(
+        multisig.delay > 0 && multisig.delay >= multisig.min_delay,
+        DelayBelowMinimum
+    );
+    Ok(())
+}
+
+/// Checks `proposer` may propose `instructions` at `now`, everything
+/// `init_transaction` requires that doesn't depend on the new account.
+pub fn check_proposal(
+    multisig: &Multisig,
+    proposer: &Pubkey,
+    instructions: &[TransactionInstruction],
+    action_hints: &[u8],
+    min_approvals: u64,
+    now: i64,
+) -> Result<()> {
+    let owner_index = multisig
+        .owners
+        .iter()
+        .position(|a| a == proposer)
+        .ok_or(ErrorCode::InvalidOwner)?;
+    require!(layout_current(multisig), MultisigNotMigrated);
+    require!(setup_confirmed(multisig), SetupNotConfirmed);
+    require!(
+        multisig.max_pending == 0 || multisig.pending_transactions < multisig.max_pending,
+        TooManyPending
+    );
+    validate_instructions(multisig, instructions)?;
+    let last_proposal_at = multisig
+        .last_proposal_at
+        .get(owner_index)
+        .copied()
+        .unwrap_or(0);
+    require!(
+        multisig.proposal_cooldown == 0
+            || last_proposal_at == 0
+            || now >= last_proposal_at.saturating_add(multisig.proposal_cooldown),
+        ProposalRateLimited
+    );
+    require!(
+        proposer_is_distinct(multisig, proposer),
+        ProposerNotDistinct
+    );
+    require!(
+        action_hints.is_empty() || action_hints.len() == instructions.len(),
+        InvalidActionHints
+    );
+    require!(
+        min_approvals <= multisig.owners.len() as u64,
+        InvalidThreshold
+    );
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn init_transaction(
+    multisig: &mut Account<Multisig>,
+    tx: &mut Transaction,
+    proposer: Pubkey,
+    instructions: Vec<TransactionInstruction>,
+    bump: u8,
+    nonce: Option<u64>,
+    canonicalize: bool,
+    action_hints: Vec<u8>,
+    min_approvals: u64,
+) -> ProgramResult {
+    let owner_index = multisig
+        .owners
+        .iter()
+        .position(|a| *a == proposer)
+        .ok_or(ErrorCode::InvalidOwner)?;
+    let multisig_key = multisig.key();
+    match nonce {
+        Some(nonce) => require_canonical_bump(
+            &[
+                b"transaction_nonce",
+                multisig_key.as_ref(),
+                nonce.to_le_bytes().as_ref(),
+            ],
+            bump,
+        )?,
+        None => require_canonical_bump(
+            &[
+                b"transaction",
+                multisig_key.as_ref(),
+                multisig.num_transactions.to_le_bytes().as_ref(),
+            ],
+            bump,
+        )?,
+    }
+    let now = clock()?.unix_timestamp;
+    check_proposal(
+        multisig,
+        &proposer,
+        &instructions,
+        &action_hints,
+        min_approvals,
+        now,
+    )?;
+
+    let mut signers = Vec::new();
+    signers.resize(multisig.owners.len(), false);
+    signers[owner_index] = true;
+
+    let num_owners = multisig.owners.len();
+    multisig.last_proposal_at.resize(num_owners, 0);
+    multisig.last_proposal_at[owner_index] = now;
+    record_proposer(multisig, proposer);
+    let mut approved_at = vec![0; multisig.owners.len()];
+    approved_at[owner_index] = now;
+    let mut approved_slot = vec![0; multisig.owners.len()];
+    approved_slot[owner_index] = clock()?.slot;
+
+    tx.multisig = multisig.key();
+    tx.index = multisig.num_transactions;
+    tx.bump = bump;
+    tx.layout_version = TRANSACTION_LAYOUT_VERSION;
+    tx.eta = eta_after(now, multisig.delay)?;
+    tx.created_at = now;
+    // Same owner set the proposer was looked up in.
+    tx.owners_seq_no = multisig.owners_seq_no;
+    tx.proposer = proposer;
+    let mut instructions = instructions;
+    if canonicalize {
+        canonicalize_instructions(&mut instructions);
+    }
+    tx.instructions = instructions;
+    tx.signers = signers;
+    tx.approved_at = approved_at;
+    tx.approved_slot = approved_slot;
+    tx.action_hints = action_hints;
+    tx.min_approvals = min_approvals;
+    if multisig.owner_snapshot_ttl > 0 {
+        tx.owner_snapshot = multisig.owners.clone();
+        tx.owner_snapshot_until = now.saturating_add(multisig.owner_snapshot_ttl);
+    }
+    record_proposal(multisig, tx)
+}
+
+/// Longest memo, in bytes.
+pub const MAX_MEMO_LEN: usize = 512;
+
+/// The title must be UTF-8 followed only by zero padding.
+pub fn set_title_and_memo(tx: &mut Transaction, title: Title, memo: String) -> Result<()> {
+    let bytes = title.bytes();
+    let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    require!(
+        std::str::from_utf8(&bytes[..len]).is_ok() && bytes[len..].iter().all(|&b| b == 0),
+        InvalidTitle
+    );
+    require!(memo.len() <= MAX_MEMO_LEN, MemoTooLong);
+    tx.title = title;
+    tx.memo = memo;
+    Ok(())
+}
+
+/// How many recent proposers a new proposer must differ from. Owner removals
+/// can't block proposing: the window never exceeds the owner count.
+pub fn recent_proposers_kept(multisig: &Multisig) -> usize {
+    let window = (multisig.min_distinct_recent_proposers as usize).min(multisig.owners.len());
+    window.saturating_sub(1)
+}
+
+/// Whether `proposer` is not among the last
+/// `min_distinct_recent_proposers - 1` proposers.
+pub fn proposer_is_distinct(multisig: &Multisig, proposer: &Pubkey) -> bool {
+    let recent = &multisig.recent_proposers;
+    let start = recent.len().saturating_sub(recent_proposers_kept(multisig));
+    !recent[start..].contains(proposer)
+}
+
+/// Remembers `proposer` for `proposer_is_distinct`, checked beforehand by
+/// `check_proposal`.
+pub fn record_proposer(multisig: &mut Multisig, proposer: Pubkey) {
+    let keep = recent_proposers_kept(multisig);
+    let recent = &mut multisig.recent_proposers;
+    recent.push(proposer);
+    let excess = recent.len().saturating_sub(keep);
+    recent.drain(..excess);
+}
+
+/// Who pays the rent of a proposal made by `signer`, see
+/// `create_transaction_account`.
+pub fn proposal_rent_payer<'a, 'info>(
+    multisig: &Multisig,
+    signer: &'a AccountInfo<'info>,
+) -> Option<&'a AccountInfo<'info>> {
+    match multisig.proposal_rent_source {
+        ProposalRentSource::Proposer => Some(signer),
+        ProposalRentSource::Pda => None,
+    }
+}
+
+/// Creates the transaction account at `tx_info`, the PDA of `multisig`'s next
+/// transaction or of `nonce`, with rent from `payer` or, when `None`, the
+/// multisig PDA.
+pub fn create_transaction_account<'info>(
+    multisig: &Account<'info, Multisig>,
+    tx_info: &AccountInfo<'info>,
+    payer: Option<&AccountInfo<'info>>,
+    system_program: &AccountInfo<'info>,
+    bump: u8,
+    nonce: Option<u64>,
+    space: usize,
+) -> ProgramResult {
+    let lamports = Rent::get()?
+        .minimum_balance(space)
+        .saturating_sub(tx_info.lamports());
+    match payer {
+        Some(payer) => solana_program::program::invoke(
+            &solana_program::system_instruction::transfer(payer.key, tx_info.key, lamports),
+            &[payer.clone(), tx_info.clone(), system_program.clone()],
+        )?,
+        None => {
+            let multisig_info = multisig.to_account_info();
+            require!(
+                available_balance(&multisig_info)? >= lamports,
+                InsufficientFunds
+            );
+            // The multisig holds data so it can't be the source of a system
+            // transfer, move the lamports directly.
+            **multisig_info.try_borrow_mut_lamports()? -= lamports;
+            **tx_info.try_borrow_mut_lamports()? += lamports;
+        }
+    }
+
+    // Have the transaction PDA allocate and assign itself.
+    let multisig_key = multisig.key();
+    let (prefix, seed): (&[u8], _) = match nonce {
+        Some(nonce) => (b"transaction_nonce", nonce.to_le_bytes()),
+        None => (b"transaction", multisig.num_transactions.to_le_bytes()),
+    };
+    let seeds = &[prefix, multisig_key.as_ref(), seed.as_ref(), &[bump]];
+    let accounts = &[tx_info.clone(), system_program.clone()];
+    solana_program::program::invoke_signed(
+        &solana_program::system_instruction::allocate(tx_info.key, space as u64),
+        accounts,
+        &[seeds],
+    )?;
+    solana_program::program::invoke_signed(
+        &solana_program::system_instruction::assign(tx_info.key, &crate::ID),
+        accounts,
+        &[seeds],
+    )
+}
+
+/// Stores `tx`, discriminator included, in an account made by
+/// `create_transaction_account`.
+pub fn write_transaction(tx_info: &AccountInfo, tx: &Transaction) -> ProgramResult {
+    let mut data = tx_info.try_borrow_mut_data()?;
+    let dst: &mut [u8] = &mut data;
+    tx.try_serialize(&mut std::io::Cursor::new(dst))?;
+    Ok(())
+}
+
+/// Emits `TransactionProposed` for a new transaction and counts it.
+pub fn record_proposal(multisig: &mut Account<Multisig>, tx: &Transaction) -> ProgramResult {
+    let mut data = tx.instructions.try_to_vec()?;
+    let truncated = data.len() > MAX_EVENT_INSTRUCTIONS_SIZE;
+    let instructions_hash = instructions_hash(&data);
+    data.truncate(MAX_EVENT_INSTRUCTIONS_SIZE);
+    emit!(TransactionProposed {
+        multisig: multisig.key(),
+        index: tx.index,
+        proposer: tx.proposer,
+        eta: tx.eta,
+        num_instructions: tx.instructions.len() as u32,
+        instructions_hash,
+        instructions: data,
+        truncated,
+    });
+
+    multisig.num_transactions = multisig
+        .num_transactions
+        .checked_add(1)
+        .ok_or(ErrorCode::Overflow)?;
+    multisig.pending_transactions = multisig
+        .pending_transactions
+        .checked_add(1)
+        .ok_or(ErrorCode::Overflow)?;
+    Ok(())
+}
+
+/// Notifies the transaction's callback program, if any, that it executed. The
+/// callback only gets read-only access to the transaction and multisig and
+/// the multisig PDA doesn't sign it.
+pub fn invoke_callback<'info>(
+    tx: &Account<'info, Transaction>,
+    multisig: &Account<'info, Multisig>,
+    accounts: &[AccountInfo<'info>],
+) -> ProgramResult {
+    if tx.callback_program == Pubkey::default() {
+        return Ok(());
+    }
+    let ix = solana_program::instruction::Instruction {
+        program_id: tx.callback_program,
+        accounts: vec![
+            AccountMeta::new_readonly(tx.key(), false),
+            AccountMeta::new_readonly(multisig.key(), false),
+        ],
+        data: tx.callback_data.clone(),
+    };
+    let mut infos = vec![tx.to_account_info(), multisig.to_account_info()];
+    infos.extend_from_slice(accounts);
+    solana_program::program::invoke(&ix, &infos)
+}
+
+/// The Clock sysvar, failing with a clear error rather than the generic
+/// sysvar one when the runtime can't provide it.
+pub fn clock() -> Result<Clock> {
+    Clock::get().map_err(|_| ErrorCode::ClockUnavailable.into())
+}
+
+/// Lamports held by `info` above its rent-exempt minimum.
+pub fn available_balance(info: &AccountInfo) -> Result<u64> {
+    let reserve = Rent::get()?.minimum_balance(info.data_len());
+    Ok(info.lamports().saturating_sub(reserve))
+}
+
+/// Fails if executed instructions left `info` below its rent-exempt
+/// minimum, which would get the account closed.
+pub fn require_rent_exempt(info: &AccountInfo) -> Result<()> {
+    let reserve = Rent::get()?.minimum_balance(info.data_len());
+    require!(info.lamports() >= reserve, WouldDeinitialize);
+    Ok(())
+}
+
+pub fn require_unsigned_by_others(multisig: &Multisig, tx: &Transaction) -> Result<()> {
+    let proposer_index = multisig.owners.iter().position(|a| *a == tx.proposer);
+    let signed_by_other = tx
+        .signers
+        .iter()
+        .enumerate()
+        .any(|(i, &signed)| signed && Some(i) != proposer_index);
+    require!(!signed_by_other, TransactionAlreadySigned);
+    Ok(())
+}
+
+/// Reallocates `account` to hold at least `space` bytes, with `payer`
+/// covering the extra rent.
+pub fn grow_account<'info>(
+    account: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    space: usize,
+) -> ProgramResult {
+    if space <= account.data_len() {
+        return Ok(());
+    }
+    let missing = Rent::get()?
+        .minimum_balance(space)
+        .saturating_sub(account.lamports());
+    if missing > 0 {
+        solana_program::program::invoke(
+            &solana_program::system_instruction::transfer(payer.key, account.key, missing),
+            &[payer.clone(), account.clone(), system_program.clone()],
+        )?;
+    }
+    account.realloc(space, false)
+}
+
+/// Seed constraints accept any bump that derives a valid address, only allow
+/// the canonical one so each seed maps to a single account.
+pub fn require_canonical_bump(seeds: &[&[u8]], bump: u8) -> Result<()> {
+    let (_, canonical) = Pubkey::find_program_address(seeds, &crate::ID);
+    require!(bump == canonical, InvalidBump);
+    Ok(())
+}
+
+/// Defense in depth for the `has_one = multisig` account constraints.
+pub fn require_transaction_of(multisig: &Account<Multisig>, tx: &Transaction) -> Result<()> {
+    require!(tx.multisig == multisig.key(), MultisigMismatch);
+    Ok(())
+}
+
+pub fn require_unique_owners(owners: &[Pubkey]) -> Result<()> {
+    let mut uniq_owners = owners.to_vec();
+    uniq_owners.sort();
+    uniq_owners.dedup();
+    require!(owners.len() == uniq_owners.len(), UniqueOwners);
+    Ok(())
+}
+
+/// Sorts the non-signer read-only accounts of each instruction by pubkey, in
+/// place of one another, leaving every other account where it was.
+pub fn canonicalize_instructions(instructions: &mut [TransactionInstruction]) {
+    for ix in instructions.iter_mut() {
+        let positions: Vec<usize> = (0..ix.keys.len())
+            .filter(|&i| !ix.keys[i].is_signer && !ix.keys[i].is_writable)
+            .collect();
+        let mut sorted: Vec<TransactionInstructionMeta> =
+            positions.iter().map(|&i| ix.keys[i]).collect();
+        sorted.sort_by_key(|a| a.pubkey);
+        for (&i, meta) in positions.iter().zip(sorted) {
+            ix.keys[i] = meta;
+        }
+    }
+}
+
+pub fn seed_accounts_space(seed_accounts: &[SeedSpec]) -> Result<usize> {
+    let data = seed_accounts
+        .try_to_vec()
+        .map_err(|_| ErrorCode::InvalidSeedSpec)?;
+    Ok(data.len())
+}
+
+/// Layout written by this version, see `Multisig::layout_version`.
+pub const MULTISIG_LAYOUT_VERSION: u8 = 1;
+
+/// The multisig as first released, fixed-size fields now live in its
+/// reserved space.
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct LegacyMultisig {
+    pub base: Pubkey,
+    pub bump: u8,
+    pub threshold: u64,
+    pub delay: i64,
+    pub grace_period: i64,
+    pub num_transactions: u64,
+    pub owners_seq_no: u64,
+    pub owners: Vec<Pubkey>,
+    pub reserved: [u64; 16],
+}
+
+/// Decodes `data`, a legacy multisig account, as the current `Multisig`.
+/// Bytes past the legacy reserved space may be left over from a longer
+/// owner list, so new fields start zeroed rather than read from them.
+/// Pending transactions of legacy multisigs aren't counted.
+pub fn migrate_legacy_multisig(data: &[u8], now: i64) -> Result<Multisig> {
+    if data.len() < 8 || data[..8] != Multisig::discriminator() {
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+    let legacy = LegacyMultisig::deserialize(&mut &data[8..])
+        .map_err(|_| ProgramError::InvalidAccountData)?;
+    // The layout version is the first byte after the owners.
+    require!(legacy.reserved == [0; 16], AlreadyMigrated);
+
+    let mut bytes = legacy
+        .try_to_vec()
+        .map_err(|_| ProgramError::InvalidAccountData)?;
+    bytes.resize(bytes.len() + std::mem::size_of::<Multisig>(), 0);
+    let mut multisig =
+        Multisig::deserialize(&mut &bytes[..]).map_err(|_| ProgramError::InvalidAccountData)?;
+    multisig.layout_version = MULTISIG_LAYOUT_VERSION;
+    multisig.min_owners = 1;
+    multisig.last_activity_at = now;
+    Ok(multisig)
+}
+
+/// Layout written by this version, see `Transaction::layout_version`.
+pub const TRANSACTION_LAYOUT_VERSION: u8 = 1;
+
+/// The transaction as first released.
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct LegacyTransaction {
+    pub multisig: Pubkey,
+    pub index: u64,
+    pub bump: u8,
+    pub eta: i64,
+    pub owners_seq_no: u64,
+    pub proposer: Pubkey,
+    pub instructions: Vec<TransactionInstruction>,
+    pub signers: Vec<bool>,
+    pub executor: Pubkey,
+    pub executed_at: i64,
+    pub reserved: [u64; 16],
+}
+
+/// Decodes `data`, a legacy transaction account, as the current
+/// `Transaction`. Per-owner fields are sized to the signers. Approval and
+/// creation times weren't recorded and start at zero, so an approval TTL or
+/// proposal cutoff treats them as old.
+pub fn migrate_legacy_transaction(data: &[u8]) -> Result<Transaction> {
+    if data.len() < 8 || data[..8] != Transaction::discriminator() {
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+    let legacy = LegacyTransaction::deserialize(&mut &data[8..])
+        .map_err(|_| ProgramError::InvalidAccountData)?;
+    // The layout version is the first byte after `executed_at`.
+    require!(legacy.reserved == [0; 16], AlreadyMigrated);
+
+    let num_signers = legacy.signers.len();
+    Ok(Transaction {
+        multisig: legacy.multisig,
+        index: legacy.index,
+        bump: legacy.bump,
+        eta: legacy.eta,
+        owners_seq_no: legacy.owners_seq_no,
+        proposer: legacy.proposer,
+        instructions: legacy.instructions,
+        signers: legacy.signers,
+        executor: legacy.executor,
+        executed_at: legacy.executed_at,
+        layout_version: TRANSACTION_LAYOUT_VERSION,
+        approved_at: vec![0; num_signers],
+        approved_slot: vec![0; num_signers],
+        ..Transaction::default()
+    })
+}
+
+pub fn multisig_space() -> usize {
+    4 + std::mem::size_of::<Multisig>()
+        + 4 * (4 + (15 * 32))
+        + 3 * (4 + (15 * 8))
+        + 4
+        + 15 * std::mem::size_of::<Delegation>()
+        + 4
+        + 15
+        + 4
+        + MAX_VALUE_TIERS * std::mem::size_of::<ValueTier>()
+}
+
+pub fn audit_log_space() -> usize {
+    8 + std::mem::size_of::<AuditLog>() + MAX_AUDIT_RECORDS * std::mem::size_of::<AuditRecord>()
+}
+
+pub fn template_space(instructions: &[TransactionInstruction], params: &[TemplateParam]) -> usize {
+    let mut space = 8 + std::mem::size_of::<ProposalTemplate>() + std::mem::size_of_val(params);
     for ix in instructions.iter() {
         space += std::mem::size_of::<Pubkey>()
-            + (ix.keys.len() as usize) * std::mem::size_of::<TransactionInstructionMeta>()
-            + (ix.data.len() as usize)
+            + 4
+            + ix.keys.len() * std::mem::size_of::<TransactionInstructionMeta>()
+            + 4
+            + ix.data.len()
+    }
+    space
+}
+
+/// Bytes a template parameter takes from the instantiation parameters.
+pub fn template_param_len(param: &TemplateParam) -> usize {
+    match param {
+        TemplateParam::Data { length, .. } => *length as usize,
+        TemplateParam::Key { .. } => std::mem::size_of::<Pubkey>(),
+    }
+}
+
+/// Instructions of a template with its parameters replaced by `values`,
+/// their bytes concatenated in order.
+pub fn instantiate_template(
+    instructions: &[TransactionInstruction],
+    params: &[TemplateParam],
+    values: &[u8],
+) -> Result<Vec<TransactionInstruction>> {
+    let expected: usize = params.iter().map(template_param_len).sum();
+    require!(values.len() == expected, InvalidTemplateParams);
+    let mut instructions = instructions.to_vec();
+    let mut values = values;
+    for param in params.iter() {
+        let (value, rest) = values.split_at(template_param_len(param));
+        values = rest;
+        match *param {
+            TemplateParam::Data {
+                instruction_index,
+                offset,
+                length,
+            } => {
+                let data = &mut instructions
+                    .get_mut(instruction_index as usize)
+                    .ok_or(ErrorCode::InvalidTemplateParams)?
+                    .data;
+                let range = offset as usize..offset as usize + length as usize;
+                data.get_mut(range)
+                    .ok_or(ErrorCode::InvalidTemplateParams)?
+                    .copy_from_slice(value);
+            }
+            TemplateParam::Key {
+                instruction_index,
+                key_index,
+            } => {
+                instructions
+                    .get_mut(instruction_index as usize)
+                    .and_then(|ix| ix.keys.get_mut(key_index as usize))
+                    .ok_or(ErrorCode::InvalidTemplateParams)?
+                    .pubkey = Pubkey::new(value);
+            }
+        }
+    }
+    Ok(instructions)
+}
+
+pub fn owner_metadata_space() -> usize {
+    8 + std::mem::size_of::<OwnerMetadata>()
+}
+
+pub fn transaction_space(instructions: Vec<TransactionInstruction>) -> usize {
+    let mut space = 4
+        + std::mem::size_of::<Transaction>()
+        + 2 * (4 + 15)
+        + 4
+        + 2 * (4 + (15 * 8))
+        + 4
+        + (15 * 4)
+        + 4
+        + MAX_RETURN_DATA_LEN;
+    for ix in instructions.iter() {
+        space += 1
+            + std::mem::size_of::<Pubkey>()
+            + ix.keys.len() * std::mem::size_of::<TransactionInstructionMeta>()
+            + ix.data.len()
     }
     space
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A multisig with every field zeroed, like a freshly allocated account.
+    fn zeroed_multisig() -> Multisig {
+        Multisig::deserialize(&mut &vec![0; 10 * 1024][..]).unwrap()
+    }
+
+    #[test]
+    fn multisig_signer_seeds_derive_the_multisig_pda() {
+        for purpose in &[[0; 8], *b"payroll\0"] {
+            let mut multisig = zeroed_multisig();
+            multisig.base = Pubkey::new_unique();
+            multisig.purpose = *purpose;
+            let mut seeds: Vec<&[u8]> = vec![b"multisig", multisig.base.as_ref()];
+            if *purpose != [0; 8] {
+                seeds.push(purpose);
+            }
+            let (address, bump) = Pubkey::find_program_address(&seeds, &ID);
+            multisig.bump = bump;
+
+            let derived =
+                Pubkey::create_program_address(&multisig_signer_seeds(&multisig), &ID).unwrap();
+            assert_eq!(derived, address);
+        }
+    }
+
+    #[test]
+    fn clock_unavailable_maps_to_its_error() {
+        // Off-chain there's no Clock sysvar to read.
+        let err = match clock() {
+            Ok(_) => panic!("clock available off-chain"),
+            Err(err) => ProgramError::from(err),
+        };
+        assert_eq!(err, ProgramError::from(ErrorCode::ClockUnavailable));
+    }
+
+    #[test]
+    fn migrate_legacy_multisig_keeps_legacy_fields() {
+        let legacy = LegacyMultisig {
+            base: Pubkey::new_unique(),
+            bump: 254,
+            threshold: 2,
+            delay: 3600,
+            grace_period: 7 * 24 * 3600,
+            num_transactions: 12,
+            owners_seq_no: 3,
+            owners: vec![Pubkey::new_unique(), Pubkey::new_unique()],
+            reserved: [0; 16],
+        };
+        let mut data = Multisig::discriminator().to_vec();
+        data.extend(legacy.try_to_vec().unwrap());
+        // Left over from a longer owner list
+        data.extend(vec![0xff; 512]);
+
+        let multisig = migrate_legacy_multisig(&data, 100).unwrap();
+        assert_eq!(multisig.base, legacy.base);
+        assert_eq!(multisig.bump, legacy.bump);
+        assert_eq!(multisig.threshold, legacy.threshold);
+        assert_eq!(multisig.delay, legacy.delay);
+        assert_eq!(multisig.grace_period, legacy.grace_period);
+        assert_eq!(multisig.num_transactions, legacy.num_transactions);
+        assert_eq!(multisig.owners_seq_no, legacy.owners_seq_no);
+        assert_eq!(multisig.owners, legacy.owners);
+        assert!(layout_current(&multisig));
+        assert!(setup_confirmed(&multisig));
+        assert_eq!(multisig.min_owners, 1);
+        assert_eq!(multisig.last_activity_at, 100);
+        assert_eq!(multisig.recovery_owner, Pubkey::default());
+        assert!(multisig.allowance.is_empty() && multisig.value_tiers.is_empty());
+
+        // Migrated accounts can't be migrated again
+        let mut migrated = Vec::new();
+        multisig.try_serialize(&mut migrated).unwrap();
+        let err = match migrate_legacy_multisig(&migrated, 100) {
+            Ok(_) => panic!("migrated twice"),
+            Err(err) => ProgramError::from(err),
+        };
+        assert_eq!(err, ProgramError::from(ErrorCode::AlreadyMigrated));
+    }
+
+    #[test]
+    fn migrate_legacy_transaction_keeps_legacy_fields() {
+        let legacy = LegacyTransaction {
+            multisig: Pubkey::new_unique(),
+            index: 7,
+            bump: 253,
+            eta: 1_000,
+            owners_seq_no: 2,
+            proposer: Pubkey::new_unique(),
+            instructions: vec![TransactionInstruction {
+                program_id: Pubkey::new_unique(),
+                keys: vec![TransactionInstructionMeta {
+                    pubkey: Pubkey::new_unique(),
+                    is_signer: false,
+                    is_writable: true,
+                }],
+                data: vec![1, 2, 3],
+            }],
+            signers: vec![true, false, true],
+            executor: Pubkey::default(),
+            executed_at: 0,
+            reserved: [0; 16],
+        };
+        let mut data = Transaction::discriminator().to_vec();
+        data.extend(legacy.try_to_vec().unwrap());
+
+        let tx = migrate_legacy_transaction(&data).unwrap();
+        assert_eq!(tx.multisig, legacy.multisig);
+        assert_eq!(tx.index, legacy.index);
+        assert_eq!(tx.bump, legacy.bump);
+        assert_eq!(tx.eta, legacy.eta);
+        assert_eq!(tx.owners_seq_no, legacy.owners_seq_no);
+        assert_eq!(tx.proposer, legacy.proposer);
+        assert_eq!(tx.instructions, legacy.instructions);
+        assert_eq!(tx.signers, legacy.signers);
+        assert!(transaction_layout_current(&tx));
+        assert_eq!(tx.approved_at, vec![0; 3]);
+        assert_eq!(tx.approved_slot, vec![0; 3]);
+        assert_eq!(tx.group_size, 0);
+        assert_eq!(tx.title, Title::default());
+
+        // The current layout fits the space allocated for it
+        let mut migrated = Vec::new();
+        tx.try_serialize(&mut migrated).unwrap();
+        assert!(migrated.len() <= transaction_space(tx.instructions.clone()));
+
+        // Migrated accounts can't be migrated again
+        let err = match migrate_legacy_transaction(&migrated) {
+            Ok(_) => panic!("migrated twice"),
+            Err(err) => ProgramError::from(err),
+        };
+        assert_eq!(err, ProgramError::from(ErrorCode::AlreadyMigrated));
+    }
+}
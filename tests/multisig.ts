@@ -1,9 +1,10 @@
 import * as assert from "assert";
+import { createHash } from "crypto";
 import * as anchor from "@project-serum/anchor";
 import { BN, Program, web3 } from "@project-serum/anchor";
 
 import { Multisig } from "../target/types/multisig.js";
-import { AccountMeta, LAMPORTS_PER_SOL } from "@solana/web3.js";
+import { AccountMeta, LAMPORTS_PER_SOL, ParsedAccountData } from "@solana/web3.js";
 
 const provider = anchor.Provider.env();
 anchor.setProvider(provider);
@@ -14,6 +15,9 @@ const base = anchor.web3.Keypair.generate();
 const ownerA = anchor.web3.Keypair.generate();
 const ownerB = anchor.web3.Keypair.generate();
 const ownerC = anchor.web3.Keypair.generate();
+const noMigration = new web3.PublicKey(0);
+const noTitle = titleOf(Buffer.alloc(48));
+const noPurpose = new Array(8).fill(0);
 let key, bump;
 
 describe("multisig", () => {
@@ -27,25 +31,46 @@ describe("multisig", () => {
 
   it("createMultisig", async () => {
     const owners = [ownerA.publicKey, ownerB.publicKey, ownerC.publicKey];
-    await program.rpc.createMultisig(owners, bn(2, 0), bn(0), bump, {
-      accounts: {
-        signer: wallet.publicKey,
-        base: base.publicKey,
-        multisig: key,
-        systemProgram: anchor.web3.SystemProgram.programId,
-      },
+    const accounts = {
+      signer: wallet.publicKey,
+      base: base.publicKey,
+      multisig: key,
+      systemProgram: anchor.web3.SystemProgram.programId,
+    };
+
+    // Can't create a multisig for a base that didn't sign
+    const ix = program.instruction.createMultisig(owners, bn(2, 0), bn(1, 0), bump, { accounts });
+    ix.keys.find(k => k.pubkey.equals(base.publicKey)).isSigner = false;
+    try {
+      await program.provider.send(new web3.Transaction().add(ix));
+      throw new Error('did not throw');
+    } catch (err) {
+      // AccountNotSigner
+      assert.match(err.message, /0xbc2/);
+    }
+
+    await program.rpc.createMultisig(owners, bn(2, 0), bn(1, 0), bump, {
+      accounts,
+      signers: [base],
     });
 
-    const multisig = await program.account.multisig.fetch(key);
+    let multisig = await program.account.multisig.fetch(key);
     assert.equal(multisig.bump, bump);
     assert.equal(multisig.numTransactions.toNumber(), 0);
     assert.equal(multisig.threshold.toNumber(), 2);
     assert.deepEqual(multisig.owners, owners);
+    assert.equal(multisig.confirmed, false);
+
+    for (const owner of [ownerA, ownerB, ownerC]) {
+      await confirmSetup(key, owner);
+    }
+    multisig = await program.account.multisig.fetch(key);
+    assert.equal(multisig.confirmed, true);
   });
 
   it("setOwners", async () => {
     let multisig = await program.account.multisig.fetch(key);
-    const ix = program.instruction.setOwners([ownerA.publicKey], {
+    const ix = program.instruction.setOwners([ownerA.publicKey], true, {
       accounts: {
         multisig: key
       },
@@ -54,7 +79,7 @@ describe("multisig", () => {
 
     // Can't create when now an owner
     try {
-      await program.rpc.createTransaction([ix], txBump, {
+      await program.rpc.createTransaction([ix], txBump, false, Buffer.from([]), new BN(0), noTitle, '', false, {
         accounts: {
           signer: wallet.publicKey,
           multisig: key,
@@ -68,7 +93,7 @@ describe("multisig", () => {
     }
 
     // Create setOwners transaction
-    await program.rpc.createTransaction([ix], txBump, {
+    await program.rpc.createTransaction([ix], txBump, false, Buffer.from([]), new BN(0), noTitle, '', false, {
       accounts: {
         signer: ownerA.publicKey,
         multisig: key,
@@ -95,10 +120,11 @@ describe("multisig", () => {
         isSigner: false,
         isWritable: false,
       }]);
+    await waitForEta(txKey);
 
     // Can't execute before threshold is reached
     try {
-      await program.rpc.executeTransaction({
+      await program.rpc.executeTransaction(false, {
         accounts: {
           signer: ownerA.publicKey,
           multisig: key,
@@ -123,7 +149,7 @@ describe("multisig", () => {
     });
 
     // Execute transaction, owner will be updated after
-    await program.rpc.executeTransaction({
+    await program.rpc.executeTransaction(false, {
       accounts: {
         signer: ownerC.publicKey,
         multisig: key,
@@ -135,7 +161,7 @@ describe("multisig", () => {
 
     // Can't execute a transaction twice
     try {
-      await program.rpc.executeTransaction({
+      await program.rpc.executeTransaction(false, {
         accounts: {
           signer: ownerA.publicKey,
           multisig: key,
@@ -159,7 +185,7 @@ describe("multisig", () => {
   });
 
   it('changeThreshold', async () => {
-    const ix = program.instruction.changeThreshold(bn(1, 0), {
+    const ix = program.instruction.changeThreshold(bn(1, 0), false, {
       accounts: {
         multisig: key
       },
@@ -179,63 +205,4118 @@ describe("multisig", () => {
     const multisig = await program.account.multisig.fetch(key);
     assert.equal(multisig.delay.toNumber(), 60);
   });
-});
 
-async function createApproveExecute(ix) {
-  let multisig = await program.account.multisig.fetch(key);
-  const [txKey, txBump] = await pda(['transaction', key, multisig.numTransactions.toNumber()]);
-  await program.rpc.createTransaction([ix], txBump, {
-    accounts: {
-      signer: ownerA.publicKey,
-      multisig: key,
-      transaction: txKey,
-      systemProgram: web3.SystemProgram.programId,
-    },
-    signers: [ownerA],
+  it('changeMaxPending', async () => {
+    const msKey = await createMultisig([ownerA.publicKey], 1, 0);
+    const ix = program.instruction.changeMaxPending(bn(2, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    await createApproveExecute(ix, msKey);
+    let multisig = await program.account.multisig.fetch(msKey);
+    assert.equal(multisig.maxPending.toNumber(), 2);
+    assert.equal(multisig.pendingTransactions.toNumber(), 0);
+
+    await createTransaction(ix, msKey);
+    await createTransaction(ix, msKey);
+    multisig = await program.account.multisig.fetch(msKey);
+    assert.equal(multisig.pendingTransactions.toNumber(), 2);
+
+    // Can't create more than max pending transactions
+    try {
+      await createTransaction(ix, msKey);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /Too many pending transactions/);
+    }
   });
 
-  const remainingAccounts = ix.keys
-    .map(k => Object.assign(k, { isSigner: false })).concat([{
-      pubkey: program.programId,
+  it('setOwners timelocked', async () => {
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey], 1, 2);
+    const ix = program.instruction.setOwners([ownerB.publicKey, ownerC.publicKey], false, {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const txKey = await createTransaction(ix, msKey);
+    const tx = await program.account.transaction.fetch(txKey);
+    assert.ok(tx.eta.toNumber() > 0);
+
+    // Can't execute before the delay elapsed
+    try {
+      await executeTransaction(ix, txKey, msKey);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /Before transation ETA/);
+    }
+
+    await sleep(3000);
+    await executeTransaction(ix, txKey, msKey);
+    const multisig = await program.account.multisig.fetch(msKey);
+    assert.deepEqual(multisig.owners, [ownerB.publicKey, ownerC.publicKey]);
+    assert.equal(multisig.ownersSeqNo.toNumber(), 1);
+  });
+
+  it('deleteTransaction', async () => {
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey], 1, 0);
+    const ix = program.instruction.changeMaxPending(bn(0, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    await createApproveExecute(ix, msKey);
+    const txKey = await createTransaction(ix, msKey);
+
+    // Only the proposer can delete
+    try {
+      await program.rpc.deleteTransaction({
+        accounts: {
+          signer: ownerB.publicKey,
+          multisig: msKey,
+          transaction: txKey,
+        },
+        signers: [ownerB],
+      });
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /did not create/);
+    }
+
+    await program.rpc.deleteTransaction({
+      accounts: {
+        signer: ownerA.publicKey,
+        multisig: msKey,
+        transaction: txKey,
+      },
+      signers: [ownerA],
+    });
+    assert.equal(await program.provider.connection.getAccountInfo(txKey), null);
+
+    const multisig = await program.account.multisig.fetch(msKey);
+    assert.equal(multisig.numTransactions.toNumber(), 2);
+    assert.equal(multisig.pendingTransactions.toNumber(), 0);
+    assert.equal(multisig.executedTransactions.toNumber(), 1);
+    assert.equal(multisig.cancelledTransactions.toNumber(), 1);
+  });
+
+  it('setOwners threshold reduction', async () => {
+    const owners = [ownerA.publicKey, ownerB.publicKey, ownerC.publicKey];
+    const msKey = await createMultisig(owners, 2, 1);
+
+    // Shrinking below the threshold must be confirmed
+    const unconfirmedIx = program.instruction.setOwners([ownerA.publicKey], false, {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    let txKey = await createTransaction(unconfirmedIx, msKey);
+    await approve(txKey, msKey, ownerB);
+    await waitForEta(txKey);
+    try {
+      await executeTransaction(unconfirmedIx, txKey, msKey);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /Threshold must be less than or equal/);
+    }
+
+    let event;
+    const listener = program.addEventListener('ThresholdAutoAdjusted', e => event = e);
+    const ix = program.instruction.setOwners([ownerA.publicKey], true, {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    txKey = await createTransaction(ix, msKey);
+    await approve(txKey, msKey, ownerB);
+    await waitForEta(txKey);
+    await executeTransaction(ix, txKey, msKey);
+    await sleep(1000);
+    await program.removeEventListener(listener);
+
+    const multisig = await program.account.multisig.fetch(msKey);
+    assert.equal(multisig.threshold.toNumber(), 1);
+    assert.ok(event.multisig.equals(msKey));
+    assert.equal(event.oldThreshold.toNumber(), 2);
+    assert.equal(event.newThreshold.toNumber(), 1);
+  });
+
+  it('createTransaction canonicalize', async () => {
+    const msKey = await createMultisig([ownerA.publicKey], 1, 0);
+    const readonly = [1, 2, 3].map(() => ({
+      pubkey: anchor.web3.Keypair.generate().publicKey,
       isSigner: false,
       isWritable: false,
-    }])
-  await program.rpc.executeTransaction({
-    accounts: {
-      signer: ownerA.publicKey,
-      multisig: key,
-      transaction: txKey,
-    },
-    remainingAccounts,
-    signers: [ownerA],
+    }));
+    const writable = { pubkey: msKey, isSigner: false, isWritable: true };
+    const ix = {
+      programId: program.programId,
+      keys: [readonly[2], writable, readonly[0], readonly[1]],
+      data: Buffer.from([]),
+    };
+
+    const multisig = await program.account.multisig.fetch(msKey);
+    const [txKey, txBump] = await pda(['transaction', msKey, multisig.numTransactions.toNumber()]);
+    await program.rpc.createTransaction([ix], txBump, true, Buffer.from([]), new BN(0), noTitle, '', false, {
+      accounts: {
+        signer: ownerA.publicKey,
+        multisig: msKey,
+        transaction: txKey,
+        systemProgram: web3.SystemProgram.programId,
+      },
+      signers: [ownerA],
+    });
+
+    const sorted = readonly.map(k => k.pubkey)
+      .sort((a, b) => Buffer.compare(a.toBuffer(), b.toBuffer()));
+    const tx = await program.account.transaction.fetch(txKey);
+    const keys = tx.instructions[0].keys.map(k => k.pubkey.toString());
+    assert.deepEqual(keys, [sorted[0], msKey, sorted[1], sorted[2]].map(k => k.toString()));
   });
-  return txKey;
-}
 
-async function airdrop(key) {
-  const tx = await program.provider.connection.requestAirdrop(
-    key,
-    LAMPORTS_PER_SOL
-  );
-  await program.provider.connection.confirmTransaction(tx);
-}
+  it('executeAllReady', async () => {
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey], 2, 0);
+    const readyIx = program.instruction.changeMaxPending(bn(5, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const pendingIx = program.instruction.changeMaxPending(bn(7, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const readyKey = await createTransaction(readyIx, msKey);
+    const pendingKey = await createTransaction(pendingIx, msKey);
+    const misusedKey = await createTransaction(pendingIx, msKey);
+    await approve(readyKey, msKey, ownerB);
+    await approve(misusedKey, msKey, ownerB);
 
-async function pda(seeds, programId = program.programId) {
-  for (let i = 0; i < seeds.length; i++) {
-    if (typeof seeds[i] === "number") {
-      const a = Buffer.from(new BN(seeds[i]).toArray().reverse());
-      const b = Buffer.alloc(8);
-      a.copy(b);
-      seeds[i] = b;
+    const accounts = (txKey, target = msKey) => [
+      { pubkey: txKey, isSigner: false, isWritable: true },
+      { pubkey: target, isSigner: false, isWritable: true },
+      { pubkey: program.programId, isSigner: false, isWritable: false },
+    ];
+    // Ready, but passed with accounts its instructions don't use
+    const misused = accounts(misusedKey, ownerC.publicKey);
+    await program.rpc.executeAllReady(Buffer.from([2, 2, 2]), {
+      accounts: {
+        signer: ownerA.publicKey,
+        multisig: msKey,
+      },
+      remainingAccounts: accounts(readyKey).concat(accounts(pendingKey), misused),
+      signers: [ownerA],
+    });
+
+    const multisig = await program.account.multisig.fetch(msKey);
+    assert.equal(multisig.maxPending.toNumber(), 5);
+    assert.equal(multisig.executedTransactions.toNumber(), 1);
+    assert.equal(multisig.pendingTransactions.toNumber(), 2);
+    const ready = await program.account.transaction.fetch(readyKey);
+    assert.notEqual(ready.executedAt.toNumber(), 0);
+    for (const txKey of [pendingKey, misusedKey]) {
+      const tx = await program.account.transaction.fetch(txKey);
+      assert.equal(tx.executedAt.toNumber(), 0);
     }
-    if (typeof seeds[i] === "string") {
-      seeds[i] = Buffer.from(seeds[i]);
+  });
+
+  it('approveInstructions', async () => {
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey], 2, 0);
+    const maxPendingIx = program.instruction.changeMaxPending(bn(5, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const dataLenIx = program.instruction.changeMaxInstructionDataLen(100, {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const txKey = await createTransaction([maxPendingIx, dataLenIx], msKey);
+
+    // ownerB only approves the first instruction
+    const approveFirst = () => program.rpc.approveInstructions(1, {
+      accounts: {
+        signer: ownerB.publicKey,
+        multisig: msKey,
+        transaction: txKey,
+      },
+      signers: [ownerB],
+    });
+    await approveFirst();
+    await executeStored(txKey, msKey);
+
+    const multisig = await program.account.multisig.fetch(msKey);
+    assert.equal(multisig.maxPending.toNumber(), 5);
+    assert.equal(multisig.maxInstructionDataLen, 0);
+    const tx = await program.account.transaction.fetch(txKey);
+    assert.equal(tx.skippedInstructions, 2);
+
+    // Same checks as approve
+    try {
+      await approveFirst();
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /already been executed/);
     }
-    if (typeof seeds[i].toBuffer == "function") {
-      seeds[i] = seeds[i].toBuffer();
+  });
+
+  it('executeTransaction unexpected account', async () => {
+    const msKey = await createMultisig([ownerA.publicKey], 1, 0);
+    const ix = program.instruction.changeMaxPending(bn(5, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const txKey = await createTransaction(ix, msKey);
+    try {
+      await program.rpc.executeTransaction(false, {
+        accounts: {
+          signer: ownerA.publicKey,
+          multisig: msKey,
+          transaction: txKey,
+        },
+        remainingAccounts: [
+          { pubkey: msKey, isSigner: false, isWritable: true },
+          { pubkey: program.programId, isSigner: false, isWritable: false },
+          { pubkey: ownerC.publicKey, isSigner: false, isWritable: true },
+        ],
+        signers: [ownerA],
+      });
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /not referenced by the transaction/);
     }
-  }
-  return await web3.PublicKey.findProgramAddress(seeds, programId);
+    await executeStored(txKey, msKey);
+  });
+
+  it('changePostChangeCooldown', async () => {
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey], 1, 1);
+    await createApproveExecute(program.instruction.changePostChangeCooldown(bn(10, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    }), msKey);
+    const owners = [ownerA.publicKey, ownerB.publicKey, ownerC.publicKey];
+    await createApproveExecute(program.instruction.setOwners(owners, false, {
+      accounts: {
+        multisig: msKey
+      },
+    }), msKey);
+
+    // Executions are blocked until the cooldown after the owner change ends
+    const ix = program.instruction.changeMaxPending(bn(5, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const txKey = await createTransaction(ix, msKey);
+    await waitForEta(txKey);
+    try {
+      await executeStored(txKey, msKey);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /paused after an owner change/);
+    }
+    await sleep(10000);
+    await executeStored(txKey, msKey);
+    const multisig = await program.account.multisig.fetch(msKey);
+    assert.equal(multisig.maxPending.toNumber(), 5);
+  });
+
+  it('changeProposalCooldown', async () => {
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey], 1, 0);
+    await createApproveExecute(program.instruction.changeProposalCooldown(bn(60, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    }), msKey);
+
+    const ix = program.instruction.changeMaxPending(bn(5, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    await createTransaction(ix, msKey, ownerB);
+    try {
+      await createTransaction(ix, msKey, ownerB);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /proposed a transaction too recently/);
+    }
+  });
+
+  it('TransactionProposed event', async () => {
+    const msKey = await createMultisig([ownerA.publicKey], 1, 0);
+    const ix = program.instruction.changeMaxPending(bn(5, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+
+    let event;
+    const listener = program.addEventListener('TransactionProposed', e => event = e);
+    await createTransaction(ix, msKey);
+    await sleep(1000);
+    await program.removeEventListener(listener);
+
+    const hash = createHash('sha256')
+      .update('multisig:instructions')
+      .update(Buffer.from(event.instructions))
+      .digest();
+    assert.ok(event.multisig.equals(msKey));
+    assert.equal(event.index.toNumber(), 0);
+    assert.ok(event.proposer.equals(ownerA.publicKey));
+    assert.equal(event.numInstructions, 1);
+    assert.equal(event.truncated, false);
+    assert.deepEqual(Buffer.from(event.instructionsHash), hash);
+  });
+
+  it('verifyTransactionHash', async () => {
+    const msKey = await createMultisig([ownerA.publicKey], 1, 0);
+    const ix = program.instruction.changeMaxPending(bn(5, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+
+    let event;
+    const listener = program.addEventListener('TransactionProposed', e => event = e);
+    const txKey = await createTransaction(ix, msKey);
+    await sleep(1000);
+    await program.removeEventListener(listener);
+
+    const verify = (hash, multisig = msKey) => program.rpc.verifyTransactionHash(hash, {
+      accounts: {
+        multisig,
+        transaction: txKey,
+      },
+    });
+    await verify(event.instructionsHash);
+    try {
+      await verify(new Array(32).fill(0));
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /don't match the expected hash/);
+    }
+
+    // The transaction has to belong to the given multisig
+    const otherKey = await createMultisig([ownerA.publicKey], 1, 0);
+    try {
+      await verify(event.instructionsHash, otherKey);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /belongs to a different multisig/);
+    }
+  });
+
+  it('executeGroup', async () => {
+    const msKey = await createMultisig([ownerA.publicKey], 1, 0);
+    const maxPendingIx = program.instruction.changeMaxPending(bn(5, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const dataLenIx = program.instruction.changeMaxInstructionDataLen(100, {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const firstKey = await createTransaction(maxPendingIx, msKey);
+    const secondKey = await createTransaction(dataLenIx, msKey);
+    for (const [i, txKey] of [firstKey, secondKey].entries()) {
+      await program.rpc.setGroup(new BN(1), i, 2, {
+        accounts: {
+          signer: ownerA.publicKey,
+          multisig: msKey,
+          transaction: txKey,
+        },
+        signers: [ownerA],
+      });
+    }
+
+    // Grouped transactions can't execute on their own
+    try {
+      await executeStored(firstKey, msKey);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /must be executed together/);
+    }
+
+    const accounts = txKey => [
+      { pubkey: txKey, isSigner: false, isWritable: true },
+      { pubkey: msKey, isSigner: false, isWritable: true },
+      { pubkey: program.programId, isSigner: false, isWritable: false },
+    ];
+    await program.rpc.executeGroup(Buffer.from([2, 2]), {
+      accounts: {
+        signer: ownerA.publicKey,
+        multisig: msKey,
+      },
+      remainingAccounts: accounts(firstKey).concat(accounts(secondKey)),
+      signers: [ownerA],
+    });
+
+    const multisig = await program.account.multisig.fetch(msKey);
+    assert.equal(multisig.maxPending.toNumber(), 5);
+    assert.equal(multisig.maxInstructionDataLen, 100);
+    assert.equal(multisig.executedTransactions.toNumber(), 2);
+  });
+
+  it('executeGroup checks each member after the ones before it', async () => {
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey], 1, 1);
+    const thresholdIx = program.instruction.changeThreshold(bn(2, 0), false, {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const maxPendingIx = program.instruction.changeMaxPending(bn(5, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const firstKey = await createTransaction(thresholdIx, msKey);
+    const secondKey = await createTransaction(maxPendingIx, msKey);
+    for (const [i, txKey] of [firstKey, secondKey].entries()) {
+      await program.rpc.setGroup(new BN(1), i, 2, {
+        accounts: {
+          signer: ownerA.publicKey,
+          multisig: msKey,
+          transaction: txKey,
+        },
+        signers: [ownerA],
+      });
+    }
+    await waitForEta(secondKey);
+
+    // The second transaction only has ownerA's approval, short of the
+    // threshold the first one raises
+    const accounts = txKey => [
+      { pubkey: txKey, isSigner: false, isWritable: true },
+      { pubkey: msKey, isSigner: false, isWritable: true },
+      { pubkey: program.programId, isSigner: false, isWritable: false },
+    ];
+    try {
+      await program.rpc.executeGroup(Buffer.from([2, 2]), {
+        accounts: {
+          signer: ownerA.publicKey,
+          multisig: msKey,
+        },
+        remainingAccounts: accounts(firstKey).concat(accounts(secondKey)),
+        signers: [ownerA],
+      });
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /Not enough owners signed/);
+    }
+    const multisig = await program.account.multisig.fetch(msKey);
+    assert.equal(multisig.threshold.toNumber(), 1);
+  });
+
+  it('createMultisig non-canonical bump', async () => {
+    const base = anchor.web3.Keypair.generate();
+    const [, canonical] = await pda(['multisig', base.publicKey]);
+    const seeds = [Buffer.from('multisig'), base.publicKey.toBuffer()];
+
+    // Find another bump that derives a valid address
+    let msKey, msBump;
+    for (msBump = canonical - 1; msBump >= 0; msBump--) {
+      try {
+        msKey = await web3.PublicKey.createProgramAddress(
+          seeds.concat([Buffer.from([msBump])]), program.programId);
+        break;
+      } catch (err) {}
+    }
+    try {
+      await program.rpc.createMultisig([ownerA.publicKey], bn(1, 0), bn(0), msBump, {
+        accounts: {
+          signer: wallet.publicKey,
+          base: base.publicKey,
+          multisig: msKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        },
+        signers: [base],
+      });
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /not the canonical bump/);
+    }
+  });
+
+  it('threshold stays within the owner count', async () => {
+    const assertFails = async (promise, error) => {
+      try {
+        await promise;
+        throw new Error('did not throw');
+      } catch (err) {
+        assert.match(err.message, error);
+      }
+    };
+    const invalidThreshold = /less than or equal to the number of owners/;
+
+    // Creation
+    await assertFails(createMultisig([ownerA.publicKey], 0, 0), invalidThreshold);
+    await assertFails(createMultisig([ownerA.publicKey], 2, 0), invalidThreshold);
+    await assertFails(createMultisig([ownerA.publicKey, ownerA.publicKey], 1, 0), /Unique Owners/);
+
+    // Threshold changes
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey], 1, 1);
+    const multisigIx = (name, ...args) => program.instruction[name](...args, {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    await assertFails(createApproveExecute(multisigIx('changeThreshold', bn(0, 0), false), msKey), invalidThreshold);
+    await assertFails(createApproveExecute(multisigIx('reconfigure', bn(0, 0), null, null), msKey), invalidThreshold);
+
+    // Owner changes
+    await assertFails(createApproveExecute(multisigIx('setOwners', [], true), msKey), invalidThreshold);
+    await assertFails(createApproveExecute(multisigIx('setOwners', [ownerA.publicKey, ownerA.publicKey], false), msKey), /Unique Owners/);
+    await createApproveExecute(multisigIx('removeOwner', ownerB.publicKey), msKey);
+    await assertFails(createApproveExecute(multisigIx('removeOwner', ownerA.publicKey), msKey), invalidThreshold);
+
+    const multisig = await program.account.multisig.fetch(msKey);
+    assert.deepEqual(multisig.owners, [ownerA.publicKey]);
+    assert.equal(multisig.threshold.toNumber(), 1);
+  });
+
+  it('resign', async () => {
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey, ownerC.publicKey], 2, 0);
+    const resign = owner => program.rpc.resign({
+      accounts: {
+        signer: owner.publicKey,
+        multisig: msKey,
+      },
+      signers: [owner],
+    });
+
+    await resign(ownerC);
+    const multisig = await program.account.multisig.fetch(msKey);
+    assert.deepEqual(multisig.owners, [ownerA.publicKey, ownerB.publicKey]);
+    assert.equal(multisig.ownersSeqNo.toNumber(), 1);
+
+    // Two owners must remain for a threshold of 2
+    try {
+      await resign(ownerB);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /less than or equal to the number of owners/);
+    }
+    try {
+      await resign(ownerC);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /not part of this multisig/);
+    }
+  });
+
+  it('changeMinOwners', async () => {
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey], 1, 1);
+    const assertFails = async (promise, error) => {
+      try {
+        await promise;
+        throw new Error('did not throw');
+      } catch (err) {
+        assert.match(err.message, error);
+      }
+    };
+    const multisigIx = (name, ...args) => program.instruction[name](...args, {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const tooFewOwners = /fewer owners than its minimum/;
+    let multisig = await program.account.multisig.fetch(msKey);
+    assert.equal(multisig.minOwners, 1);
+    await assertFails(createApproveExecute(multisigIx('changeMinOwners', 3), msKey), tooFewOwners);
+    await createApproveExecute(multisigIx('changeMinOwners', 2), msKey);
+
+    await assertFails(createApproveExecute(multisigIx('removeOwner', ownerB.publicKey), msKey), tooFewOwners);
+    await assertFails(program.rpc.resign({
+      accounts: {
+        signer: ownerB.publicKey,
+        multisig: msKey,
+      },
+      signers: [ownerB],
+    }), tooFewOwners);
+    multisig = await program.account.multisig.fetch(msKey);
+    assert.deepEqual(multisig.owners, [ownerA.publicKey, ownerB.publicKey]);
+  });
+
+  it('vault pays for created accounts', async () => {
+    const connection = program.provider.connection;
+    const msKey = await createMultisig([ownerA.publicKey], 1, 0);
+    const [vault] = await pda(['vault', msKey]);
+    await airdrop(vault);
+
+    const seed = 'created';
+    const newKey = await web3.PublicKey.createWithSeed(vault, seed, web3.SystemProgram.programId);
+    const lamports = await connection.getMinimumBalanceForRentExemption(0);
+    const ix = web3.SystemProgram.createAccountWithSeed({
+      fromPubkey: vault,
+      basePubkey: vault,
+      newAccountPubkey: newKey,
+      seed,
+      lamports,
+      space: 0,
+      programId: web3.SystemProgram.programId,
+    });
+    const txKey = await createTransaction(ix, msKey);
+    await executeStored(txKey, msKey);
+
+    const created = await connection.getAccountInfo(newKey);
+    assert.equal(created.lamports, lamports);
+    assert.equal(await connection.getBalance(vault), LAMPORTS_PER_SOL - lamports);
+  });
+
+  it('executeTransaction with sysvar accounts', async () => {
+    const connection = program.provider.connection;
+    const msKey = await createMultisig([ownerA.publicKey], 1, 0);
+    const [vault] = await pda(['vault', msKey]);
+    await airdrop(vault);
+
+    // Initialize reads the Rent sysvar, authorize the Clock one
+    const seed = 'stake';
+    const stakeKey = await web3.PublicKey.createWithSeed(vault, seed, web3.StakeProgram.programId);
+    const newAuthority = web3.Keypair.generate().publicKey;
+    const ixs = [
+      ...web3.StakeProgram.createAccountWithSeed({
+        fromPubkey: vault,
+        stakePubkey: stakeKey,
+        basePubkey: vault,
+        seed,
+        authorized: new web3.Authorized(vault, vault),
+        lamports: await connection.getMinimumBalanceForRentExemption(web3.StakeProgram.space),
+      }).instructions,
+      ...web3.StakeProgram.authorize({
+        stakePubkey: stakeKey,
+        authorizedPubkey: vault,
+        newAuthorizedPubkey: newAuthority,
+        stakeAuthorizationType: web3.StakeAuthorizationLayout.Withdrawer,
+      }).instructions,
+    ];
+    assert.ok(ixs[2].keys.some(k => k.pubkey.equals(web3.SYSVAR_CLOCK_PUBKEY)));
+    const txKey = await createTransaction(ixs, msKey);
+    await executeStored(txKey, msKey);
+
+    const stake = await connection.getParsedAccountInfo(stakeKey);
+    const meta = (stake.value.data as ParsedAccountData).parsed.info.meta;
+    assert.equal(meta.authorized.withdrawer, newAuthority.toString());
+
+    // Sysvars can't be proposed as writable
+    const writableSysvar = web3.SystemProgram.transfer({
+      fromPubkey: vault,
+      toPubkey: newAuthority,
+      lamports: 1,
+    });
+    writableSysvar.keys.push({ pubkey: web3.SYSVAR_CLOCK_PUBKEY, isSigner: false, isWritable: true });
+    try {
+      await createTransaction(writableSysvar, msKey);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /Sysvars can only be passed read-only/);
+    }
+  });
+
+  it('setValueTiers', async () => {
+    const connection = program.provider.connection;
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey, ownerC.publicKey], 1, 1);
+    const [vault] = await pda(['vault', msKey]);
+    await airdrop(vault, 2 * LAMPORTS_PER_SOL);
+    await createApproveExecute(program.instruction.setValueTiers([
+      { minLamports: new BN(LAMPORTS_PER_SOL), threshold: new BN(2) },
+    ], {
+      accounts: {
+        multisig: msKey
+      },
+    }), msKey);
+    const to = web3.Keypair.generate().publicKey;
+    const transfer = lamports => web3.SystemProgram.transfer({
+      fromPubkey: vault,
+      toPubkey: to,
+      lamports,
+    });
+
+    // Below the tier the threshold applies
+    const smallKey = await createTransaction(transfer(LAMPORTS_PER_SOL / 10), msKey);
+    await waitForEta(smallKey);
+    await executeStored(smallKey, msKey);
+
+    // A large transfer needs the tier's approvals
+    const largeKey = await createTransaction(transfer(LAMPORTS_PER_SOL), msKey);
+    await waitForEta(largeKey);
+    try {
+      await executeStored(largeKey, msKey);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /Not enough owners signed/);
+    }
+    await approve(largeKey, msKey, ownerB);
+    await executeStored(largeKey, msKey);
+    assert.equal(await connection.getBalance(to), 1.1 * LAMPORTS_PER_SOL);
+  });
+
+  it('autoExecute', async () => {
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey], 2, 0);
+    const ix = program.instruction.changeMaxPending(bn(7, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    let multisig = await program.account.multisig.fetch(msKey);
+    const [txKey, txBump] = await pda(['transaction', msKey, multisig.numTransactions.toNumber()]);
+    await program.rpc.createTransaction([ix], txBump, false, Buffer.from([]), new BN(0), noTitle, '', true, {
+      accounts: {
+        signer: ownerA.publicKey,
+        multisig: msKey,
+        transaction: txKey,
+        systemProgram: web3.SystemProgram.programId,
+      },
+      signers: [ownerA],
+    });
+
+    // The final approval executes the transaction
+    await program.rpc.approve({
+      accounts: {
+        signer: ownerB.publicKey,
+        multisig: msKey,
+        transaction: txKey,
+      },
+      remainingAccounts: ix.keys
+        .map(k => Object.assign(k, { isSigner: false }))
+        .concat([{ pubkey: program.programId, isSigner: false, isWritable: false }]),
+      signers: [ownerB],
+    });
+    const tx = await program.account.transaction.fetch(txKey);
+    assert.notEqual(tx.executedAt.toNumber(), 0);
+    multisig = await program.account.multisig.fetch(msKey);
+    assert.equal(multisig.maxPending.toNumber(), 7);
+  });
+
+  it('inactivityFailsafe', async () => {
+    const connection = program.provider.connection;
+    const msKey = await createMultisig([ownerA.publicKey], 1, 1);
+    const [vault] = await pda(['vault', msKey]);
+    const safe = web3.Keypair.generate().publicKey;
+    await createApproveExecute(program.instruction.setFailsafe(ownerC.publicKey, bn(3, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    }), msKey);
+    await airdrop(msKey);
+    await airdrop(vault);
+
+    const failsafe = () => program.rpc.inactivityFailsafe({
+      accounts: {
+        signer: ownerC.publicKey,
+        multisig: msKey,
+        vault,
+        to: safe,
+        systemProgram: web3.SystemProgram.programId,
+      },
+      signers: [ownerC],
+    });
+    try {
+      await failsafe();
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /still active/);
+    }
+
+    await sleep(4000);
+    let event;
+    const listener = program.addEventListener('InactivityFailsafeTriggered', e => event = e);
+    await failsafe();
+    await sleep(1000);
+    await program.removeEventListener(listener);
+
+    assert.equal(await connection.getBalance(safe), 2 * LAMPORTS_PER_SOL);
+    assert.equal(await connection.getBalance(vault), 0);
+    assert.ok(event.beneficiary.equals(ownerC.publicKey));
+    assert.equal(event.amount.toNumber(), 2 * LAMPORTS_PER_SOL);
+  });
+
+  it('changeRequireDistinctSlots', async () => {
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey], 2, 1);
+    const [setupKey] = await pda(['transaction', msKey, 0]);
+    const setupIx = program.instruction.changeRequireDistinctSlots(true, {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    await createTransaction(setupIx, msKey);
+    await approve(setupKey, msKey, ownerB);
+    await waitForEta(setupKey);
+    await executeStored(setupKey, msKey);
+
+    // Propose and approve in a single transaction, so in the same slot
+    const ix = program.instruction.changeMaxPending(bn(5, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const [txKey, txBump] = await pda(['transaction', msKey, 1]);
+    const bundle = new web3.Transaction().add(
+      program.instruction.createTransaction([ix], txBump, false, Buffer.from([]), new BN(0), noTitle, '', false, {
+        accounts: {
+          signer: ownerA.publicKey,
+          multisig: msKey,
+          transaction: txKey,
+          systemProgram: web3.SystemProgram.programId,
+        },
+      }),
+      program.instruction.approve({
+        accounts: {
+          signer: ownerB.publicKey,
+          multisig: msKey,
+          transaction: txKey,
+        },
+      }),
+    );
+    await program.provider.send(bundle, [ownerA, ownerB]);
+    await waitForEta(txKey);
+    try {
+      await executeStored(txKey, msKey);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /Not enough owners signed/);
+    }
+
+    // A later approval lands in another slot
+    await sleep(1000);
+    await approve(txKey, msKey, ownerB);
+    await executeStored(txKey, msKey);
+    const multisig = await program.account.multisig.fetch(msKey);
+    assert.equal(multisig.maxPending.toNumber(), 5);
+  });
+
+  it('setExecutorAllowlist', async () => {
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey], 2, 1);
+    const [setupKey] = await pda(['transaction', msKey, 0]);
+    const setupIx = program.instruction.setExecutorAllowlist([ownerA.publicKey], {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    await createTransaction(setupIx, msKey);
+    await approve(setupKey, msKey, ownerB);
+    await waitForEta(setupKey);
+    await executeStored(setupKey, msKey);
+
+    // ownerB can propose and approve, but only ownerA can execute
+    const ix = program.instruction.changeMaxPending(bn(5, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const txKey = await createTransaction(ix, msKey, ownerB);
+    await approve(txKey, msKey, ownerA);
+    await waitForEta(txKey);
+    try {
+      await executeStored(txKey, msKey, ownerB);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /not allowed to execute/);
+    }
+    await executeStored(txKey, msKey, ownerA);
+    const multisig = await program.account.multisig.fetch(msKey);
+    assert.equal(multisig.maxPending.toNumber(), 5);
+  });
+
+  it('setBalanceAssertion', async () => {
+    const connection = program.provider.connection;
+    const msKey = await createMultisig([ownerA.publicKey], 1, 0);
+    const watched = web3.Keypair.generate().publicKey;
+    await airdrop(watched);
+    const ix = program.instruction.changeMaxPending(bn(5, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const txKey = await createTransaction(ix, msKey);
+    await program.rpc.setBalanceAssertion({
+      account: watched,
+      tokenAccount: false,
+      minBalance: new BN(LAMPORTS_PER_SOL),
+      maxBalance: new BN(LAMPORTS_PER_SOL),
+    }, {
+      accounts: {
+        signer: ownerA.publicKey,
+        multisig: msKey,
+        transaction: txKey,
+      },
+      signers: [ownerA],
+    });
+
+    // The balance drifted out of the expected range
+    await airdrop(watched, 1000);
+    try {
+      await executeStored(txKey, msKey);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /outside the expected range/);
+    }
+    const tx = await program.account.transaction.fetch(txKey);
+    assert.equal(tx.executedAt.toNumber(), 0);
+    assert.equal(await connection.getBalance(watched), LAMPORTS_PER_SOL + 1000);
+  });
+
+  it('executeTransaction is atomic', async () => {
+    const msKey = await createMultisig([ownerA.publicKey], 1, 0);
+    const [vault] = await pda(['vault', msKey]);
+    const maxPendingIx = program.instruction.changeMaxPending(bn(5, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    // The vault is unfunded, so the last instruction fails
+    const transferIx = web3.SystemProgram.transfer({
+      fromPubkey: vault,
+      toPubkey: ownerA.publicKey,
+      lamports: 1,
+    });
+    const txKey = await createTransaction([maxPendingIx, transferIx], msKey);
+    try {
+      await executeStored(txKey, msKey);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.ok(!err.message.includes('did not throw'));
+    }
+
+    // The first instruction's effects and the execution were rolled back
+    const multisig = await program.account.multisig.fetch(msKey);
+    assert.equal(multisig.maxPending.toNumber(), 0);
+    assert.equal(multisig.executedTransactions.toNumber(), 0);
+    assert.equal(multisig.pendingTransactions.toNumber(), 1);
+    const tx = await program.account.transaction.fetch(txKey);
+    assert.equal(tx.executedAt.toNumber(), 0);
+    // Still Pubkey::default(), which is the system program id
+    assert.equal(tx.executor.toString(), web3.SystemProgram.programId.toString());
+  });
+
+  it('executeTransaction keeps the multisig rent exempt', async () => {
+    const connection = program.provider.connection;
+    const msKey = await createMultisig([ownerA.publicKey], 1, 0);
+    await airdrop(msKey);
+    const balance = await connection.getBalance(msKey);
+    const ix = web3.SystemProgram.transfer({
+      fromPubkey: msKey,
+      toPubkey: ownerA.publicKey,
+      lamports: balance,
+    });
+    const txKey = await createTransaction(ix, msKey);
+    try {
+      await executeStored(txKey, msKey);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.ok(!err.message.includes('did not throw'));
+    }
+    assert.equal(await connection.getBalance(msKey), balance);
+    const tx = await program.account.transaction.fetch(txKey);
+    assert.equal(tx.executedAt.toNumber(), 0);
+  });
+
+  it('executeTransaction rejects self references', async () => {
+    const msKey = await createMultisig([ownerA.publicKey], 1, 0);
+    const [vault] = await pda(['vault', msKey]);
+    await airdrop(vault);
+    const multisig = await program.account.multisig.fetch(msKey);
+    const [nextKey] = await pda(['transaction', msKey, multisig.numTransactions.toNumber()]);
+
+    // Writable reference to the very transaction account holding it
+    const ix = web3.SystemProgram.transfer({
+      fromPubkey: vault,
+      toPubkey: nextKey,
+      lamports: 1,
+    });
+    const txKey = await createTransaction(ix, msKey);
+    assert.ok(txKey.equals(nextKey));
+    try {
+      await executeStored(txKey, msKey);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /can't write to the transaction being executed/);
+    }
+  });
+
+  it('setSeedAccounts', async () => {
+    const msKey = await createMultisig([ownerA.publicKey], 1, 0);
+    // The multisig account is left as a placeholder in the proposal
+    const ix = program.instruction.changeMaxPending(bn(5, 0), {
+      accounts: {
+        multisig: web3.SystemProgram.programId
+      },
+    });
+    const txKey = await createTransaction(ix, msKey);
+
+    // Resolve it from the base stored in the multisig account on execution
+    await program.rpc.setSeedAccounts([{
+      instructionIndex: 0,
+      keyIndex: 0,
+      programId: program.programId,
+      seeds: [
+        { bytes: { bytes: Buffer.from('multisig') } },
+        { accountData: { account: msKey, offset: 8, length: 32 } },
+      ],
+    }], {
+      accounts: {
+        signer: ownerA.publicKey,
+        multisig: msKey,
+        transaction: txKey,
+        systemProgram: web3.SystemProgram.programId,
+      },
+      signers: [ownerA],
+    });
+    await program.rpc.executeTransaction(false, {
+      accounts: {
+        signer: ownerA.publicKey,
+        multisig: msKey,
+        transaction: txKey,
+      },
+      remainingAccounts: [
+        { pubkey: msKey, isSigner: false, isWritable: true },
+        { pubkey: program.programId, isSigner: false, isWritable: false },
+      ],
+      signers: [ownerA],
+    });
+
+    const multisig = await program.account.multisig.fetch(msKey);
+    assert.equal(multisig.maxPending.toNumber(), 5);
+  });
+
+  it('importTransaction', async () => {
+    const migrator = web3.Keypair.generate();
+    await airdrop(migrator.publicKey);
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey], 2, 0, migrator.publicKey);
+    const ix = program.instruction.changeMaxPending(bn(5, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const importTx = async (signers = [true, false]) => {
+      const multisig = await program.account.multisig.fetch(msKey);
+      const [txKey, txBump] = await pda(['transaction', msKey, multisig.numTransactions.toNumber()]);
+      await program.rpc.importTransaction([ix], txBump, signers, bn(0, 0), {
+        accounts: {
+          signer: migrator.publicKey,
+          multisig: msKey,
+          transaction: txKey,
+          systemProgram: web3.SystemProgram.programId,
+        },
+        signers: [migrator],
+      });
+      return txKey;
+    };
+
+    // The migration authority alone can't bring in an executable transaction
+    try {
+      await importTx([true, true]);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /Imported approvals must stay below the threshold/);
+    }
+
+    // Import a proposal ownerA already approved, then ownerB approves
+    const txKey = await importTx();
+    let tx = await program.account.transaction.fetch(txKey);
+    assert.deepEqual(tx.signers, [true, false]);
+
+    // No more imports once the migration is complete
+    await program.rpc.completeMigration({
+      accounts: {
+        signer: migrator.publicKey,
+        multisig: msKey,
+      },
+      signers: [migrator],
+    });
+    try {
+      await importTx();
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /Migration is complete/);
+    }
+
+    await approve(txKey, msKey, ownerB);
+    await executeStored(txKey, msKey);
+    const multisig = await program.account.multisig.fetch(msKey);
+    assert.equal(multisig.maxPending.toNumber(), 5);
+  });
+
+  it('changeEnforceSeparation', async () => {
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey], 1, 1);
+    await createApproveExecute(program.instruction.changeEnforceSeparation(true, {
+      accounts: {
+        multisig: msKey
+      },
+    }), msKey);
+
+    const ix = program.instruction.changeMaxPending(bn(5, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const txKey = await createTransaction(ix, msKey, ownerA);
+    await waitForEta(txKey);
+    try {
+      await executeStored(txKey, msKey, ownerA);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /can't execute their own transaction/);
+    }
+    await executeStored(txKey, msKey, ownerB);
+    const multisig = await program.account.multisig.fetch(msKey);
+    assert.equal(multisig.maxPending.toNumber(), 5);
+  });
+
+  it('setDependency', async () => {
+    const msKey = await createMultisig([ownerA.publicKey], 1, 0);
+    const grantIx = program.instruction.changeMaxPending(bn(5, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const useIx = program.instruction.changeMaxInstructionDataLen(100, {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const aKey = await createTransaction(grantIx, msKey);
+    const bKey = await createTransaction(useIx, msKey);
+    await program.rpc.setDependency(aKey, {
+      accounts: {
+        signer: ownerA.publicKey,
+        multisig: msKey,
+        transaction: bKey,
+      },
+      signers: [ownerA],
+    });
+
+    try {
+      await executeStored(bKey, msKey);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /depends on hasn't executed/);
+    }
+    await executeStored(aKey, msKey);
+    await executeStored(bKey, msKey);
+    const multisig = await program.account.multisig.fetch(msKey);
+    assert.equal(multisig.maxInstructionDataLen, 100);
+  });
+
+  it('createTransaction action hints', async () => {
+    const msKey = await createMultisig([ownerA.publicKey], 1, 0);
+    const ixs = [bn(5, 0), bn(6, 0)].map(n => program.instruction.changeMaxPending(n, {
+      accounts: {
+        multisig: msKey
+      },
+    }));
+    const [txKey, txBump] = await pda(['transaction', msKey, 0]);
+    const create = hints => program.rpc.createTransaction(ixs, txBump, false, Buffer.from(hints), new BN(0), noTitle, '', false, {
+      accounts: {
+        signer: ownerA.publicKey,
+        multisig: msKey,
+        transaction: txKey,
+        systemProgram: web3.SystemProgram.programId,
+      },
+      signers: [ownerA],
+    });
+
+    // Hints must match the instructions
+    try {
+      await create([1]);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /one action hint per instruction/);
+    }
+    await create([1, 2]);
+    const tx = await program.account.transaction.fetch(txKey);
+    assert.deepEqual([...tx.actionHints], [1, 2]);
+  });
+
+  it('approve mismatched multisig', async () => {
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey], 2, 0);
+    const otherKey = await createMultisig([ownerA.publicKey, ownerB.publicKey], 2, 0);
+    const ix = program.instruction.changeMaxPending(bn(5, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const txKey = await createTransaction(ix, msKey);
+    try {
+      await approve(txKey, otherKey, ownerB);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /belongs to a different multisig/);
+    }
+    try {
+      await executeStored(txKey, otherKey);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /belongs to a different multisig/);
+    }
+  });
+
+  it('changeApprovalReward', async () => {
+    const connection = program.provider.connection;
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey], 1, 5);
+    await createApproveExecute(program.instruction.changeApprovalReward(new BN(1000), {
+      accounts: {
+        multisig: msKey
+      },
+    }), msKey);
+    await airdrop(msKey);
+
+    const ix = program.instruction.changeMaxPending(bn(5, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const txKey = await createTransaction(ix, msKey);
+    const before = await connection.getBalance(ownerB.publicKey);
+    await approve(txKey, msKey, ownerB);
+    assert.equal(await connection.getBalance(ownerB.publicKey), before + 1000);
+
+    // Approving again doesn't pay twice
+    await approve(txKey, msKey, ownerB);
+    assert.equal(await connection.getBalance(ownerB.publicKey), before + 1000);
+
+    // Nor does approving again after revoking
+    const revoke = () => program.rpc.revokeApproval({
+      accounts: {
+        signer: ownerB.publicKey,
+        multisig: msKey,
+        transaction: txKey,
+      },
+      signers: [ownerB],
+    });
+    for (let i = 0; i < 2; i++) {
+      await revoke();
+      await approve(txKey, msKey, ownerB);
+      // Identical transactions in the same blockhash would be deduplicated
+      await sleep(1000);
+    }
+    assert.equal(await connection.getBalance(ownerB.publicKey), before + 1000);
+    const multisig = await program.account.multisig.fetch(msKey);
+    assert.equal(multisig.totalIncentivesPaid.toNumber(), 1000);
+
+    // Nor does approving after the eta
+    const lateKey = await createTransaction(ix, msKey);
+    await waitForEta(lateKey);
+    await approve(lateKey, msKey, ownerB);
+    assert.equal(await connection.getBalance(ownerB.publicKey), before + 1000);
+  });
+
+  it('changeMaxTotalIncentives', async () => {
+    const connection = program.provider.connection;
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey], 1, 5);
+    await createApproveExecute(program.instruction.changeApprovalReward(new BN(1000), {
+      accounts: {
+        multisig: msKey
+      },
+    }), msKey);
+    await createApproveExecute(program.instruction.changeMaxTotalIncentives(new BN(2000), {
+      accounts: {
+        multisig: msKey
+      },
+    }), msKey);
+    await airdrop(msKey);
+
+    const ix = program.instruction.changeMaxPending(bn(5, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const before = await connection.getBalance(ownerB.publicKey);
+    for (let i = 0; i < 3; i++) {
+      const txKey = await createTransaction(ix, msKey);
+      await approve(txKey, msKey, ownerB);
+    }
+
+    // The third approval still counts but isn't paid
+    assert.equal(await connection.getBalance(ownerB.publicKey), before + 2000);
+    const multisig = await program.account.multisig.fetch(msKey);
+    assert.equal(multisig.totalIncentivesPaid.toNumber(), 2000);
+  });
+
+  it('reconfigure', async () => {
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey, ownerC.publicKey], 1, 1);
+    const reconfigure = (threshold, delay, gracePeriod) => program.instruction.reconfigure(threshold, delay, gracePeriod, {
+      accounts: {
+        multisig: msKey
+      },
+    });
+
+    // Any invalid value rejects the whole change
+    for (const [ix, error] of [
+      [reconfigure(bn(2, 0), bn(31 * 24 * 3600, 0), null), /between 0 and 30 days/],
+      [reconfigure(bn(4, 0), bn(2, 0), null), /less than or equal to the number of owners/],
+      [reconfigure(bn(2, 0), null, bn(0, 0)), /Grace period must be positive/],
+    ]) {
+      try {
+        await createApproveExecute(ix, msKey);
+        throw new Error('did not throw');
+      } catch (err) {
+        assert.match(err.message, error);
+      }
+    }
+    let multisig = await program.account.multisig.fetch(msKey);
+    assert.equal(multisig.threshold.toNumber(), 1);
+    assert.equal(multisig.configVersion.toNumber(), 0);
+
+    await createApproveExecute(reconfigure(bn(2, 0), bn(2, 0), bn(3600, 0)), msKey);
+    multisig = await program.account.multisig.fetch(msKey);
+    assert.equal(multisig.threshold.toNumber(), 2);
+    assert.equal(multisig.delay.toNumber(), 2);
+    assert.equal(multisig.gracePeriod.toNumber(), 3600);
+    assert.equal(multisig.configVersion.toNumber(), 1);
+  });
+
+  it('createTransaction min approvals', async () => {
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey, ownerC.publicKey], 1, 0);
+    const ix = program.instruction.changeMaxPending(bn(5, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    await createApproveExecute(ix, msKey);
+
+    const txKey = await createTransaction(ix, msKey, ownerA, 2);
+    try {
+      await executeStored(txKey, msKey);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /Not enough owners signed/);
+    }
+    await approve(txKey, msKey, ownerB);
+    await executeStored(txKey, msKey);
+
+    try {
+      await createTransaction(ix, msKey, ownerA, 4);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /less than or equal to the number of owners/);
+    }
+  });
+
+  it('ownerActivity', async () => {
+    // A delay, so the threshold proposal below is accepted
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey], 2, 1);
+    const metadata = {};
+    for (const owner of [ownerA, ownerB]) {
+      const [metadataKey, bump] = await pda(['owner', msKey, owner.publicKey]);
+      await program.rpc.heartbeat(bump, {
+        accounts: {
+          signer: owner.publicKey,
+          multisig: msKey,
+          ownerMetadata: metadataKey,
+          systemProgram: web3.SystemProgram.programId,
+        },
+        signers: [owner],
+      });
+      metadata[owner.publicKey.toString()] = { pubkey: metadataKey, isSigner: false, isWritable: true };
+    }
+    const metadataOf = owner => metadata[owner.publicKey.toString()];
+    const activity = async owner => {
+      const data = await view('ownerActivity', { accounts: { ownerMetadata: metadataOf(owner).pubkey } });
+      assert.ok(new web3.PublicKey(data.slice(0, 32)).equals(owner.publicKey));
+      return [32, 40, 48].map(offset => Number(data.readBigUInt64LE(offset)));
+    };
+
+    const ix = program.instruction.changeMaxPending(bn(7, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    let multisig = await program.account.multisig.fetch(msKey);
+    const [txKey, txBump] = await pda(['transaction', msKey, multisig.numTransactions.toNumber()]);
+    await program.rpc.createTransaction([ix], txBump, false, Buffer.from([]), new BN(0), noTitle, '', false, {
+      accounts: {
+        signer: ownerA.publicKey,
+        multisig: msKey,
+        transaction: txKey,
+        systemProgram: web3.SystemProgram.programId,
+      },
+      remainingAccounts: [metadataOf(ownerA)],
+      signers: [ownerA],
+    });
+    await program.rpc.approve({
+      accounts: {
+        signer: ownerB.publicKey,
+        multisig: msKey,
+        transaction: txKey,
+      },
+      remainingAccounts: [metadataOf(ownerB)],
+      signers: [ownerB],
+    });
+    await waitForEta(txKey);
+    await program.rpc.executeTransaction(false, {
+      accounts: {
+        signer: ownerA.publicKey,
+        multisig: msKey,
+        transaction: txKey,
+      },
+      remainingAccounts: [
+        { pubkey: msKey, isSigner: false, isWritable: true },
+        { pubkey: program.programId, isSigner: false, isWritable: false },
+        metadataOf(ownerA),
+      ],
+      signers: [ownerA],
+    });
+    multisig = await program.account.multisig.fetch(msKey);
+    assert.equal(multisig.maxPending.toNumber(), 7);
+
+    assert.deepEqual(await activity(ownerA), [1, 0, 1]);
+    assert.deepEqual(await activity(ownerB), [0, 1, 0]);
+
+    // Without its metadata account an action isn't counted
+    await createTransaction(ix, msKey, ownerB);
+    assert.deepEqual(await activity(ownerB), [0, 1, 0]);
+
+    // Approving single instructions counts once, however often it's repeated
+    const partialTxKey = await createTransaction([ix, ix], msKey);
+    for (const mask of [1, 3]) {
+      await program.rpc.approveInstructions(mask, {
+        accounts: {
+          signer: ownerB.publicKey,
+          multisig: msKey,
+          transaction: partialTxKey,
+        },
+        remainingAccounts: [metadataOf(ownerB)],
+        signers: [ownerB],
+      });
+    }
+    assert.deepEqual(await activity(ownerB), [0, 2, 0]);
+
+    // Built-in proposals count as proposals
+    multisig = await program.account.multisig.fetch(msKey);
+    const [thresholdTxKey, thresholdTxBump] = await pda(['transaction', msKey, multisig.numTransactions.toNumber()]);
+    await program.rpc.proposeChangeThreshold(thresholdTxBump, bn(1, 0), {
+      accounts: {
+        signer: ownerA.publicKey,
+        multisig: msKey,
+        transaction: thresholdTxKey,
+        systemProgram: web3.SystemProgram.programId,
+      },
+      remainingAccounts: [metadataOf(ownerA)],
+      signers: [ownerA],
+    });
+    assert.deepEqual(await activity(ownerA), [2, 0, 1]);
+  });
+
+  it('heartbeat', async () => {
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey], 2, 0);
+    const [metadataKey, bump] = await pda(['owner', msKey, ownerB.publicKey]);
+    const heartbeat = owner => program.rpc.heartbeat(bump, {
+      accounts: {
+        signer: owner.publicKey,
+        multisig: msKey,
+        ownerMetadata: metadataKey,
+        systemProgram: web3.SystemProgram.programId,
+      },
+      signers: [owner],
+    });
+
+    await heartbeat(ownerB);
+    let metadata = await program.account.ownerMetadata.fetch(metadataKey);
+    assert.equal(metadata.owner.toString(), ownerB.publicKey.toString());
+    const firstSeen = metadata.lastSeen.toNumber();
+    assert.ok(Math.abs(firstSeen - Date.now() / 1000) < 60);
+
+    await sleep(2000);
+    await heartbeat(ownerB);
+    metadata = await program.account.ownerMetadata.fetch(metadataKey);
+    assert.ok(metadata.lastSeen.toNumber() > firstSeen);
+
+    try {
+      await heartbeat(ownerC);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.ok(!err.message.includes('did not throw'));
+    }
+  });
+
+  it('createTransaction title and memo', async () => {
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey], 2, 0);
+    const ix = program.instruction.changeMaxPending(bn(5, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const title = Buffer.alloc(48);
+    title.write('Raise max pending');
+    const memo = 'Lets us queue the quarterly payouts at once, see the forum thread.';
+    const create = async title => {
+      const multisig = await program.account.multisig.fetch(msKey);
+      const [txKey, txBump] = await pda(['transaction', msKey, multisig.numTransactions.toNumber()]);
+      await program.rpc.createTransaction([ix], txBump, false, Buffer.from([]), new BN(0), titleOf(title), memo, false, {
+        accounts: {
+          signer: ownerA.publicKey,
+          multisig: msKey,
+          transaction: txKey,
+          systemProgram: web3.SystemProgram.programId,
+        },
+        signers: [ownerA],
+      });
+      return txKey;
+    };
+
+    const txKey = await create(title);
+    const tx = await program.account.transaction.fetch(txKey);
+    const stored = Buffer.concat([Buffer.from(tx.title.head), Buffer.from(tx.title.tail)]);
+    assert.equal(stored.length, 48);
+    assert.equal(stored.toString().replace(/\0+$/, ''), 'Raise max pending');
+    assert.equal(tx.memo, memo);
+
+    // Bytes after the padding starts must stay zero
+    const invalid = Buffer.from(title);
+    invalid[47] = 1;
+    try {
+      await create(invalid);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /zero padded UTF-8/);
+    }
+  });
+
+  it('canExecute', async () => {
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey, ownerC.publicKey], 3, 0);
+    const ix = program.instruction.changeMaxPending(bn(5, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const txKey = await createTransaction(ix, msKey);
+    const canExecute = async owner => {
+      const data = await view('canExecute', owner, {
+        accounts: {
+          multisig: msKey,
+          transaction: txKey,
+        },
+      });
+      return data[0] === 1;
+    };
+
+    // Only ownerA approved, one more approval isn't enough
+    assert.equal(await canExecute(ownerB.publicKey), false);
+    await approve(txKey, msKey, ownerB);
+
+    // ownerC's approval is the one that reaches the threshold
+    assert.equal(await canExecute(ownerC.publicKey), true);
+    assert.equal(await canExecute(ownerA.publicKey), false);
+    assert.equal(await canExecute(ownerB.publicKey), false);
+    assert.equal(await canExecute(wallet.publicKey), false);
+
+    await approve(txKey, msKey, ownerC);
+    await executeStored(txKey, msKey);
+    assert.equal(await canExecute(ownerC.publicKey), false);
+  });
+
+  it('timeUntilExecutable', async () => {
+    const msKey = await createMultisig([ownerA.publicKey], 1, 5);
+    const ix = program.instruction.changeMaxPending(bn(5, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const txKey = await createTransaction(ix, msKey);
+    const remaining = async name => {
+      const data = await view(name, {
+        accounts: {
+          multisig: msKey,
+          transaction: txKey,
+        },
+      });
+      return new BN(data, 'le').toNumber();
+    };
+
+    const executable = await remaining('timeUntilExecutable');
+    const expiry = await remaining('timeUntilExpiry');
+    assert.ok(executable > 0);
+    assert.ok(executable <= 5);
+    assert.ok(expiry >= 14 * 24 * 3600);
+
+    await sleep(2000);
+    assert.ok(await remaining('timeUntilExecutable') < executable);
+    assert.ok(await remaining('timeUntilExpiry') < expiry);
+
+    // Never negative once the eta passed
+    await waitForEta(txKey);
+    assert.equal(await remaining('timeUntilExecutable'), 0);
+
+    // Not for a transaction of another multisig
+    const otherKey = await createMultisig([ownerA.publicKey], 1, 0);
+    for (const name of ['timeUntilExecutable', 'timeUntilExpiry']) {
+      try {
+        await program.rpc[name]({
+          accounts: {
+            multisig: otherKey,
+            transaction: txKey,
+          },
+        });
+        throw new Error('did not throw');
+      } catch (err) {
+        assert.match(err.message, /belongs to a different multisig/);
+      }
+    }
+  });
+
+  it('changeMinDistinctRecentProposers', async () => {
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey, ownerC.publicKey], 1, 1);
+    await createApproveExecute(program.instruction.changeMinDistinctRecentProposers(2, {
+      accounts: {
+        multisig: msKey
+      },
+    }), msKey);
+
+    const ix = program.instruction.changeMaxPending(bn(5, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    await createTransaction(ix, msKey, ownerA);
+    try {
+      await createTransaction(ix, msKey, ownerA);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /proposed too recently/);
+    }
+    // validateProposal agrees
+    const validation = await view('validateProposal', [ix], Buffer.from([]), new BN(0), {
+      accounts: {
+        signer: ownerA.publicKey,
+        multisig: msKey,
+      },
+      signers: [ownerA],
+    });
+    assert.equal(validation[0], 1);
+    assert.equal(validation.readUInt32LE(1), program.idl.errors.find(e => e.name === 'ProposerNotDistinct').code);
+    await createTransaction(ix, msKey, ownerB);
+    await createTransaction(ix, msKey, ownerA);
+
+    const txKey = await createTransaction(program.instruction.changeMinDistinctRecentProposers(4, {
+      accounts: {
+        multisig: msKey
+      },
+    }), msKey, ownerC);
+    await waitForEta(txKey);
+    try {
+      await executeStored(txKey, msKey);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /diversity can't exceed the number of owners/);
+    }
+  });
+
+  it('reserved fields are zero and can be carved into new fields', async () => {
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey], 2, 0);
+    const ix = program.instruction.changeMaxPending(bn(5, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const txKey = await createTransaction(ix, msKey);
+
+    for (const [name, address] of [['Multisig', msKey], ['Transaction', txKey]]) {
+      const { data } = await program.provider.connection.getAccountInfo(address);
+      const account = program.coder.accounts.decode(name, data);
+      const encoded = await program.coder.accounts.encode(name, account);
+
+      // Round trips byte for byte, reserved space is all zero
+      assert.ok(data.slice(0, encoded.length).equals(encoded));
+      const idl = JSON.parse(JSON.stringify(program.idl));
+      const fields = idl.accounts.find(a => a.name === name).type.fields;
+      const reservedIndex = fields.findIndex(f => f.name.replace(/^_/, '') === 'reserved');
+      const reserved = fields[reservedIndex];
+      assert.ok(account[reserved.name].every(n => n.isZero()));
+      if (name === 'Multisig') {
+        // Growable fields follow the reserved space, so the fixed-size ones
+        // keep their offsets
+        const ownersIndex = fields.findIndex(f => f.name === 'owners');
+        assert.ok(fields.slice(ownersIndex + 1, reservedIndex).every(f => !f.type.vec));
+        assert.equal(account.layoutVersion, 1);
+      } else {
+        // The layout version takes the first byte of the legacy reserved space
+        const executedAtIndex = fields.findIndex(f => f.name === 'executedAt');
+        assert.equal(fields[executedAtIndex + 1].name, 'layoutVersion');
+        assert.equal(account.layoutVersion, 1);
+      }
+
+      // A later version taking a u64 out of reserved space still decodes
+      // accounts written by this one, with the new field zeroed
+      reserved.type.array[1] -= 1;
+      fields.splice(reservedIndex, 0, { name: 'futureField', type: 'u64' });
+      const upgraded = new anchor.Coder(idl).accounts.decode(name, data);
+      assert.equal(upgraded.futureField.toNumber(), 0);
+      for (const field of fields.filter(f => f !== reserved && f.name !== 'futureField')) {
+        assert.deepEqual(upgraded[field.name], account[field.name]);
+      }
+    }
+
+    // Only accounts in the legacy layout can be migrated
+    try {
+      await program.rpc.migrateMultisig({
+        accounts: {
+          payer: wallet.publicKey,
+          multisig: msKey,
+          systemProgram: web3.SystemProgram.programId,
+        },
+      });
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /already uses the current layout/);
+    }
+    try {
+      await program.rpc.migrateTransaction({
+        accounts: {
+          payer: wallet.publicKey,
+          multisig: msKey,
+          transaction: txKey,
+          systemProgram: web3.SystemProgram.programId,
+        },
+      });
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /already uses the current layout/);
+    }
+  });
+
+  it('delegateApproval', async () => {
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey], 2, 0);
+    const delegate = (owner, to, until) => program.rpc.delegateApproval(to, new BN(until), {
+      accounts: {
+        signer: owner.publicKey,
+        multisig: msKey,
+      },
+      signers: [owner],
+    });
+    const until = Math.floor(Date.now() / 1000) + 600;
+
+    // Owners can't be delegates, their approval would count twice
+    try {
+      await delegate(ownerB, ownerA.publicKey, until);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /Delegate must be a new key/);
+    }
+
+    const ix = program.instruction.changeMaxPending(bn(5, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const txKey = await createTransaction(ix, msKey);
+    try {
+      await approve(txKey, msKey, ownerC);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /not part of this multisig/);
+    }
+
+    // ownerC approves in place of the absent ownerB
+    await delegate(ownerB, ownerC.publicKey, until);
+    await approve(txKey, msKey, ownerC);
+    const tx = await program.account.transaction.fetch(txKey);
+    assert.deepEqual(tx.signers, [true, true]);
+    await executeStored(txKey, msKey);
+
+    // Revoked delegations no longer work
+    await delegate(ownerB, web3.SystemProgram.programId, 0);
+    const nextKey = await createTransaction(ix, msKey);
+    try {
+      await approve(nextKey, msKey, ownerC);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /not part of this multisig/);
+    }
+  });
+
+  it('reclaimInvalidated', async () => {
+    const connection = program.provider.connection;
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey], 1, 1);
+    const ix = program.instruction.changeMaxPending(bn(5, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const txKey = await createTransaction(ix, msKey, ownerB);
+    await approve(txKey, msKey, ownerA);
+    const reclaim = () => program.rpc.reclaimInvalidated({
+      accounts: {
+        signer: ownerA.publicKey,
+        multisig: msKey,
+        transaction: txKey,
+        proposer: ownerB.publicKey,
+      },
+      signers: [ownerA],
+    });
+    try {
+      await reclaim();
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /still valid/);
+    }
+
+    const owners = [ownerA.publicKey, ownerB.publicKey, ownerC.publicKey];
+    await createApproveExecute(program.instruction.setOwners(owners, false, {
+      accounts: {
+        multisig: msKey
+      },
+    }), msKey);
+
+    // Signed by ownerA, but stranded by the owner change
+    const rent = await connection.getBalance(txKey);
+    const before = await connection.getBalance(ownerB.publicKey);
+    await reclaim();
+    assert.equal(await connection.getAccountInfo(txKey), null);
+    assert.equal(await connection.getBalance(ownerB.publicKey), before + rent);
+    const multisig = await program.account.multisig.fetch(msKey);
+    assert.equal(multisig.pendingTransactions.toNumber(), 0);
+  });
+
+  it('executeTransaction grace period', async () => {
+    const msKey = await createMultisig([ownerA.publicKey], 1, 1);
+    const reconfigure = gracePeriod => program.instruction.reconfigure(null, null, bn(gracePeriod, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    try {
+      await createApproveExecute(reconfigure(0), msKey);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /Grace period must be positive/);
+    }
+    await createApproveExecute(reconfigure(5), msKey);
+
+    const ix = program.instruction.changeMaxPending(bn(5, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    // Inside [eta, eta + 5]
+    const txKey = await createTransaction(ix, msKey);
+    await waitForEta(txKey);
+    await executeStored(txKey, msKey);
+
+    // Past it
+    const lateKey = await createTransaction(ix, msKey);
+    await waitForEta(lateKey);
+    await sleep(6000);
+    try {
+      await executeStored(lateKey, msKey);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /grace period has passed/);
+    }
+  });
+
+  it('executeTransaction eta and grace period boundaries', async () => {
+    const msKey = await createMultisig([ownerA.publicKey], 1, 1);
+    await createApproveExecute(program.instruction.reconfigure(null, bn(3, 0), bn(3, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    }), msKey);
+    const ix = program.instruction.changeMaxPending(bn(5, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const onTime = await createTransaction(ix, msKey);
+    const lastSecond = await createTransaction(ix, msKey);
+    const stale = await createTransaction(ix, msKey);
+    const eta = async txKey => (await program.account.transaction.fetch(txKey)).eta.toNumber();
+    const executeAt = async (txKey, time) => {
+      await waitForClock(time);
+      await executeStored(txKey, msKey);
+    };
+    const assertFails = async (promise, error) => {
+      try {
+        await promise;
+        throw new Error('did not throw');
+      } catch (err) {
+        assert.match(err.message, error);
+      }
+    };
+
+    // Exact to the validator clock's second
+    await assertFails(executeAt(onTime, await eta(onTime) - 1), /ETA/);
+    await executeAt(onTime, await eta(onTime));
+    await executeAt(lastSecond, await eta(lastSecond) + 3);
+    await assertFails(executeAt(stale, await eta(stale) + 4), /is stale/);
+  });
+
+  it('createTransactionWithNonce', async () => {
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey], 2, 0);
+    const ix = program.instruction.changeMaxPending(bn(5, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const create = async (owner, nonce) => {
+      const [txKey, txBump] = await pda(['transaction_nonce', msKey, nonce]);
+      await program.rpc.createTransactionWithNonce([ix], new BN(nonce), txBump, false, Buffer.from([]), new BN(0), noTitle, '', false, {
+        accounts: {
+          signer: owner.publicKey,
+          multisig: msKey,
+          transaction: txKey,
+          systemProgram: web3.SystemProgram.programId,
+        },
+        signers: [owner],
+      });
+      return txKey;
+    };
+
+    // Both see the same num_transactions, but don't collide
+    const nonce = Math.floor(Math.random() * 1e9);
+    const [keyA, keyB] = await Promise.all([create(ownerA, nonce), create(ownerB, nonce + 1)]);
+    const txA = await program.account.transaction.fetch(keyA);
+    const txB = await program.account.transaction.fetch(keyB);
+    assert.deepEqual([txA.index.toNumber(), txB.index.toNumber()].sort(), [0, 1]);
+    const multisig = await program.account.multisig.fetch(msKey);
+    assert.equal(multisig.numTransactions.toNumber(), 2);
+
+    // A nonce can only be used once
+    try {
+      await create(ownerA, nonce);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.ok(!err.message.includes('did not throw'));
+    }
+  });
+
+  it('createAuditLog', async () => {
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey], 1, 0);
+    const [auditLog, auditBump] = await pda(['audit_log', msKey]);
+    await program.rpc.createAuditLog(auditBump, {
+      accounts: {
+        signer: ownerB.publicKey,
+        multisig: msKey,
+        auditLog,
+        systemProgram: web3.SystemProgram.programId,
+      },
+      signers: [ownerB],
+    });
+
+    const ixs = [5, 6].map(maxPending => program.instruction.changeMaxPending(bn(maxPending, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    }));
+    const firstKey = await createTransaction(ixs[0], msKey);
+    const secondKey = await createTransaction(ixs[1], msKey);
+
+    // Executions without the log are rejected
+    try {
+      await executeTransaction(ixs[0], firstKey, msKey);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /audit log must be passed/);
+    }
+
+    await executeStored(firstKey, msKey);
+    await executeStored(secondKey, msKey, ownerB);
+    const log = await program.account.auditLog.fetch(auditLog);
+    assert.equal(log.total.toNumber(), 2);
+    assert.deepEqual(log.records.map(r => r.index.toNumber()), [0, 1]);
+    assert.deepEqual(
+      log.records.map(r => r.executor.toString()),
+      [ownerA.publicKey.toString(), ownerB.publicKey.toString()],
+    );
+    assert.deepEqual(log.records.map(r => r.numInstructions), [1, 1]);
+  });
+
+  it('changeThreshold strands pending transactions', async () => {
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey, ownerC.publicKey], 1, 1);
+    const pendingKey = await createTransaction(program.instruction.changeMaxPending(bn(5, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    }), msKey);
+    const raise = confirm => program.instruction.changeThreshold(bn(2, 0), confirm, {
+      accounts: {
+        multisig: msKey
+      },
+      remainingAccounts: [{ pubkey: pendingKey, isSigner: false, isWritable: false }],
+    });
+
+    // The pending transaction has its one approval, raising needs confirmation
+    try {
+      await createApproveExecute(raise(false), msKey);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /strands approved transactions/);
+    }
+    await createApproveExecute(raise(true), msKey);
+    const multisig = await program.account.multisig.fetch(msKey);
+    assert.equal(multisig.threshold.toNumber(), 2);
+    try {
+      await executeStored(pendingKey, msKey);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /Not enough owners signed/);
+    }
+  });
+
+  it('amIOwner', async () => {
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey], 1, 0);
+    const amIOwner = async signer => [...await view('amIOwner', {
+      accounts: {
+        signer: signer.publicKey,
+        multisig: msKey,
+      },
+      signers: [signer],
+    })];
+
+    // true, Some(1)
+    assert.deepEqual(await amIOwner(ownerB), [1, 1, 1, 0, 0, 0]);
+    // false, None
+    assert.deepEqual(await amIOwner(ownerC), [0, 0]);
+  });
+
+  it('executeTransaction requires a signing executor', async () => {
+    const msKey = await createMultisig([ownerA.publicKey], 1, 0);
+    const txKey = await createTransaction(program.instruction.changeMaxPending(bn(2, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    }), msKey);
+    const ix = program.instruction.executeTransaction(false, {
+      accounts: {
+        signer: ownerA.publicKey,
+        multisig: msKey,
+        transaction: txKey,
+      },
+      remainingAccounts: [
+        { pubkey: msKey, isSigner: false, isWritable: true },
+        { pubkey: program.programId, isSigner: false, isWritable: false },
+      ],
+    });
+    ix.keys.find(k => k.pubkey.equals(ownerA.publicKey)).isSigner = false;
+    try {
+      await program.provider.send(new web3.Transaction().add(ix));
+      throw new Error('did not throw');
+    } catch (err) {
+      // AccountNotSigner
+      assert.match(err.message, /0xbc2/);
+    }
+    const tx = await program.account.transaction.fetch(txKey);
+    assert.equal(tx.executedAt.toNumber(), 0);
+  });
+
+  it('executeTransaction idempotent retries', async () => {
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey], 1, 0);
+    const ix = program.instruction.changeMaxPending(bn(6, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const txKey = await createTransaction(ix, msKey);
+    await executeStored(txKey, msKey);
+    const tx = await program.account.transaction.fetch(txKey);
+    const retry = (owner = ownerA) => ({
+      accounts: {
+        signer: owner.publicKey,
+        multisig: msKey,
+        transaction: txKey,
+      },
+      signers: [owner],
+    });
+
+    // The executor retrying gets the original receipt back
+    await program.rpc.executeTransaction(true, retry());
+    const receipt = await view('executeTransaction', true, retry());
+    assert.ok(new web3.PublicKey(receipt.slice(0, 32)).equals(txKey));
+    assert.equal(new BN(receipt.slice(32, 40), 'le').toNumber(), tx.executedAt.toNumber());
+    assert.equal(receipt[40], 1);
+
+    for (const [idempotent, owner] of [[false, ownerA], [true, ownerB]]) {
+      try {
+        await program.rpc.executeTransaction(idempotent, retry(owner));
+        throw new Error('did not throw');
+      } catch (err) {
+        assert.match(err.message, /already been executed/);
+      }
+    }
+  });
+
+  it('executeTransaction stores return data', async () => {
+    const msKey = await createMultisig([ownerA.publicKey], 1, 0);
+    const ixs = [
+      program.instruction.amIOwner({
+        accounts: {
+          signer: msKey,
+          multisig: msKey,
+        },
+      }),
+      // Sets no return data, so the above is kept
+      program.instruction.changeMaxPending(bn(4, 0), {
+        accounts: {
+          multisig: msKey
+        },
+      }),
+    ];
+    const txKey = await createTransaction(ixs, msKey);
+    await executeStored(txKey, msKey);
+
+    const tx = await program.account.transaction.fetch(txKey);
+    // false, None
+    assert.deepEqual([...tx.returnData], [0, 0]);
+  });
+
+  it('negative delays are rejected', async () => {
+    // A negative delay would give proposals an eta in the past, or before
+    // the epoch, so the multisig is never created.
+    for (const delay of [-1, -2e9]) {
+      try {
+        await createMultisig([ownerA.publicKey], 1, delay);
+        throw new Error('did not throw');
+      } catch (err) {
+        assert.match(err.message, /between 0 and 30 days/);
+      }
+    }
+
+    const msKey = await createMultisig([ownerA.publicKey], 1, 1);
+    try {
+      await createApproveExecute(program.instruction.changeDelay(bn(-5, 0), {
+        accounts: {
+          multisig: msKey
+        },
+      }), msKey);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /between 0 and 30 days/);
+    }
+  });
+
+  it('validateProposal', async () => {
+    const msKey = await createMultisig([ownerA.publicKey], 1, 0);
+    const [vault] = await pda(['vault', msKey]);
+    const transfer = web3.SystemProgram.transfer({
+      fromPubkey: vault,
+      toPubkey: ownerB.publicKey,
+      lamports: 1,
+    });
+    const validate = async (ixs, signer = ownerA, hints = Buffer.from([]), minApprovals = 0) => [...await view('validateProposal', ixs, hints, new BN(minApprovals), {
+      accounts: {
+        signer: signer.publicKey,
+        multisig: msKey,
+      },
+      signers: [signer],
+    })];
+    const code = name => {
+      const bytes = Buffer.alloc(4);
+      bytes.writeUInt32LE(program.idl.errors.find(e => e.name === name).code);
+      return [1, ...bytes];
+    };
+
+    const before = await program.account.multisig.fetch(msKey);
+    assert.deepEqual(await validate([transfer]), [0]);
+    const writableSysvar = { ...transfer, keys: [...transfer.keys, { pubkey: web3.SYSVAR_CLOCK_PUBKEY, isSigner: false, isWritable: true }] };
+    assert.deepEqual(await validate([writableSysvar]), code('InvalidSysvarMeta'));
+    assert.deepEqual(await validate([transfer], ownerC), code('InvalidOwner'));
+    assert.deepEqual(await validate([transfer], ownerA, Buffer.from([0, 0])), code('InvalidActionHints'));
+    assert.deepEqual(await validate([transfer], ownerA, Buffer.from([]), 2), code('InvalidThreshold'));
+
+    // Nothing was created
+    const multisig = await program.account.multisig.fetch(msKey);
+    assert.equal(multisig.numTransactions.toNumber(), before.numTransactions.toNumber());
+  });
+
+  it('createMultisigV2 purposes', async () => {
+    const base = web3.Keypair.generate();
+    const create = async purpose => {
+      const seeds = purpose ? ['multisig', base.publicKey, Buffer.from(purpose)] : ['multisig', base.publicKey];
+      const [msKey, msBump] = await pda(seeds);
+      const config = multisigConfig([ownerA.publicKey], 1, 0, { purpose: purpose || noPurpose });
+      await program.rpc.createMultisigV2(config, msBump, {
+        accounts: {
+          signer: wallet.publicKey,
+          base: base.publicKey,
+          multisig: msKey,
+          systemProgram: web3.SystemProgram.programId,
+        },
+        signers: [base],
+      });
+      return msKey;
+    };
+
+    // The default purpose keeps the original address
+    const defaultKey = await create(null);
+    const treasuryKey = await create([...Buffer.from('treasury')]);
+    const opsKey = await create([...Buffer.from('ops\0\0\0\0\0')]);
+    assert.ok(!treasuryKey.equals(opsKey));
+    assert.ok(!treasuryKey.equals(defaultKey));
+    const multisig = await program.account.multisig.fetch(treasuryKey);
+    assert.ok(multisig.base.equals(base.publicKey));
+    assert.equal(Buffer.from(multisig.purpose).toString(), 'treasury');
+
+    // Each signs as its own PDA
+    await confirmSetup(treasuryKey, ownerA);
+    await createApproveExecute(program.instruction.changeMaxPending(bn(3, 0), {
+      accounts: {
+        multisig: treasuryKey
+      },
+    }), treasuryKey);
+    assert.equal((await program.account.multisig.fetch(treasuryKey)).maxPending.toNumber(), 3);
+  });
+
+  it('verifyMultisigPda', async () => {
+    const base = web3.Keypair.generate();
+    const [msKey, msBump] = await pda(['multisig', base.publicKey]);
+    await program.rpc.createMultisig([ownerA.publicKey], bn(1, 0), bn(0), msBump, {
+      accounts: {
+        signer: wallet.publicKey,
+        base: base.publicKey,
+        multisig: msKey,
+        systemProgram: web3.SystemProgram.programId,
+      },
+      signers: [base],
+    });
+    const verify = async base => [...await view('verifyMultisigPda', base, {
+      accounts: {
+        multisig: msKey,
+      },
+    })];
+
+    assert.deepEqual(await verify(base.publicKey), [1]);
+    assert.deepEqual(await verify(web3.Keypair.generate().publicKey), [0]);
+  });
+
+  it('listTransactions', async () => {
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey], 2, 0);
+    const ix = program.instruction.changeMaxPending(bn(5, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const txKeys = [];
+    for (let i = 0; i < 3; i++) {
+      txKeys.push(await createTransaction([ix, ix].slice(0, i + 1), msKey));
+    }
+    await approve(txKeys[1], msKey, ownerB);
+    await executeStored(txKeys[1], msKey);
+
+    const list = async (start, limit) => {
+      const data = await view('listTransactions', new BN(start), limit, {
+        accounts: {
+          multisig: msKey,
+        },
+        remainingAccounts: txKeys.slice().reverse().map(pubkey => ({ pubkey, isSigner: false, isWritable: false })),
+      });
+      // Borsh Vec<TransactionSummary>: u32 length, then 19 bytes per entry
+      const summaries = [];
+      for (let i = 0; i < data.readUInt32LE(0); i++) {
+        const entry = data.slice(4 + i * 19, 4 + (i + 1) * 19);
+        summaries.push({
+          index: Number(entry.readBigUInt64LE(0)),
+          executed: entry[16] === 1,
+          signerCount: entry[17],
+          numInstructions: entry[18],
+        });
+      }
+      return summaries;
+    };
+
+    assert.deepEqual(await list(0, 3), [
+      { index: 0, executed: false, signerCount: 1, numInstructions: 1 },
+      { index: 1, executed: true, signerCount: 2, numInstructions: 2 },
+      { index: 2, executed: false, signerCount: 1, numInstructions: 2 },
+    ]);
+    assert.deepEqual((await list(1, 1)).map(s => s.index), [1]);
+  });
+
+  it('cancelTransaction', async () => {
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey, ownerC.publicKey], 3, 1);
+    const cancel = (txKey, owner) => program.rpc.cancelTransaction({
+      accounts: {
+        signer: owner.publicKey,
+        multisig: msKey,
+        transaction: txKey,
+      },
+      signers: [owner],
+    });
+    const executeWithAll = async ix => {
+      const txKey = await createTransaction(ix, msKey);
+      await approve(txKey, msKey, ownerB);
+      await approve(txKey, msKey, ownerC);
+      await waitForEta(txKey);
+      await executeStored(txKey, msKey);
+    };
+
+    // Cancelling needs the execution threshold by default
+    const ix = program.instruction.changeMaxPending(bn(5, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    let txKey = await createTransaction(ix, msKey);
+    await cancel(txKey, ownerB);
+    let tx = await program.account.transaction.fetch(txKey);
+    assert.equal(tx.cancelledAt.toNumber(), 0);
+
+    await executeWithAll(program.instruction.changeCancelThreshold(bn(1, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    }));
+
+    // A single owner can now stop a proposal that needs all three to execute
+    txKey = await createTransaction(ix, msKey);
+    await approve(txKey, msKey, ownerB);
+    await cancel(txKey, ownerC);
+    tx = await program.account.transaction.fetch(txKey);
+    assert.ok(tx.cancelledAt.toNumber() > 0);
+    try {
+      await approve(txKey, msKey, ownerC);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /Transaction was cancelled/);
+    }
+    await waitForEta(txKey);
+    try {
+      await executeStored(txKey, msKey);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /Transaction was cancelled/);
+    }
+
+    // And the proposer can clean it up despite ownerB's approval
+    await program.rpc.deleteTransaction({
+      accounts: {
+        signer: ownerA.publicKey,
+        multisig: msKey,
+        transaction: txKey,
+      },
+      signers: [ownerA],
+    });
+    assert.equal(await program.provider.connection.getAccountInfo(txKey), null);
+  });
+
+  it('changeOwnerSnapshotTtl', async () => {
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey, ownerC.publicKey], 2, 1);
+    const multisigIx = (name, ...args) => program.instruction[name](...args, {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const approveAndExecute = async txKey => {
+      await approve(txKey, msKey, ownerB);
+      await waitForEta(txKey);
+      await executeStored(txKey, msKey);
+    };
+    await approveAndExecute(await createTransaction(multisigIx('changeOwnerSnapshotTtl', bn(3600, 0)), msKey));
+
+    // Proposed before the owner change, so under the original owners
+    const txKey = await createTransaction(multisigIx('changeMaxPending', bn(9, 0)), msKey);
+    await approveAndExecute(await createTransaction(multisigIx('removeOwner', ownerC.publicKey), msKey));
+    let multisig = await program.account.multisig.fetch(msKey);
+    assert.deepEqual(multisig.owners, [ownerA.publicKey, ownerB.publicKey]);
+    const tx = await program.account.transaction.fetch(txKey);
+    assert.deepEqual(tx.ownerSnapshot, [ownerA.publicKey, ownerB.publicKey, ownerC.publicKey]);
+
+    await approveAndExecute(txKey);
+    multisig = await program.account.multisig.fetch(msKey);
+    assert.equal(multisig.maxPending.toNumber(), 9);
+  });
+
+  it('cancel and freeze votes follow the owner snapshot', async () => {
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey, ownerC.publicKey], 2, 1);
+    const multisigIx = (name, ...args) => program.instruction[name](...args, {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const approveAndExecute = async txKey => {
+      await approve(txKey, msKey, ownerB);
+      await waitForEta(txKey);
+      await executeStored(txKey, msKey);
+    };
+    await approveAndExecute(await createTransaction(multisigIx('changeOwnerSnapshotTtl', bn(3600, 0)), msKey));
+    const txKey = await createTransaction(multisigIx('changeMaxPending', bn(9, 0)), msKey);
+    await approveAndExecute(await createTransaction(multisigIx('removeOwner', ownerC.publicKey), msKey));
+
+    // ownerC was an owner when it was proposed, so still votes on it
+    const vote = (name, owner) => program.rpc[name]({
+      accounts: {
+        signer: owner.publicKey,
+        multisig: msKey,
+        transaction: txKey,
+      },
+      signers: [owner],
+    });
+    await vote('freezeTransaction', ownerC);
+    await vote('freezeTransaction', ownerA);
+    let tx = await program.account.transaction.fetch(txKey);
+    assert.ok(tx.frozen);
+    await vote('cancelTransaction', ownerC);
+    await vote('cancelTransaction', ownerB);
+    tx = await program.account.transaction.fetch(txKey);
+    assert.ok(tx.cancelledAt.toNumber() > 0);
+  });
+
+  it('removeOwner keeps owner snapshots in step', async () => {
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey, ownerC.publicKey], 1, 1);
+    const multisigIx = (name, ...args) => program.instruction[name](...args, {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    await createApproveExecute(multisigIx('changeOwnerSnapshotTtl', bn(3600, 0)), msKey);
+    await createApproveExecute(multisigIx('changePreserveApprovals', true), msKey);
+
+    // The removal drops ownerC's approval slot and snapshot entry together
+    const txKey = await createTransaction(multisigIx('changeMaxPending', bn(9, 0)), msKey);
+    const removeIx = program.instruction.removeOwner(ownerC.publicKey, {
+      accounts: {
+        multisig: msKey
+      },
+      remainingAccounts: [{ pubkey: txKey, isSigner: false, isWritable: true }],
+    });
+    await createApproveExecute(removeIx, msKey);
+    // Further changes put the transaction back on its snapshot
+    await createApproveExecute(multisigIx('removeOwner', ownerB.publicKey), msKey);
+    const tx = await program.account.transaction.fetch(txKey);
+    assert.deepEqual(tx.signers, [true, false]);
+    assert.deepEqual(tx.ownerSnapshot, [ownerA.publicKey, ownerB.publicKey]);
+
+    await waitForEta(txKey);
+    await executeStored(txKey, msKey);
+    const multisig = await program.account.multisig.fetch(msKey);
+    assert.equal(multisig.maxPending.toNumber(), 9);
+  });
+
+  it('revokeApproval of the proposer', async () => {
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey], 2, 0);
+    const ix = program.instruction.changeMaxPending(bn(6, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const txKey = await createTransaction(ix, msKey);
+    let tx = await program.account.transaction.fetch(txKey);
+    assert.deepEqual(tx.signers, [true, false]);
+
+    await program.rpc.revokeApproval({
+      accounts: {
+        signer: ownerA.publicKey,
+        multisig: msKey,
+        transaction: txKey,
+      },
+      signers: [ownerA],
+    });
+    tx = await program.account.transaction.fetch(txKey);
+    assert.deepEqual(tx.signers, [false, false]);
+    assert.equal(tx.approvedAt[0].toNumber(), 0);
+
+    // Still open, but short of the proposer's approval
+    await approve(txKey, msKey, ownerB);
+    try {
+      await executeStored(txKey, msKey, ownerB);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /Not enough owners signed this transaction/);
+    }
+    await approve(txKey, msKey, ownerA);
+    await executeStored(txKey, msKey, ownerB);
+    const multisig = await program.account.multisig.fetch(msKey);
+    assert.equal(multisig.maxPending.toNumber(), 6);
+  });
+
+  it('setProposalCutoff', async () => {
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey], 1, 1);
+    const maxPendingIx = maxPending => program.instruction.changeMaxPending(bn(maxPending, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const oldKey = await createTransaction(maxPendingIx(3), msKey);
+    await sleep(2000);
+    // Far in the future, so now
+    await createApproveExecute(program.instruction.setProposalCutoff(bn(1e12, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    }), msKey);
+
+    for (const attempt of [() => executeStored(oldKey, msKey), () => approve(oldKey, msKey, ownerB)]) {
+      try {
+        await attempt();
+        throw new Error('did not throw');
+      } catch (err) {
+        assert.match(err.message, /before the multisig's proposal cutoff/);
+      }
+    }
+
+    // Newer proposals are unaffected
+    await sleep(1000);
+    await createApproveExecute(maxPendingIx(4), msKey);
+    const multisig = await program.account.multisig.fetch(msKey);
+    assert.equal(multisig.maxPending.toNumber(), 4);
+  });
+
+  it('freezeTransaction', async () => {
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey], 1, 0);
+    const ix = maxPending => program.instruction.changeMaxPending(bn(maxPending, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const frozenKey = await createTransaction(ix(3), msKey);
+    const otherKey = await createTransaction(ix(4), msKey);
+    const vote = (name, txKey) => program.rpc[name]({
+      accounts: {
+        signer: ownerB.publicKey,
+        multisig: msKey,
+        transaction: txKey,
+      },
+      signers: [ownerB],
+    });
+
+    await vote('freezeTransaction', frozenKey);
+    assert.equal((await program.account.transaction.fetch(frozenKey)).frozen, true);
+    const approveFirst = () => program.rpc.approveInstructions(1, {
+      accounts: {
+        signer: ownerB.publicKey,
+        multisig: msKey,
+        transaction: frozenKey,
+      },
+      signers: [ownerB],
+    });
+    const attempts = [
+      () => executeStored(frozenKey, msKey),
+      () => approve(frozenKey, msKey, ownerB),
+      approveFirst,
+    ];
+    for (const attempt of attempts) {
+      try {
+        await attempt();
+        throw new Error('did not throw');
+      } catch (err) {
+        assert.match(err.message, /Transaction is frozen/);
+      }
+    }
+
+    // Other proposals are unaffected
+    await executeStored(otherKey, msKey);
+    assert.equal((await program.account.multisig.fetch(msKey)).maxPending.toNumber(), 4);
+
+    await vote('unfreezeTransaction', frozenKey);
+    await executeStored(frozenKey, msKey);
+    assert.equal((await program.account.multisig.fetch(msKey)).maxPending.toNumber(), 3);
+  });
+
+  it('freeze votes follow owner changes', async () => {
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey, ownerC.publicKey], 2, 1);
+    const govern = async ix => {
+      const txKey = await createTransaction(ix, msKey);
+      await approve(txKey, msKey, ownerB);
+      await waitForEta(txKey);
+      await executeTransaction(ix, txKey, msKey);
+    };
+    const multisigIx = (name, ...args) => program.instruction[name](...args, {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    await govern(multisigIx('changePreserveApprovals', true));
+    await govern(multisigIx('changePreserveApprovalsOnReorder', true));
+
+    const pendingKey = await createTransaction(multisigIx('changeMaxPending', bn(6, 0)), msKey);
+    const freeze = owner => program.rpc.freezeTransaction({
+      accounts: {
+        signer: owner.publicKey,
+        multisig: msKey,
+        transaction: pendingKey,
+      },
+      signers: [owner],
+    });
+    const pendingAccount = [{ pubkey: pendingKey, isSigner: false, isWritable: true }];
+    await freeze(ownerB);
+
+    // A vote moves with its owner, voting again doesn't count twice
+    const reordered = [ownerC.publicKey, ownerA.publicKey, ownerB.publicKey];
+    await govern(program.instruction.setOwners(reordered, false, {
+      accounts: {
+        multisig: msKey
+      },
+      remainingAccounts: pendingAccount,
+    }));
+    await freeze(ownerB);
+    let pending = await program.account.transaction.fetch(pendingKey);
+    assert.deepEqual(pending.freezeVotes, [false, false, true]);
+    assert.equal(pending.frozen, false);
+
+    await govern(program.instruction.removeOwner(ownerA.publicKey, {
+      accounts: {
+        multisig: msKey
+      },
+      remainingAccounts: pendingAccount,
+    }));
+    await sleep(1000);
+    await freeze(ownerB);
+    pending = await program.account.transaction.fetch(pendingKey);
+    assert.deepEqual(pending.freezeVotes, [false, true]);
+    assert.equal(pending.frozen, false);
+
+    await freeze(ownerC);
+    pending = await program.account.transaction.fetch(pendingKey);
+    assert.equal(pending.frozen, true);
+  });
+
+  it('confirmSetup', async () => {
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey], 1, 0, noMigration, []);
+    const ix = program.instruction.changeMaxPending(bn(5, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const createFails = async () => {
+      try {
+        await createTransaction(ix, msKey);
+        throw new Error('did not throw');
+      } catch (err) {
+        assert.match(err.message, /confirm the multisig setup/);
+      }
+    };
+
+    await createFails();
+    await confirmSetup(msKey, ownerA);
+    await createFails();
+    await confirmSetup(msKey, ownerB);
+    const txKey = await createTransaction(ix, msKey);
+    await executeStored(txKey, msKey);
+  });
+
+  it('proposeChangeThreshold', async () => {
+    // ownerC lost their key, ownerA and ownerB lower the threshold
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey, ownerC.publicKey], 2, 1);
+    let multisig = await program.account.multisig.fetch(msKey);
+    const [txKey, txBump] = await pda(['transaction', msKey, multisig.numTransactions.toNumber()]);
+    await program.rpc.proposeChangeThreshold(txBump, bn(1, 0), {
+      accounts: {
+        signer: ownerA.publicKey,
+        multisig: msKey,
+        transaction: txKey,
+        systemProgram: web3.SystemProgram.programId,
+      },
+      signers: [ownerA],
+    });
+    await approve(txKey, msKey, ownerB);
+
+    // Goes through the delay like any other proposal
+    try {
+      await executeStored(txKey, msKey);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /Before transation ETA/);
+    }
+    await waitForEta(txKey);
+    await executeStored(txKey, msKey);
+    multisig = await program.account.multisig.fetch(msKey);
+    assert.equal(multisig.threshold.toNumber(), 1);
+
+    // ownerA alone is now enough
+    await createApproveExecute(program.instruction.changeMaxPending(bn(5, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    }), msKey);
+    multisig = await program.account.multisig.fetch(msKey);
+    assert.equal(multisig.maxPending.toNumber(), 5);
+  });
+
+  it('createTransaction rejects duplicate account metas', async () => {
+    const msKey = await createMultisig([ownerA.publicKey], 1, 0);
+    const ix = program.instruction.changeMaxPending(bn(5, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const other = web3.Keypair.generate().publicKey;
+    ix.keys.push({ pubkey: other, isSigner: false, isWritable: false });
+    ix.keys.push({ pubkey: msKey, isSigner: false, isWritable: false });
+
+    // Rather than silently taking the union of the flags, as the runtime would
+    try {
+      await createTransaction(ix, msKey);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /appears more than once in an instruction/);
+    }
+
+    // Accounts only have to be unique within an instruction
+    ix.keys.pop();
+    await createTransaction([ix, ix], msKey);
+    await createApproveExecute(ix, msKey);
+    const multisig = await program.account.multisig.fetch(msKey);
+    assert.equal(multisig.maxPending.toNumber(), 5);
+  });
+
+  it('changeMaxInstructionDataLen', async () => {
+    const msKey = await createMultisig([ownerA.publicKey], 1, 0);
+    await createApproveExecute(program.instruction.changeMaxInstructionDataLen(16, {
+      accounts: {
+        multisig: msKey
+      },
+    }), msKey);
+    const memo = data => ({
+      programId: new web3.PublicKey('MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr'),
+      keys: [],
+      data: Buffer.alloc(data, 'a'),
+    });
+
+    await createTransaction(memo(16), msKey);
+    try {
+      await createTransaction(memo(17), msKey);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /exceeds the multisig's limit/);
+    }
+  });
+
+  it('createMultisigV2', async () => {
+    const config = {
+      owners: [ownerA.publicKey, ownerB.publicKey],
+      threshold: bn(2, 0),
+      delay: bn(60, 0),
+      gracePeriod: bn(3600, 0),
+      minDelay: bn(30, 0),
+      maxPending: bn(10, 0),
+      approvalTtl: bn(7200, 0),
+      proposalCooldown: bn(5, 0),
+      cancelThreshold: bn(1, 0),
+      approvalReward: bn(1000, 0),
+      maxTotalIncentives: bn(100000, 0),
+      maxInstructionDataLen: 256,
+      minOwners: 2,
+      purpose: noPurpose,
+      migrationAuthority: noMigration,
+    };
+    const create = async config => {
+      const base = web3.Keypair.generate();
+      const [msKey, msBump] = await pda(['multisig', base.publicKey]);
+      await program.rpc.createMultisigV2(config, msBump, {
+        accounts: {
+          signer: wallet.publicKey,
+          base: base.publicKey,
+          multisig: msKey,
+          systemProgram: web3.SystemProgram.programId,
+        },
+        signers: [base],
+      });
+      return msKey;
+    };
+
+    // Nothing is created when any setting is invalid
+    for (const [invalid, error] of [
+      [{ threshold: bn(3, 0) }, /less than or equal to the number of owners/],
+      [{ minDelay: bn(61, 0) }, /between 0 and 30 days/],
+      [{ gracePeriod: bn(0, 0) }, /Grace period must be positive/],
+      [{ minOwners: 3 }, /fewer owners than its minimum/],
+    ]) {
+      try {
+        await create({ ...config, ...invalid });
+        throw new Error('did not throw');
+      } catch (err) {
+        assert.match(err.message, error);
+      }
+    }
+
+    const msKey = await create(config);
+    const multisig = await program.account.multisig.fetch(msKey);
+    assert.deepEqual(multisig.owners, config.owners);
+    for (const field of ['threshold', 'delay', 'gracePeriod', 'minDelay', 'maxPending', 'approvalTtl',
+      'proposalCooldown', 'cancelThreshold', 'approvalReward', 'maxTotalIncentives']) {
+      assert.equal(multisig[field].toNumber(), config[field].toNumber(), field);
+    }
+    assert.equal(multisig.maxInstructionDataLen, 256);
+    assert.equal(multisig.minOwners, 2);
+    assert.ok(multisig.migrationAuthority.equals(noMigration));
+    assert.equal(multisig.confirmed, false);
+  });
+
+  it('exportConfig', async () => {
+    const config = {
+      owners: [ownerA.publicKey, ownerB.publicKey],
+      threshold: bn(1, 0),
+      delay: bn(0, 0),
+      gracePeriod: bn(3600, 0),
+      minDelay: bn(0, 0),
+      maxPending: bn(10, 0),
+      approvalTtl: bn(7200, 0),
+      proposalCooldown: bn(5, 0),
+      cancelThreshold: bn(2, 0),
+      approvalReward: bn(1000, 0),
+      maxTotalIncentives: bn(100000, 0),
+      maxInstructionDataLen: 256,
+      minOwners: 2,
+      purpose: [...Buffer.from('grants\0\0')],
+      migrationAuthority: ownerC.publicKey,
+    };
+    const base = web3.Keypair.generate();
+    const [msKey, msBump] = await pda(['multisig', base.publicKey, Buffer.from(config.purpose)]);
+    await program.rpc.createMultisigV2(config, msBump, {
+      accounts: {
+        signer: wallet.publicKey,
+        base: base.publicKey,
+        multisig: msKey,
+        systemProgram: web3.SystemProgram.programId,
+      },
+      signers: [base],
+    });
+
+    const data = await view('exportConfig', { accounts: { multisig: msKey } });
+    assert.equal(data.readUInt32LE(0), 2);
+    assert.ok(new web3.PublicKey(data.slice(4, 36)).equals(ownerA.publicKey));
+    assert.ok(new web3.PublicKey(data.slice(36, 68)).equals(ownerB.publicKey));
+    const fields = ['threshold', 'delay', 'gracePeriod', 'minDelay', 'maxPending', 'approvalTtl',
+      'proposalCooldown', 'cancelThreshold', 'approvalReward', 'maxTotalIncentives'];
+    fields.forEach((field, i) => {
+      assert.equal(Number(data.readBigInt64LE(68 + i * 8)), config[field].toNumber(), field);
+    });
+    let offset = 68 + fields.length * 8;
+    assert.equal(data.readUInt32LE(offset), 256);
+    assert.equal(data[offset + 4], 2);
+    assert.deepEqual([...data.slice(offset + 5, offset + 13)], config.purpose);
+    assert.ok(new web3.PublicKey(data.slice(offset + 13, offset + 45)).equals(ownerC.publicKey));
+    offset += 45;
+    // Settings outside of the v2 config, untouched
+    assert.equal(Number(data.readBigUInt64LE(offset)), 0);
+    assert.deepEqual([...data.slice(offset + 8, offset + 13)], [0, 0, 0, 0, 0]);
+  });
+
+  it('createTransactionFromTemplate', async () => {
+    const connection = program.provider.connection;
+    const msKey = await createMultisig([ownerA.publicKey], 1, 0);
+    const [vault] = await pda(['vault', msKey]);
+    await airdrop(vault);
+
+    // Vault payment with the recipient and amount left as parameters
+    const payment = web3.SystemProgram.transfer({
+      fromPubkey: vault,
+      toPubkey: web3.SystemProgram.programId,
+      lamports: 0,
+    });
+    const params = [
+      { key: { instructionIndex: 0, keyIndex: 1 } },
+      { data: { instructionIndex: 0, offset: 4, length: 8 } },
+    ];
+    const [templateKey, templateBump] = await pda(['template', msKey, 0]);
+    await program.rpc.createTemplate(new BN(0), templateBump, [payment], params, {
+      accounts: {
+        signer: ownerA.publicKey,
+        multisig: msKey,
+        template: templateKey,
+        systemProgram: web3.SystemProgram.programId,
+      },
+      signers: [ownerA],
+    });
+
+    const propose = async (values) => {
+      const multisig = await program.account.multisig.fetch(msKey);
+      const [txKey, txBump] = await pda(['transaction', msKey, multisig.numTransactions.toNumber()]);
+      await program.rpc.createTransactionFromTemplate(txBump, values, {
+        accounts: {
+          signer: ownerA.publicKey,
+          multisig: msKey,
+          template: templateKey,
+          transaction: txKey,
+          systemProgram: web3.SystemProgram.programId,
+        },
+        signers: [ownerA],
+      });
+      return txKey;
+    };
+    const recipient = web3.Keypair.generate().publicKey;
+    const lamports = Buffer.alloc(8);
+    lamports.writeBigUInt64LE(BigInt(LAMPORTS_PER_SOL / 2));
+    const txKey = await propose(Buffer.concat([recipient.toBuffer(), lamports]));
+    await executeStored(txKey, msKey);
+    assert.equal(await connection.getBalance(recipient), LAMPORTS_PER_SOL / 2);
+
+    try {
+      await propose(recipient.toBuffer());
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /Template parameters don't match the template/);
+    }
+  });
+
+  it('transferProposership', async () => {
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey, ownerC.publicKey], 2, 0);
+    const ix = program.instruction.changeMaxPending(bn(5, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const txKey = await createTransaction(ix, msKey);
+    const transfer = (owner, newProposer) => program.rpc.transferProposership(newProposer, {
+      accounts: {
+        signer: owner.publicKey,
+        multisig: msKey,
+        transaction: txKey,
+      },
+      signers: [owner],
+    });
+
+    // Only the proposer can hand it over, and only to an owner
+    try {
+      await transfer(ownerC, ownerC.publicKey);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /Only the proposer/);
+    }
+    try {
+      await transfer(ownerA, wallet.publicKey);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /not part of this multisig/);
+    }
+
+    // ownerA's approval came with proposing and is withdrawn with it
+    let event;
+    const listener = program.addEventListener('ProposershipTransferred', e => event = e);
+    await transfer(ownerA, ownerB.publicKey);
+    await sleep(1000);
+    await program.removeEventListener(listener);
+    assert.ok(event.oldProposer.equals(ownerA.publicKey));
+    assert.ok(event.newProposer.equals(ownerB.publicKey));
+    assert.ok(event.approvalWithdrawn);
+    const tx = await program.account.transaction.fetch(txKey);
+    assert.ok(tx.proposer.equals(ownerB.publicKey));
+    assert.deepEqual(tx.signers, [false, false, false]);
+    assert.deepEqual(tx.approvalRevoked, [true, false, false]);
+
+    await program.rpc.deleteTransaction({
+      accounts: {
+        signer: ownerB.publicKey,
+        multisig: msKey,
+        transaction: txKey,
+      },
+      signers: [ownerB],
+    });
+    assert.equal(await program.provider.connection.getAccountInfo(txKey), null);
+  });
+
+  it('changeProposalRentSource', async () => {
+    const connection = program.provider.connection;
+    const msKey = await createMultisig([ownerA.publicKey], 1, 1);
+    const ix = program.instruction.changeMaxPending(bn(5, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+
+    // The proposer pays by default
+    let proposerBefore = await connection.getBalance(ownerA.publicKey);
+    let multisigBefore = await connection.getBalance(msKey);
+    let txKey = await createTransaction(ix, msKey);
+    let rent = (await connection.getAccountInfo(txKey)).lamports;
+    assert.ok(await connection.getBalance(ownerA.publicKey) <= proposerBefore - rent);
+    assert.equal(await connection.getBalance(msKey), multisigBefore);
+
+    await createApproveExecute(program.instruction.changeProposalRentSource({ pda: {} }, {
+      accounts: {
+        multisig: msKey
+      },
+    }), msKey);
+    try {
+      await createTransaction(ix, msKey);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /Insufficient funds/);
+    }
+
+    await airdrop(msKey);
+    proposerBefore = await connection.getBalance(ownerA.publicKey);
+    multisigBefore = await connection.getBalance(msKey);
+    txKey = await createTransaction(ix, msKey);
+    rent = (await connection.getAccountInfo(txKey)).lamports;
+    assert.equal(await connection.getBalance(msKey), multisigBefore - rent);
+    assert.ok(await connection.getBalance(ownerA.publicKey) > proposerBefore - rent);
+
+    // Every way of proposing follows the rent source
+    const paidByMultisig = async (propose, txKey) => {
+      multisigBefore = await connection.getBalance(msKey);
+      await propose();
+      rent = (await connection.getAccountInfo(txKey)).lamports;
+      assert.equal(await connection.getBalance(msKey), multisigBefore - rent);
+    };
+    const [nonceTxKey, nonceTxBump] = await pda(['transaction_nonce', msKey, 42]);
+    await paidByMultisig(() => program.rpc.createTransactionWithNonce([ix], new BN(42), nonceTxBump, false, Buffer.from([]), new BN(0), noTitle, '', false, {
+      accounts: {
+        signer: ownerA.publicKey,
+        multisig: msKey,
+        transaction: nonceTxKey,
+        systemProgram: web3.SystemProgram.programId,
+      },
+      signers: [ownerA],
+    }), nonceTxKey);
+    const multisig = await program.account.multisig.fetch(msKey);
+    const [thresholdTxKey, thresholdTxBump] = await pda(['transaction', msKey, multisig.numTransactions.toNumber()]);
+    await paidByMultisig(() => program.rpc.proposeChangeThreshold(thresholdTxBump, bn(1, 0), {
+      accounts: {
+        signer: ownerA.publicKey,
+        multisig: msKey,
+        transaction: thresholdTxKey,
+        systemProgram: web3.SystemProgram.programId,
+      },
+      signers: [ownerA],
+    }), thresholdTxKey);
+  });
+
+  it('changeApprovalTtl', async () => {
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey], 1, 1);
+    await createApproveExecute(program.instruction.changeApprovalTtl(bn(2, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    }), msKey);
+    await createApproveExecute(program.instruction.changeThreshold(bn(2, 0), false, {
+      accounts: {
+        multisig: msKey
+      },
+    }), msKey);
+
+    const ix = program.instruction.changeMaxPending(bn(3, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const txKey = await createTransaction(ix, msKey);
+    await sleep(3000);
+    await approve(txKey, msKey, ownerB);
+
+    // ownerA's approval expired
+    try {
+      await executeTransaction(ix, txKey, msKey);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /Not enough owners signed/);
+    }
+
+    await approve(txKey, msKey, ownerA);
+    await executeTransaction(ix, txKey, msKey);
+    const multisig = await program.account.multisig.fetch(msKey);
+    assert.equal(multisig.maxPending.toNumber(), 3);
+  });
+
+  it('createTransactionPdaFunded', async () => {
+    const connection = program.provider.connection;
+    const ownerD = anchor.web3.Keypair.generate();
+    await airdrop(ownerD.publicKey, 10000);
+    const msKey = await createMultisig([ownerA.publicKey, ownerD.publicKey], 1, 0, noMigration, [ownerA, ownerD]);
+    await airdrop(msKey);
+
+    const ix = program.instruction.changeMaxPending(bn(1, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const multisigBalance = await connection.getBalance(msKey);
+    const multisig = await program.account.multisig.fetch(msKey);
+    const [txKey, txBump] = await pda(['transaction', msKey, multisig.numTransactions.toNumber()]);
+    await program.rpc.createTransactionPdaFunded([ix], txBump, false, Buffer.from([]), new BN(0), noTitle, '', false, {
+      accounts: {
+        signer: ownerD.publicKey,
+        multisig: msKey,
+        transaction: txKey,
+        systemProgram: web3.SystemProgram.programId,
+      },
+      signers: [ownerD],
+    });
+
+    const tx = await program.account.transaction.fetch(txKey);
+    assert.deepEqual(tx.proposer, ownerD.publicKey);
+    assert.deepEqual(tx.signers, [false, true]);
+
+    const txAccount = await connection.getAccountInfo(txKey);
+    const rent = await connection.getMinimumBalanceForRentExemption(txAccount.data.length);
+    assert.equal(txAccount.lamports, rent);
+    assert.equal(await connection.getBalance(msKey), multisigBalance - rent);
+    assert.equal(await connection.getBalance(ownerD.publicKey), 10000);
+  });
+
+  it('amendTransaction', async () => {
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey], 1, 0);
+    const placeholder = {
+      programId: new web3.PublicKey('MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr'),
+      keys: [],
+      data: Buffer.from('placeholder'),
+    };
+    const txKey = await createTransaction(placeholder, msKey);
+
+    const ix = program.instruction.changeMaxPending(bn(4, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const amend = owner => program.rpc.amendTransaction([ix], {
+      accounts: {
+        signer: owner.publicKey,
+        multisig: msKey,
+        transaction: txKey,
+        systemProgram: web3.SystemProgram.programId,
+      },
+      signers: [owner],
+    });
+
+    // Only the proposer can amend
+    try {
+      await amend(ownerB);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /Only the proposer/);
+    }
+
+    await amend(ownerA);
+    const tx = await program.account.transaction.fetch(txKey);
+    assert.equal(tx.instructions.length, 1);
+    assert.ok(tx.instructions[0].programId.equals(program.programId));
+    assert.equal(tx.instructions[0].data.toString(), ix.data.toString());
+
+    await executeTransaction(ix, txKey, msKey);
+    const multisig = await program.account.multisig.fetch(msKey);
+    assert.equal(multisig.maxPending.toNumber(), 4);
+  });
+
+  it('appendInstructions', async () => {
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey], 1, 0);
+    const maxPendingIx = value => program.instruction.changeMaxPending(bn(value, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const txKey = await createTransaction(maxPendingIx(4), msKey);
+    const append = (owner, ix) => program.rpc.appendInstructions([ix], {
+      accounts: {
+        signer: owner.publicKey,
+        multisig: msKey,
+        transaction: txKey,
+        systemProgram: web3.SystemProgram.programId,
+      },
+      signers: [owner],
+    });
+
+    // Only the proposer can append
+    try {
+      await append(ownerB, maxPendingIx(6));
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /Only the proposer/);
+    }
+
+    await append(ownerA, maxPendingIx(6));
+    const tx = await program.account.transaction.fetch(txKey);
+    assert.equal(tx.instructions.length, 2);
+    assert.equal(tx.instructions[1].data.toString(), maxPendingIx(6).data.toString());
+
+    // Not once another owner approved
+    await approve(txKey, msKey, ownerB);
+    try {
+      await append(ownerA, maxPendingIx(8));
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /signed by an owner/);
+    }
+
+    await executeStored(txKey, msKey);
+    const multisig = await program.account.multisig.fetch(msKey);
+    assert.equal(multisig.maxPending.toNumber(), 6);
+  });
+
+  it('setOwners reorder', async () => {
+    const owners = [ownerA.publicKey, ownerB.publicKey, ownerC.publicKey];
+    const msKey = await createMultisig(owners, 1, 1);
+    const pendingIx = program.instruction.changeMaxPending(bn(6, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const pendingKey = await createTransaction(pendingIx, msKey);
+
+    const ix = program.instruction.setOwners([ownerC.publicKey, ownerA.publicKey, ownerB.publicKey], false, {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const txKey = await createTransaction(ix, msKey);
+    await waitForEta(txKey);
+    try {
+      await executeTransaction(ix, txKey, msKey);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /owner set is unchanged/);
+    }
+
+    let multisig = await program.account.multisig.fetch(msKey);
+    assert.equal(multisig.ownersSeqNo.toNumber(), 0);
+    assert.deepEqual(multisig.owners, owners);
+
+    await executeTransaction(pendingIx, pendingKey, msKey);
+    multisig = await program.account.multisig.fetch(msKey);
+    assert.equal(multisig.maxPending.toNumber(), 6);
+  });
+
+  it('setOwners reorder preserving approvals', async () => {
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey, ownerC.publicKey], 1, 1);
+    await createApproveExecute(program.instruction.changePreserveApprovalsOnReorder(true, {
+      accounts: {
+        multisig: msKey
+      },
+    }), msKey);
+    const pendingIx = program.instruction.changeMaxPending(bn(6, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const pendingKey = await createTransaction(pendingIx, msKey);
+    await approve(pendingKey, msKey, ownerB);
+
+    const reordered = [ownerC.publicKey, ownerA.publicKey, ownerB.publicKey];
+    const ix = program.instruction.setOwners(reordered, false, {
+      accounts: {
+        multisig: msKey
+      },
+      remainingAccounts: [{ pubkey: pendingKey, isSigner: false, isWritable: true }],
+    });
+    const txKey = await createTransaction(ix, msKey);
+    await waitForEta(txKey);
+    await executeTransaction(ix, txKey, msKey);
+
+    const multisig = await program.account.multisig.fetch(msKey);
+    assert.deepEqual(multisig.owners, reordered);
+    assert.equal(multisig.ownersSeqNo.toNumber(), 1);
+
+    // ownerA and ownerB approvals moved to their new indices
+    const pending = await program.account.transaction.fetch(pendingKey);
+    assert.deepEqual(pending.signers, [false, true, true]);
+    assert.equal(pending.ownersSeqNo.toNumber(), 1);
+    await executeStored(pendingKey, msKey);
+    assert.equal((await program.account.multisig.fetch(msKey)).maxPending.toNumber(), 6);
+  });
+
+  it('setCallback', async () => {
+    // Memo v1 stands in for a callback program, it accepts any accounts
+    const memoProgram = new web3.PublicKey('Memo1UhkJRfHyvLMcVucJwxXeuD728EqVDDwQDxFMNo');
+    const msKey = await createMultisig([ownerA.publicKey], 1, 0);
+    const ix = program.instruction.changeMaxPending(bn(8, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const txKey = await createTransaction(ix, msKey);
+
+    const setCallback = programId => program.rpc.setCallback(programId, Buffer.from('executed'), {
+      accounts: {
+        signer: ownerA.publicKey,
+        multisig: msKey,
+        transaction: txKey,
+        systemProgram: web3.SystemProgram.programId,
+      },
+      signers: [ownerA],
+    });
+
+    // The multisig program can't be its own callback
+    try {
+      await setCallback(program.programId);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /Callback program can't be/);
+    }
+    await setCallback(memoProgram);
+
+    const sig = await program.rpc.executeTransaction(false, {
+      accounts: {
+        signer: ownerA.publicKey,
+        multisig: msKey,
+        transaction: txKey,
+      },
+      remainingAccounts: [
+        { pubkey: msKey, isSigner: false, isWritable: true },
+        { pubkey: program.programId, isSigner: false, isWritable: false },
+        { pubkey: memoProgram, isSigner: false, isWritable: false },
+      ],
+      signers: [ownerA],
+    });
+
+    const result = await program.provider.connection.getTransaction(sig, { commitment: 'confirmed' });
+    const logs = result.meta.logMessages.join('\n');
+    const invoked = logs.indexOf(`Program ${memoProgram.toString()} invoke [2]`);
+    assert.ok(invoked > logs.indexOf('Instruction: ChangeMaxPending'));
+    const multisig = await program.account.multisig.fetch(msKey);
+    assert.equal(multisig.maxPending.toNumber(), 8);
+  });
+
+  it('executeTransaction logs a compute estimate', async () => {
+    const msKey = await createMultisig([ownerA.publicKey], 1, 0);
+    const memo = text => ({
+      programId: new web3.PublicKey('MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr'),
+      keys: [],
+      data: Buffer.from(text),
+    });
+    const executeLogs = async txKey => {
+      const sig = await executeStored(txKey, msKey);
+      const result = await program.provider.connection.getTransaction(sig, { commitment: 'confirmed' });
+      return result.meta.logMessages.join('\n');
+    };
+
+    let logs = await executeLogs(await createTransaction(memo('one'), msKey));
+    assert.ok(logs.includes('Estimated compute units: 45030'));
+    assert.ok(!logs.includes('likely exceeds the default'));
+
+    const many = Array.from({ length: 9 }, (_, i) => memo(`memo ${i}`));
+    logs = await executeLogs(await createTransaction(many, msKey));
+    assert.ok(logs.includes('likely exceeds the default 200000 compute units'));
+  });
+
+  it('createTransaction by a removed owner', async () => {
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey], 1, 1);
+    const removeIx = program.instruction.removeOwner(ownerB.publicKey, {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const removeKey = await createTransaction(removeIx, msKey);
+    await waitForEta(removeKey);
+
+    // The removal lands right before ownerB's proposal
+    const ix = program.instruction.changeMaxPending(bn(5, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    await executeStored(removeKey, msKey);
+    try {
+      await createTransaction(ix, msKey, ownerB);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /not part of this multisig/);
+    }
+    const multisig = await program.account.multisig.fetch(msKey);
+    assert.deepEqual(multisig.owners, [ownerA.publicKey]);
+    assert.equal(multisig.numTransactions.toNumber(), 1);
+  });
+
+  it('removeOwner preserving approvals', async () => {
+    const owners = [ownerA.publicKey, ownerB.publicKey, ownerC.publicKey];
+    const msKey = await createMultisig(owners, 1, 1);
+    await createApproveExecute(program.instruction.changePreserveApprovals(true, {
+      accounts: {
+        multisig: msKey
+      },
+    }), msKey);
+    await createApproveExecute(program.instruction.changeThreshold(bn(2, 0), false, {
+      accounts: {
+        multisig: msKey
+      },
+    }), msKey);
+
+    const pendingIx = program.instruction.changeMaxPending(bn(9, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const pendingKey = await createTransaction(pendingIx, msKey);
+    await approve(pendingKey, msKey, ownerB);
+    await approve(pendingKey, msKey, ownerC);
+    // Short of the cancel threshold, dropped with its owner
+    await program.rpc.cancelTransaction({
+      accounts: {
+        signer: ownerC.publicKey,
+        multisig: msKey,
+        transaction: pendingKey,
+      },
+      signers: [ownerC],
+    });
+
+    const ix = program.instruction.removeOwner(ownerC.publicKey, {
+      accounts: {
+        multisig: msKey
+      },
+      remainingAccounts: [{ pubkey: pendingKey, isSigner: false, isWritable: true }],
+    });
+    const txKey = await createTransaction(ix, msKey);
+    await approve(txKey, msKey, ownerB);
+    await waitForEta(txKey);
+    await executeTransaction(ix, txKey, msKey);
+
+    let multisig = await program.account.multisig.fetch(msKey);
+    assert.deepEqual(multisig.owners, [ownerA.publicKey, ownerB.publicKey]);
+    const pending = await program.account.transaction.fetch(pendingKey);
+    assert.deepEqual(pending.signers, [true, true]);
+    assert.deepEqual(pending.cancelVotes, [false, false]);
+    assert.equal(pending.ownersSeqNo.toNumber(), multisig.ownersSeqNo.toNumber());
+
+    await executeTransaction(pendingIx, pendingKey, msKey);
+    multisig = await program.account.multisig.fetch(msKey);
+    assert.equal(multisig.maxPending.toNumber(), 9);
+  });
+
+  it('pdaAvailableBalance', async () => {
+    const connection = program.provider.connection;
+    const msKey = await createMultisig([ownerA.publicKey], 1, 0);
+    await airdrop(msKey);
+
+    const data = await view('pdaAvailableBalance', {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const account = await connection.getAccountInfo(msKey);
+    const rent = await connection.getMinimumBalanceForRentExemption(account.data.length);
+    assert.equal(new BN(data, 'le').toNumber(), LAMPORTS_PER_SOL);
+    assert.equal(account.lamports - rent, LAMPORTS_PER_SOL);
+  });
+
+  it('setPrecondition', async () => {
+    const msKey = await createMultisig([ownerA.publicKey], 1, 0);
+    const watchedKey = await createMultisig([ownerB.publicKey], 1, 0);
+    const ix = program.instruction.changeMaxPending(bn(3, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const txKey = await createTransaction(ix, msKey);
+
+    // Wait for the watched multisig's num_transactions to be above 0
+    await program.rpc.setPrecondition({
+      account: watchedKey,
+      offset: 8 + 32 + 1 + 8 + 8 + 8,
+      comparison: { greaterThan: {} },
+      value: new BN(0),
+    }, {
+      accounts: {
+        signer: ownerA.publicKey,
+        multisig: msKey,
+        transaction: txKey,
+      },
+      signers: [ownerA],
+    });
+    try {
+      await executeStored(txKey, msKey);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /precondition not met/);
+    }
+
+    await createTransaction(ix, watchedKey, ownerB);
+    await executeStored(txKey, msKey);
+    const multisig = await program.account.multisig.fetch(msKey);
+    assert.equal(multisig.maxPending.toNumber(), 3);
+  });
+
+  it('estimateSpace', async () => {
+    const connection = program.provider.connection;
+    const msKey = await createMultisig([ownerA.publicKey], 1, 0);
+    const ix = program.instruction.changeMaxPending(bn(3, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const txKey = await createTransaction(ix, msKey);
+
+    const data = await view('estimateSpace', [ix], 0, { accounts: { multisig: msKey } });
+    const multisigAccount = await connection.getAccountInfo(msKey);
+    const txAccount = await connection.getAccountInfo(txKey);
+    assert.equal(new BN(data.slice(0, 8), 'le').toNumber(), multisigAccount.data.length);
+    assert.equal(new BN(data.slice(8, 16), 'le').toNumber(), txAccount.data.length);
+  });
+
+  it('estimateSpace counts the memo and owner snapshot', async () => {
+    const connection = program.provider.connection;
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey], 1, 1);
+    await createApproveExecute(program.instruction.changeOwnerSnapshotTtl(bn(3600, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    }), msKey);
+    const ix = program.instruction.changeMaxPending(bn(3, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const memo = 'Quarterly max pending review';
+    const multisig = await program.account.multisig.fetch(msKey);
+    const [txKey, txBump] = await pda(['transaction', msKey, multisig.numTransactions.toNumber()]);
+    await program.rpc.createTransaction([ix], txBump, false, Buffer.from([]), new BN(0), noTitle, memo, false, {
+      accounts: {
+        signer: ownerA.publicKey,
+        multisig: msKey,
+        transaction: txKey,
+        systemProgram: web3.SystemProgram.programId,
+      },
+      signers: [ownerA],
+    });
+
+    const data = await view('estimateSpace', [ix], memo.length, { accounts: { multisig: msKey } });
+    const txAccount = await connection.getAccountInfo(txKey);
+    assert.equal(new BN(data.slice(8, 16), 'le').toNumber(), txAccount.data.length);
+  });
+
+  it('transactionSigners', async () => {
+    const owners = [ownerA.publicKey, ownerB.publicKey, ownerC.publicKey];
+    const msKey = await createMultisig(owners, 2, 0);
+    const ix = program.instruction.changeMaxPending(bn(1, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const txKey = await createTransaction(ix, msKey);
+
+    const data = await view('transactionSigners', {
+      accounts: {
+        multisig: msKey,
+        transaction: txKey,
+      },
+    });
+    // Borsh Vec<(Pubkey, bool)>: u32 length, then 33 bytes per entry
+    assert.equal(data.readUInt32LE(0), owners.length);
+    owners.forEach((owner, i) => {
+      const offset = 4 + i * 33;
+      assert.ok(new web3.PublicKey(data.slice(offset, offset + 32)).equals(owner));
+      assert.equal(data[offset + 32], i == 0 ? 1 : 0);
+    });
+  });
+
+  it('ownerSpend', async () => {
+    const connection = program.provider.connection;
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey], 1, 1);
+    await airdrop(msKey);
+    await createApproveExecute(program.instruction.setAllowance(ownerB.publicKey, new BN(1000), {
+      accounts: {
+        multisig: msKey
+      },
+    }), msKey);
+
+    const spend = amount => program.rpc.ownerSpend(new BN(amount), {
+      accounts: {
+        signer: ownerB.publicKey,
+        multisig: msKey,
+        to: ownerC.publicKey,
+      },
+      signers: [ownerB],
+    });
+
+    const balance = await connection.getBalance(ownerC.publicKey);
+    await spend(600);
+    assert.equal(await connection.getBalance(ownerC.publicKey), balance + 600);
+    const multisig = await program.account.multisig.fetch(msKey);
+    assert.deepEqual(multisig.allowanceSpent.map(n => n.toNumber()), [0, 600]);
+
+    // Can't spend over the remaining allowance
+    try {
+      await spend(500);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /exceeds the owner's remaining allowance/);
+    }
+
+    // Granting an allowance moves funds, so it needs the governance delay
+    const instantKey = await createMultisig([ownerA.publicKey, ownerB.publicKey], 1, 0);
+    try {
+      await createTransaction(program.instruction.setAllowance(ownerB.publicKey, new BN(1000), {
+        accounts: {
+          multisig: instantKey
+        },
+      }), instantKey);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /Delay is below the minimum/);
+    }
+  });
+
+  it('proposeOwnerChange', async () => {
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey], 1, 2);
+    const accounts = owner => ({
+      accounts: {
+        signer: owner.publicKey,
+        multisig: msKey,
+      },
+      signers: [owner],
+    });
+    const proposeIx = (owners, threshold) => program.instruction.proposeOwnerChange(owners, bn(threshold, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+
+    await createApproveExecute(proposeIx([ownerB.publicKey, ownerC.publicKey], 2), msKey);
+    let multisig = await program.account.multisig.fetch(msKey);
+    assert.deepEqual(multisig.pendingOwners, [ownerB.publicKey, ownerC.publicKey]);
+    assert.equal(multisig.pendingThreshold.toNumber(), 2);
+
+    // Can't confirm before the delay elapsed
+    try {
+      await program.rpc.confirmOwnerChange(accounts(ownerB));
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /Before transation ETA/);
+    }
+
+    // Nor replace the pending change
+    const ix = proposeIx([ownerA.publicKey], 1);
+    const txKey = await createTransaction(ix, msKey);
+    await waitForEta(txKey);
+    try {
+      await executeTransaction(ix, txKey, msKey);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /already pending/);
+    }
+
+    await sleep(3000);
+    await program.rpc.confirmOwnerChange(accounts(ownerB));
+    multisig = await program.account.multisig.fetch(msKey);
+    assert.deepEqual(multisig.owners, [ownerB.publicKey, ownerC.publicKey]);
+    assert.equal(multisig.threshold.toNumber(), 2);
+    assert.equal(multisig.ownersSeqNo.toNumber(), 1);
+    assert.equal(multisig.pendingOwnersEta.toNumber(), 0);
+  });
+
+  it('proposeOwnerChange needs the threshold', async () => {
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey], 2, 1);
+    const ix = program.instruction.proposeOwnerChange([ownerA.publicKey], bn(1, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+
+    // ownerA can't sign for the multisig
+    try {
+      await program.rpc.proposeOwnerChange([ownerA.publicKey], bn(1, 0), {
+        accounts: {
+          multisig: msKey
+        },
+        signers: [ownerA],
+      });
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /signature/i);
+    }
+
+    // And a proposal only ownerA approved doesn't execute
+    const txKey = await createTransaction(ix, msKey);
+    await waitForEta(txKey);
+    try {
+      await executeTransaction(ix, txKey, msKey);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /Not enough owners signed/);
+    }
+    const multisig = await program.account.multisig.fetch(msKey);
+    assert.equal(multisig.pendingOwnersEta.toNumber(), 0);
+    assert.deepEqual(multisig.owners, [ownerA.publicKey, ownerB.publicKey]);
+  });
+
+  it('abortOwnerChange', async () => {
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey], 1, 2);
+    const accounts = owner => ({
+      accounts: {
+        signer: owner.publicKey,
+        multisig: msKey,
+      },
+      signers: [owner],
+    });
+
+    await createApproveExecute(program.instruction.proposeOwnerChange([ownerA.publicKey], bn(1, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    }), msKey);
+    await program.rpc.abortOwnerChange(accounts(ownerB));
+    await sleep(3000);
+
+    try {
+      await program.rpc.confirmOwnerChange(accounts(ownerA));
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /no pending owner change/);
+    }
+    const multisig = await program.account.multisig.fetch(msKey);
+    assert.deepEqual(multisig.owners, [ownerA.publicKey, ownerB.publicKey]);
+  });
+
+  it('recover', async () => {
+    const ownerD = anchor.web3.Keypair.generate();
+    const msKey = await createMultisig([ownerA.publicKey, ownerB.publicKey], 2, 1);
+    const ix = program.instruction.setRecovery(ownerC.publicKey, bn(2, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    const txKey = await createTransaction(ix, msKey);
+    await approve(txKey, msKey, ownerB);
+    await waitForEta(txKey);
+    await executeTransaction(ix, txKey, msKey);
+
+    const recover = () => program.rpc.recover(bn(1, 0), ownerB.publicKey, ownerD.publicKey, {
+      accounts: {
+        signer: ownerC.publicKey,
+        multisig: msKey,
+      },
+      signers: [ownerC],
+    });
+
+    // Can't recover while the multisig is active
+    try {
+      await recover();
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /still active/);
+    }
+
+    await sleep(3000);
+    await recover();
+    const multisig = await program.account.multisig.fetch(msKey);
+    assert.deepEqual(multisig.owners, [ownerA.publicKey, ownerD.publicKey]);
+    assert.equal(multisig.threshold.toNumber(), 1);
+    assert.equal(multisig.ownersSeqNo.toNumber(), 1);
+
+    // Recovery can only be used once per configuration
+    try {
+      await recover();
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /Owners changed/);
+    }
+  });
+
+  it('createTransaction instruction too large', async () => {
+    const msKey = await createMultisig([ownerA.publicKey], 1, 0);
+    const ix = {
+      programId: program.programId,
+      keys: [],
+      data: Buffer.alloc(1300),
+    };
+    // A single transaction can't carry such an instruction either, so this
+    // may be refused before reaching the program.
+    try {
+      await createTransaction(ix, msKey);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /too large/i);
+    }
+  });
+
+  it('createTransaction logs every violation', async () => {
+    const msKey = await createMultisig([ownerA.publicKey], 1, 0);
+    await createApproveExecute(program.instruction.changeMaxInstructionDataLen(24, {
+      accounts: {
+        multisig: msKey
+      },
+    }), msKey);
+    const [vault] = await pda(['vault', msKey]);
+    const writableSysvar = web3.SystemProgram.transfer({
+      fromPubkey: vault,
+      toPubkey: ownerB.publicKey,
+      lamports: 1,
+    });
+    writableSysvar.keys.push({ pubkey: web3.SYSVAR_CLOCK_PUBKEY, isSigner: false, isWritable: true });
+    const ixs = [
+      // Privileged, but the multisig has no delay
+      program.instruction.changeThreshold(bn(1, 0), false, {
+        accounts: {
+          multisig: msKey
+        },
+      }),
+      { programId: program.programId, keys: [], data: Buffer.alloc(32) },
+      writableSysvar,
+    ];
+
+    const multisig = await program.account.multisig.fetch(msKey);
+    const [txKey, txBump] = await pda(['transaction', msKey, multisig.numTransactions.toNumber()]);
+    const tx = new web3.Transaction().add(
+      program.instruction.createTransaction(ixs, txBump, false, Buffer.from([]), new BN(0), noTitle, '', false, {
+        accounts: {
+          signer: ownerA.publicKey,
+          multisig: msKey,
+          transaction: txKey,
+          systemProgram: web3.SystemProgram.programId,
+        },
+      }),
+    );
+    const { value } = await program.provider.connection.simulateTransaction(tx, [ownerA]);
+    assert.ok(value.err);
+    const logs = value.logs.join('\n');
+    assert.ok(logs.includes('Instruction 0 rejected: Delay is below the minimum'));
+    assert.ok(logs.includes('Instruction 1 rejected: Instruction data exceeds'));
+    assert.ok(logs.includes('Instruction 2 rejected: Sysvars can only be passed read-only'));
+  });
+
+  it('proposeNonceCreate', async () => {
+    const connection = program.provider.connection;
+    const msKey = await createMultisig([ownerA.publicKey], 1, 0);
+    const lamports = await connection.getMinimumBalanceForRentExemption(web3.NONCE_ACCOUNT_LENGTH);
+    const nonceKey = await web3.PublicKey.createWithSeed(msKey, 'nonce', web3.SystemProgram.programId);
+
+    let multisig = await program.account.multisig.fetch(msKey);
+    let [txKey, txBump] = await pda(['transaction', msKey, multisig.numTransactions.toNumber()]);
+    await program.rpc.proposeNonceCreate(txBump, ownerA.publicKey, 'nonce', new BN(lamports), {
+      accounts: {
+        signer: ownerA.publicKey,
+        multisig: msKey,
+        transaction: txKey,
+        systemProgram: web3.SystemProgram.programId,
+      },
+      signers: [ownerA],
+    });
+    await executeStored(txKey, msKey);
+
+    const nonce = await connection.getAccountInfo(nonceKey);
+    assert.ok(nonce.owner.equals(web3.SystemProgram.programId));
+    const nonceAccount = web3.NonceAccount.fromAccountData(nonce.data);
+    assert.ok(nonceAccount.authorizedPubkey.equals(msKey));
+
+    multisig = await program.account.multisig.fetch(msKey);
+    [txKey, txBump] = await pda(['transaction', msKey, multisig.numTransactions.toNumber()]);
+    await program.rpc.proposeNonceAdvance(txBump, nonceKey, {
+      accounts: {
+        signer: ownerA.publicKey,
+        multisig: msKey,
+        transaction: txKey,
+        systemProgram: web3.SystemProgram.programId,
+      },
+      signers: [ownerA],
+    });
+    const tx = await program.account.transaction.fetch(txKey);
+    assert.ok(tx.instructions[0].programId.equals(web3.SystemProgram.programId));
+    assert.ok(tx.instructions[0].keys[0].pubkey.equals(nonceKey));
+  });
+
+  it('governance proposals require a delay', async () => {
+    const msKey = await createMultisig([ownerA.publicKey], 1, 0);
+    const ix = program.instruction.changeThreshold(bn(1, 0), false, {
+      accounts: {
+        multisig: msKey
+      },
+    });
+    try {
+      await createTransaction(ix, msKey);
+      throw new Error('did not throw');
+    } catch (err) {
+      assert.match(err.message, /Delay is below the minimum/);
+    }
+
+    // Other instructions don't need one
+    await createApproveExecute(program.instruction.changeMaxPending(bn(1, 0), {
+      accounts: {
+        multisig: msKey
+      },
+    }), msKey);
+  });
+});
+
+// Creates a multisig and confirms its setup with the owners in `keypairs`.
+async function createMultisig(owners, threshold, delay, migrationAuthority = noMigration, keypairs = [ownerA, ownerB, ownerC]) {
+  const base = anchor.web3.Keypair.generate();
+  const [msKey, msBump] = await pda(['multisig', base.publicKey]);
+  const accounts = {
+    signer: wallet.publicKey,
+    base: base.publicKey,
+    multisig: msKey,
+    systemProgram: anchor.web3.SystemProgram.programId,
+  };
+  // Only v2 takes a migration authority
+  if (migrationAuthority.equals(noMigration)) {
+    await program.rpc.createMultisig(owners, bn(threshold, 0), bn(delay, 0), msBump, {
+      accounts,
+      signers: [base],
+    });
+  } else {
+    const config = multisigConfig(owners, threshold, delay, { migrationAuthority });
+    await program.rpc.createMultisigV2(config, msBump, {
+      accounts,
+      signers: [base],
+    });
+  }
+  for (const keypair of keypairs.filter(k => owners.some(o => o.equals(k.publicKey)))) {
+    await confirmSetup(msKey, keypair);
+  }
+  return msKey;
+}
+
+// create_multisig_v2 config matching create_multisig's defaults.
+function multisigConfig(owners, threshold, delay, overrides = {}) {
+  return {
+    owners,
+    threshold: bn(threshold, 0),
+    delay: bn(delay, 0),
+    gracePeriod: bn(14 * 24 * 3600, 0),
+    minDelay: bn(0),
+    maxPending: bn(0),
+    approvalTtl: bn(0),
+    proposalCooldown: bn(0),
+    cancelThreshold: bn(0),
+    approvalReward: bn(0),
+    maxTotalIncentives: bn(0),
+    maxInstructionDataLen: 0,
+    minOwners: 1,
+    purpose: noPurpose,
+    migrationAuthority: noMigration,
+    ...overrides,
+  };
+}
+
+async function confirmSetup(multisigKey, owner) {
+  await program.rpc.confirmSetup({
+    accounts: {
+      signer: owner.publicKey,
+      multisig: multisigKey,
+    },
+    signers: [owner],
+  });
+}
+
+async function createTransaction(ix, multisigKey = key, owner = ownerA, minApprovals = 0) {
+  let multisig = await program.account.multisig.fetch(multisigKey);
+  const [txKey, txBump] = await pda(['transaction', multisigKey, multisig.numTransactions.toNumber()]);
+  const ixs = Array.isArray(ix) ? ix : [ix];
+  await program.rpc.createTransaction(ixs, txBump, false, Buffer.from([]), new BN(minApprovals), noTitle, '', false, {
+    accounts: {
+      signer: owner.publicKey,
+      multisig: multisigKey,
+      transaction: txKey,
+      systemProgram: web3.SystemProgram.programId,
+    },
+    signers: [owner],
+  });
+  return txKey;
+}
+
+async function executeTransaction(ix, txKey, multisigKey = key, owner = ownerA) {
+  const remainingAccounts = ix.keys
+    .map(k => Object.assign(k, { isSigner: false })).concat([{
+      pubkey: program.programId,
+      isSigner: false,
+      isWritable: false,
+    }])
+  await program.rpc.executeTransaction(false, {
+    accounts: {
+      signer: owner.publicKey,
+      multisig: multisigKey,
+      transaction: txKey,
+    },
+    remainingAccounts,
+    signers: [owner],
+  });
+}
+
+// Executes a stored transaction, passing every account its instructions use.
+async function executeStored(txKey, multisigKey = key, owner = ownerA) {
+  const tx = await program.account.transaction.fetch(txKey);
+  const remainingAccounts = [];
+  for (const ix of tx.instructions) {
+    for (const k of ix.keys) {
+      remainingAccounts.push({ pubkey: k.pubkey, isSigner: false, isWritable: k.isWritable });
+    }
+    remainingAccounts.push({ pubkey: ix.programId, isSigner: false, isWritable: false });
+  }
+  if (tx.precondition) {
+    remainingAccounts.push({ pubkey: tx.precondition.account, isSigner: false, isWritable: false });
+  }
+  if (!tx.dependsOn.equals(new web3.PublicKey(0))) {
+    remainingAccounts.push({ pubkey: tx.dependsOn, isSigner: false, isWritable: false });
+  }
+  if (tx.balanceAssertion) {
+    remainingAccounts.push({ pubkey: tx.balanceAssertion.account, isSigner: false, isWritable: false });
+  }
+  const multisig = await program.account.multisig.fetch(multisigKey);
+  if (!multisig.auditLog.equals(new web3.PublicKey(0))) {
+    remainingAccounts.push({ pubkey: multisig.auditLog, isSigner: false, isWritable: true });
+  }
+  return await program.rpc.executeTransaction(false, {
+    accounts: {
+      signer: owner.publicKey,
+      multisig: multisigKey,
+      transaction: txKey,
+    },
+    remainingAccounts,
+    signers: [owner],
+  });
+}
+
+async function approve(txKey, multisigKey = key, owner = ownerA) {
+  await program.rpc.approve({
+    accounts: {
+      signer: owner.publicKey,
+      multisig: multisigKey,
+      transaction: txKey,
+    },
+    signers: [owner],
+  });
+}
+
+async function createApproveExecute(ix, multisigKey = key) {
+  const txKey = await createTransaction(ix, multisigKey);
+  await waitForEta(txKey);
+  await executeTransaction(ix, txKey, multisigKey);
+  return txKey;
+}
+
+async function airdrop(key, lamports = LAMPORTS_PER_SOL) {
+  const tx = await program.provider.connection.requestAirdrop(
+    key,
+    lamports
+  );
+  await program.provider.connection.confirmTransaction(tx);
+}
+
+async function pda(seeds, programId = program.programId) {
+  for (let i = 0; i < seeds.length; i++) {
+    if (typeof seeds[i] === "number") {
+      const a = Buffer.from(new BN(seeds[i]).toArray().reverse());
+      const b = Buffer.alloc(8);
+      a.copy(b);
+      seeds[i] = b;
+    }
+    if (typeof seeds[i] === "string") {
+      seeds[i] = Buffer.from(seeds[i]);
+    }
+    if (typeof seeds[i].toBuffer == "function") {
+      seeds[i] = seeds[i].toBuffer();
+    }
+  }
+  return await web3.PublicKey.findProgramAddress(seeds, programId);
+}
+
+async function waitForEta(txKey) {
+  const tx = await program.account.transaction.fetch(txKey);
+  const wait = tx.eta.toNumber() * 1000 - Date.now();
+  if (wait > 0) {
+    await sleep(wait + 1000);
+  }
+}
+
+// Waits until the cluster clock reads `time`, in seconds.
+async function waitForClock(time) {
+  const connection = program.provider.connection;
+  for (;;) {
+    const clock = await connection.getAccountInfo(web3.SYSVAR_CLOCK_PUBKEY, 'processed');
+    if (Number(clock.data.readBigInt64LE(32)) >= time) {
+      return;
+    }
+    await sleep(100);
+  }
+}
+
+// Simulates a read-only instruction and returns the data it set with
+// set_return_data.
+async function view(name, ...args) {
+  const { raw } = await program.simulate[name](...args);
+  const prefix = `Program return: ${program.programId.toString()} `;
+  const log = raw.find(l => l.startsWith(prefix));
+  return Buffer.from(log.slice(prefix.length), 'base64');
+}
+
+// 48 title bytes as the program's `Title`
+function titleOf(bytes) {
+  return { head: [...bytes.slice(0, 32)], tail: [...bytes.slice(32, 48)] };
+}
+
+function sleep(ms) {
+  return new Promise(resolve => setTimeout(resolve, ms));
 }
 
 function bn(value, decimals = 9) {